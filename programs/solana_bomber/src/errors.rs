@@ -31,6 +31,15 @@ pub enum GameError {
     #[msg("Invalid referral fee (must be 0-10000)")]
     InvalidReferralFee,
 
+    #[msg("Amount must be greater than zero")]
+    InvalidAmount,
+
+    #[msg("Cannot unstake more than is staked")]
+    InsufficientStake,
+
+    #[msg("Reward pool cannot cover this refund")]
+    InsufficientRewardPool,
+
     // ========== Hero Errors ==========
     #[msg("Invalid hero index")]
     InvalidHeroIndex,
@@ -44,9 +53,79 @@ pub enum GameError {
     #[msg("Hero is already on the map")]
     HeroAlreadyOnMap,
 
-    #[msg("Invalid hero quantity (must be 1-10)")]
+    #[msg("Invalid hero quantity (must be 1 to the configured maximum)")]
     InvalidHeroQuantity,
 
+    #[msg("Hero is already at the maximum level")]
+    MaxHeroLevelReached,
+
+    #[msg("Hero does not have enough XP for the next level")]
+    InsufficientXp,
+
+    #[msg("Hero stats are already at the cap for its rarity")]
+    HeroStatsAtCap,
+
+    #[msg("Fusion needs the configured number of distinct heroes of one rarity")]
+    InvalidFusionHeroes,
+
+    #[msg("Legendary heroes cannot be fused further")]
+    MaxRarityReached,
+
+    #[msg("Invalid fusion config (count 2-10, success 0-10000 bps)")]
+    InvalidFusionConfig,
+
+    #[msg("Invalid hero price config (price > 0, max quantity 1-20)")]
+    InvalidHeroPriceConfig,
+
+    #[msg("Invalid house level config (grids must fit and never shrink on upgrade)")]
+    InvalidHouseLevelConfig,
+
+    #[msg("No hero IDs left for this account")]
+    HeroIdsExhausted,
+
+    #[msg("Token account does not hold an exported hero NFT")]
+    HeroTokenNotHeld,
+
+    #[msg("Invalid hero NFT base URI (1-72 printable ASCII characters, no query or fragment)")]
+    InvalidHeroNftBaseUri,
+
+    #[msg("Hero NFT base URI has not been set")]
+    HeroNftBaseUriNotSet,
+
+    #[msg("Only heroes off the map and grid can be transferred")]
+    HeroNotTransferable,
+
+    #[msg("Heroes cannot be transferred to yourself")]
+    CannotTransferToSelf,
+
+    #[msg("Inventory is full")]
+    InventoryFull,
+
+    #[msg("Hero page account not provided")]
+    HeroPageMissing,
+
+    #[msg("Invalid hero page account")]
+    InvalidHeroPage,
+
+    #[msg("Hero page still holds heroes")]
+    HeroPageNotEmpty,
+
+    #[msg("Inventory must be migrated to hero pages first")]
+    InventoryNotMigrated,
+
+    // ========== Randomness Errors ==========
+    #[msg("A hero mint is already waiting to be revealed")]
+    MintAlreadyPending,
+
+    #[msg("No pending hero mint to reveal")]
+    NoPendingMint,
+
+    #[msg("Randomness for this mint is not available yet")]
+    RandomnessNotAvailable,
+
+    #[msg("Invalid randomness account")]
+    InvalidRandomnessAccount,
+
     // ========== Grid/House Errors ==========
     #[msg("House not initialized - purchase initial house first")]
     HouseNotInitialized,
@@ -102,6 +181,15 @@ pub enum GameError {
     #[msg("Invalid calculation result")]
     InvalidCalculation,
 
+    #[msg("Rewards precision must be non-zero and cannot change after the game starts")]
+    InvalidRewardsPrecision,
+
+    #[msg("Maximum BOMBcoin supply has been reached")]
+    MaxSupplyReached,
+
+    #[msg("Max supply cannot be below the amount already emitted")]
+    InvalidMaxSupply,
+
     // ========== Referral Errors ==========
     #[msg("Referrer has already been set")]
     ReferrerAlreadySet,
@@ -112,10 +200,53 @@ pub enum GameError {
     #[msg("Invalid referrer account")]
     InvalidReferrer,
 
+    #[msg("Referrer token account must be the referrer's associated token account")]
+    InvalidReferrerTokenAccount,
+
+    #[msg("Referrer token account is required when a referrer is set")]
+    MissingReferrerTokenAccount,
+
+    #[msg("Referrer user account is required when a referrer is set")]
+    MissingReferrerAccount,
+
+    #[msg("Referrer has reached the maximum number of referrals")]
+    ReferralListFull,
+
+    #[msg("Referrer chain would loop back to this user")]
+    ReferralCycle,
+
+    #[msg("Referral tiers must be 1-3 contiguous non-zero fees totalling at most 100%")]
+    InvalidReferralTiers,
+
+    // ========== Marketplace Errors ==========
+    #[msg("Listing price must be greater than zero")]
+    InvalidListingPrice,
+
+    #[msg("Only heroes off the map and grid can be listed")]
+    HeroNotListable,
+
+    #[msg("Sellers cannot buy their own listing")]
+    CannotBuyOwnListing,
+
+    #[msg("Invalid marketplace fee (must be 0-10000)")]
+    InvalidMarketplaceFee,
+
+    #[msg("BOMBcoin listings need the buyer, seller and treasury token accounts")]
+    MissingPaymentAccounts,
+
+    #[msg("Payment token account does not belong to the expected owner")]
+    InvalidPaymentAccount,
+
     // ========== Account Errors ==========
     #[msg("Account already initialized")]
     AlreadyInitialized,
 
     #[msg("Invalid account owner")]
     InvalidOwner,
+
+    #[msg("Account is already on the current layout")]
+    AlreadyMigrated,
+
+    #[msg("Account layout version is not supported by this program")]
+    UnknownAccountVersion,
 }
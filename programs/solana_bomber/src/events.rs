@@ -0,0 +1,453 @@
+use anchor_lang::prelude::*;
+use crate::state::{
+    EmissionSchedule, HeroRarity, HouseLevelConfig, ListingCurrency, RewardDestination, MAX_REFERRAL_TIERS,
+};
+
+// ========== Admin Events ==========
+
+/// Global state created
+#[event]
+pub struct GlobalStateInitialized {
+    pub authority: Pubkey,
+    pub dev_treasury: Pubkey,
+    pub reward_token_mint: Pubkey,
+    pub initial_house_price: u64,
+    pub initial_bombcoin_per_block: u64,
+    pub halving_interval: u64,
+    pub burn_pct: u16,
+    pub referral_fee: u16,
+    pub rewards_precision: u64,
+    pub max_supply: u64,
+}
+
+/// Game config changed (only the updated fields are set)
+#[event]
+pub struct ConfigUpdated {
+    pub initial_house_price: Option<u64>,
+    pub initial_bombcoin_per_block: Option<u64>,
+    pub halving_interval: Option<u64>,
+    pub burn_pct: Option<u16>,
+    pub referral_fee: Option<u16>,
+    pub rewards_precision: Option<u64>,
+    pub max_supply: Option<u64>,
+}
+
+#[event]
+pub struct EmissionScheduleSet {
+    pub schedule: EmissionSchedule,
+}
+
+#[event]
+pub struct ReferralTiersSet {
+    pub tier_fees: [u16; MAX_REFERRAL_TIERS],
+}
+
+#[event]
+pub struct FusionConfigUpdated {
+    pub hero_count: u8,
+    pub success_bps: u16,
+    pub cost: u64,
+}
+
+#[event]
+pub struct MarketplaceFeeUpdated {
+    pub fee_bps: u16,
+}
+
+#[event]
+pub struct HeroPriceUpdated {
+    pub price: u64,
+    pub max_quantity: u8,
+}
+
+#[event]
+pub struct HeroNftBaseUriUpdated {
+    pub uri: String,
+}
+
+#[event]
+pub struct HouseLevelUpdated {
+    pub level: u8,
+    pub config: HouseLevelConfig,
+}
+
+#[event]
+pub struct TreasuryUpdated {
+    pub new_treasury: Pubkey,
+}
+
+#[event]
+pub struct GameStarted {
+    pub start_time: i64,
+}
+
+#[event]
+pub struct PauseToggled {
+    pub paused: bool,
+}
+
+#[event]
+pub struct MintingToggled {
+    pub enabled: bool,
+}
+
+#[event]
+pub struct HouseUpgradesToggled {
+    pub enabled: bool,
+}
+
+#[event]
+pub struct TokenFundsWithdrawn {
+    pub treasury_token_account: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct GlobalStateMigrated {
+    pub from_version: u8,
+    pub version: u8,
+    pub cumulative_bombcoin_per_power: u128,
+    pub last_reward_time: i64,
+}
+
+#[event]
+pub struct UserAccountMigrated {
+    pub owner: Pubkey,
+    pub from_version: u8,
+    pub version: u8,
+}
+
+#[event]
+pub struct TokenTreasuryInitialized {
+    pub token_treasury: Pubkey,
+}
+
+#[event]
+pub struct StakePoolInitialized {
+    pub stake_vault: Pubkey,
+}
+
+#[event]
+pub struct RandomnessFeedInitialized {
+    pub feed_authority: Pubkey,
+}
+
+#[event]
+pub struct RandomnessFeedToggled {
+    pub enabled: bool,
+}
+
+#[event]
+pub struct RandomnessPublished {
+    pub sequence: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct TestCoinsMinted {
+    pub user: Pubkey,
+    pub amount: u64,
+}
+
+// ========== Player Events ==========
+
+#[event]
+pub struct HousePurchased {
+    pub user: Pubkey,
+    pub entry_fee: u64,
+    pub grid_width: u8,
+    pub grid_height: u8,
+}
+
+#[event]
+pub struct ReferrerSet {
+    pub user: Pubkey,
+    pub referrer: Pubkey,
+}
+
+/// Coins charged for a hero purchase; heroes are rolled at reveal
+#[event]
+pub struct HeroPurchaseCommitted {
+    pub user: Pubkey,
+    pub quantity: u8,
+    pub total_cost: u64,
+    pub burn_amount: u64,
+    pub treasury_amount: u64,
+    pub target_slot: u64,
+    pub paid_in_tokens: bool,
+}
+
+#[event]
+pub struct HeroMinted {
+    pub user: Pubkey,
+    pub hero_id: u16,
+    pub rarity: HeroRarity,
+    pub skin_id: u8,
+    pub power: u32,
+    pub speed: u32,
+    pub stamina: u32,
+    pub max_hp: u32,
+    pub bomb_number: u8,
+    pub bomb_range: u8,
+    /// HMP in hundredths (HMP_SCALE)
+    pub hmp: u64,
+}
+
+#[event]
+pub struct HeroLeveledUp {
+    pub user: Pubkey,
+    pub hero_id: u16,
+    pub new_level: u8,
+    pub power: u32,
+    pub speed: u32,
+    pub stamina: u32,
+    pub cost: u64,
+}
+
+#[event]
+pub struct HeroRetired {
+    pub user: Pubkey,
+    pub hero_id: u16,
+    pub rarity: HeroRarity,
+    pub refund: u64,
+}
+
+/// Hero gifted to another player; `new_hero_id` is its ID in the receiver's inventory
+#[event]
+pub struct HeroTransferred {
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub hero_id: u16,
+    pub new_hero_id: u16,
+    pub rarity: HeroRarity,
+}
+
+/// Hero left the inventory as a 1-of-1 NFT
+#[event]
+pub struct HeroExported {
+    pub user: Pubkey,
+    pub hero_id: u16,
+    pub mint: Pubkey,
+    pub rarity: HeroRarity,
+}
+
+/// Hero NFT burned and its hero re-inserted (with a new ID)
+#[event]
+pub struct HeroImported {
+    pub user: Pubkey,
+    pub hero_id: u16,
+    pub mint: Pubkey,
+    pub rarity: HeroRarity,
+}
+
+#[event]
+pub struct HeroPageOpened {
+    pub user: Pubkey,
+    pub page_index: u16,
+}
+
+#[event]
+pub struct HeroPageClosed {
+    pub user: Pubkey,
+    pub page_index: u16,
+}
+
+/// Legacy inventory heroes moved into hero pages; `remaining` still await migration
+#[event]
+pub struct InventoryMigrated {
+    pub user: Pubkey,
+    pub moved: u16,
+    pub remaining: u16,
+}
+
+/// Heroes consumed and coins charged for a fusion; resolved at reveal
+#[event]
+pub struct HeroesFused {
+    pub user: Pubkey,
+    pub consumed_rarity: HeroRarity,
+    pub consumed_count: u8,
+    pub target_rarity: HeroRarity,
+    pub cost: u64,
+    pub target_slot: u64,
+}
+
+#[event]
+pub struct FusionResolved {
+    pub user: Pubkey,
+    pub target_rarity: HeroRarity,
+    pub success: bool,
+}
+
+#[event]
+pub struct HeroesRevealed {
+    pub user: Pubkey,
+    pub quantity: u8,
+    /// Commitment was stale, so every hero was rolled Common
+    pub stale: bool,
+}
+
+#[event]
+pub struct HeroPlaced {
+    pub user: Pubkey,
+    pub hero_id: u16,
+    pub x: u8,
+    pub y: u8,
+    pub is_restroom: bool,
+}
+
+#[event]
+pub struct HeroRemovedFromGrid {
+    pub user: Pubkey,
+    pub hero_id: u16,
+    pub x: u8,
+    pub y: u8,
+}
+
+#[event]
+pub struct HeroMovedToMap {
+    pub user: Pubkey,
+    pub hero_id: u16,
+    /// Player power after the move
+    pub player_power: u64,
+}
+
+#[event]
+pub struct HeroRemovedFromMap {
+    pub user: Pubkey,
+    pub hero_id: u16,
+    pub old_power: u64,
+    pub new_power: u64,
+    pub total_hash_power: u64,
+}
+
+#[event]
+pub struct RewardsClaimed {
+    pub user: Pubkey,
+    pub gross_reward: u64,
+    pub net_reward: u64,
+    pub destination: RewardDestination,
+    pub referral_bonus: u64,
+    pub referrer: Option<Pubkey>,
+    pub player_power: u64,
+    pub reward_debt: u128,
+    /// Pending rewards dropped because they exceeded the remaining supply
+    /// (emissions are capped by the same budget, so at most rounding dust)
+    pub forfeited_reward: u64,
+}
+
+/// SPL BOMBcoin burned from a wallet and credited to the in-game balance
+#[event]
+pub struct CoinsDeposited {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub coin_balance: u64,
+}
+
+/// In-game balance debited and minted as SPL BOMBcoin to a wallet
+#[event]
+pub struct CoinsWithdrawn {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub coin_balance: u64,
+}
+
+// ========== Staking Events ==========
+
+#[event]
+pub struct StakeAccountOpened {
+    pub user: Pubkey,
+}
+
+/// SPL BOMBcoin moved from a wallet into the stake vault
+#[event]
+pub struct Staked {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub stake: u64,
+    pub total_staked: u64,
+}
+
+/// Staked BOMBcoin returned from the vault to the wallet
+#[event]
+pub struct Unstaked {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub stake: u64,
+    pub total_staked: u64,
+}
+
+/// Reward pool shared out over the current stakers
+#[event]
+pub struct DividendsDistributed {
+    pub amount: u64,
+    pub total_staked: u64,
+    pub acc_dividend_per_share: u128,
+}
+
+/// Harvested dividends minted to a staker
+#[event]
+pub struct DividendsClaimed {
+    pub user: Pubkey,
+    pub amount: u64,
+}
+
+/// Referral bonus paid to one upline of a claiming user
+#[event]
+pub struct ReferralBonusPaid {
+    pub user: Pubkey,
+    pub referrer: Pubkey,
+    /// 1 = direct referrer
+    pub tier: u8,
+    pub amount: u64,
+}
+
+#[event]
+pub struct HeroListed {
+    pub seller: Pubkey,
+    pub listing: Pubkey,
+    pub hero_id: u16,
+    pub rarity: HeroRarity,
+    pub price: u64,
+    pub currency: ListingCurrency,
+}
+
+#[event]
+pub struct ListingCancelled {
+    pub seller: Pubkey,
+    pub listing: Pubkey,
+    pub hero_id: u16,
+}
+
+/// Listing bought; `hero_id` is the hero's new ID in the buyer's inventory
+#[event]
+pub struct ListingFilled {
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    pub listing: Pubkey,
+    pub hero_id: u16,
+    pub price: u64,
+    pub currency: ListingCurrency,
+    pub burn_amount: u64,
+    pub treasury_amount: u64,
+}
+
+#[event]
+pub struct HpRecovered {
+    pub user: Pubkey,
+    pub hero_id: u16,
+    pub recovered: u32,
+    pub is_restroom: bool,
+}
+
+#[event]
+pub struct HouseUpgraded {
+    pub user: Pubkey,
+    pub new_level: u8,
+    pub grid_width: u8,
+    pub grid_height: u8,
+    pub cost: u64,
+    pub paid_in_tokens: bool,
+    /// Burn/treasury split of a token payment (zero when paid in coins)
+    pub burn_amount: u64,
+    pub treasury_amount: u64,
+}
@@ -0,0 +1,121 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::GameError;
+use crate::state::*;
+use crate::utils::mining_power;
+
+/// A player's hero pages, loaded from an instruction's remaining accounts
+///
+/// Callers pass only the pages holding the heroes an instruction touches, plus
+/// the pages of every mining hero whenever the player's power is recalculated.
+/// Modified pages are persisted with `exit`.
+pub struct HeroPages<'info> {
+    pages: Vec<Account<'info, HeroPage>>,
+    dirty: Vec<bool>,
+}
+
+impl<'info> HeroPages<'info> {
+    /// Load the owner's pages for an instruction that reads or writes heroes
+    /// Heroes still in the legacy inventory must be migrated first
+    pub fn load(user_account: &UserAccount, accounts: &'info [AccountInfo<'info>]) -> Result<Self> {
+        require!(user_account.inventory.is_empty(), GameError::InventoryNotMigrated);
+        Self::load_for(user_account.owner, accounts)
+    }
+
+    /// Load `owner`'s pages without checking the legacy inventory (views and migration)
+    /// Every account must be a distinct hero page; pages of other players are skipped
+    pub fn load_for(owner: Pubkey, accounts: &'info [AccountInfo<'info>]) -> Result<Self> {
+        let mut pages = Vec::with_capacity(accounts.len());
+        for (i, info) in accounts.iter().enumerate() {
+            require!(
+                !accounts[..i].iter().any(|other| other.key == info.key),
+                GameError::InvalidHeroPage
+            );
+
+            let page: Account<'info, HeroPage> = Account::try_from(info)?;
+            if page.owner == owner {
+                pages.push(page);
+            }
+        }
+
+        let dirty = vec![false; pages.len()];
+        Ok(Self { pages, dirty })
+    }
+
+    fn position(&self, hero_id: u16) -> Result<usize> {
+        let index = HeroPage::index_of(hero_id);
+        self.pages
+            .iter()
+            .position(|page| page.index == index)
+            .ok_or_else(|| error!(GameError::HeroPageMissing))
+    }
+
+    /// Whether the page that holds this hero ID was passed in
+    pub fn has_page_for(&self, hero_id: u16) -> bool {
+        self.position(hero_id).is_ok()
+    }
+
+    /// Hero with this ID
+    pub fn hero(&self, hero_id: u16) -> Result<&Hero> {
+        let pos = self.position(hero_id)?;
+        self.pages[pos]
+            .hero(hero_id)
+            .ok_or_else(|| error!(GameError::HeroNotInInventory))
+    }
+
+    /// Mutable hero with this ID (its page is persisted on exit)
+    pub fn hero_mut(&mut self, hero_id: u16) -> Result<&mut Hero> {
+        let pos = self.position(hero_id)?;
+        self.dirty[pos] = true;
+        self.pages[pos]
+            .hero_mut(hero_id)
+            .ok_or_else(|| error!(GameError::HeroNotInInventory))
+    }
+
+    /// Put a hero into the slot for its ID
+    pub fn insert(&mut self, hero: Hero) -> Result<()> {
+        let pos = self.position(hero.id)?;
+        self.dirty[pos] = true;
+        require!(self.pages[pos].insert(hero), GameError::InvalidHeroPage);
+        Ok(())
+    }
+
+    /// Take the hero with this ID out of its page
+    pub fn remove(&mut self, hero_id: u16) -> Result<Hero> {
+        let pos = self.position(hero_id)?;
+        self.dirty[pos] = true;
+        self.pages[pos]
+            .remove(hero_id)
+            .ok_or_else(|| error!(GameError::HeroNotInInventory))
+    }
+
+    /// Heroes on the loaded pages
+    pub fn heroes(&self) -> impl Iterator<Item = &Hero> + use<'_, 'info> {
+        self.pages
+            .iter()
+            .flat_map(|page| page.heroes.iter().flatten())
+    }
+
+    /// Mining power (whole HMP) of mining heroes that still have HP
+    /// Every mining hero's page is required
+    pub fn map_power(&self, active_map: &[u16]) -> Result<u64> {
+        let mut total_hmp = 0u64;
+        for &hero_id in active_map {
+            let hero = self.hero(hero_id)?;
+            if hero.is_active() {
+                total_hmp += hero.calculate_hmp();
+            }
+        }
+        Ok(mining_power(total_hmp))
+    }
+
+    /// Persist the pages that were modified (remaining accounts are not saved automatically)
+    pub fn exit(&self) -> Result<()> {
+        for (page, &dirty) in self.pages.iter().zip(self.dirty.iter()) {
+            if dirty {
+                page.exit(&crate::ID)?;
+            }
+        }
+        Ok(())
+    }
+}
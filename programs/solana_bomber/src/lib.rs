@@ -1,13 +1,28 @@
 use anchor_lang::prelude::*;
+use anchor_spl::associated_token::{get_associated_token_address, AssociatedToken};
+use anchor_spl::metadata::mpl_token_metadata::types::DataV2;
+use anchor_spl::metadata::{
+    self, BurnNft, CreateMasterEditionV3, CreateMetadataAccountsV3, Metadata as TokenMetadata,
+};
 use anchor_spl::token::{self, Mint, MintTo, Token, TokenAccount};
 
 pub mod state;
 pub mod utils;
 pub mod errors;
+pub mod events;
+pub mod inventory;
+pub mod migration;
+#[cfg(feature = "bench")]
+pub mod zero_copy;
 
 use state::*;
 use utils::*;
 use errors::*;
+use events::*;
+use inventory::*;
+use migration::*;
+#[cfg(feature = "bench")]
+use zero_copy::*;
 
 declare_id!("5ADLMwFhWfUHd1rxbRa3DZ8mVCZMDoJryfMi1dAxRNpc");
 
@@ -20,6 +35,7 @@ pub mod solana_bomber {
     // ========================================================================
 
     /// Initialize the global game state with dynamic configuration (admin only, one-time)
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize_global_state(
         ctx: Context<InitializeGlobalState>,
         dev_treasury: Pubkey,
@@ -35,11 +51,14 @@ pub mod solana_bomber {
         // Validate percentages (0-10000 basis points = 0-100%)
         require!(burn_pct <= 10_000, GameError::InvalidBurnPercentage);
         require!(referral_fee <= 10_000, GameError::InvalidReferralFee);
+        require!(rewards_precision > 0, GameError::InvalidRewardsPrecision);
 
         // Core Identity
         global_state.authority = ctx.accounts.authority.key();
         global_state.dev_treasury = dev_treasury;
         global_state.reward_token_mint = ctx.accounts.reward_token_mint.key();
+        global_state.version = GLOBAL_STATE_VERSION;
+        global_state.use_randomness_feed = false;
 
         // Game State
         global_state.game_has_started = false;
@@ -51,19 +70,36 @@ pub mod solana_bomber {
         global_state.unique_heroes_count = 0;
         global_state.total_hash_power = 0;
         global_state.cumulative_bombcoin_per_power = 0;
+        global_state.last_reward_time = 0;
 
         // Dynamic Economic Parameters
         global_state.initial_house_price = initial_house_price;
         global_state.initial_bombcoin_per_block = initial_bombcoin_per_block;
         global_state.halving_interval = halving_interval;
+        global_state.emission_schedule = EmissionSchedule::Supply;
         global_state.burn_pct = burn_pct;
         global_state.referral_fee = referral_fee;
+        global_state.upline_referral_fees = [0; MAX_REFERRAL_TIERS - 1];
         global_state.rewards_precision = rewards_precision;
+        global_state.max_supply = MAX_BOMBCOIN_SUPPLY;
+        global_state.fusion_hero_count = DEFAULT_FUSION_HERO_COUNT;
+        global_state.fusion_success_bps = DEFAULT_FUSION_SUCCESS_BPS;
+        global_state.fusion_cost = DEFAULT_FUSION_COST;
+        global_state.marketplace_fee_bps = DEFAULT_MARKETPLACE_FEE_BPS;
+        global_state.hero_price = DEFAULT_HERO_PRICE;
+        global_state.max_hero_quantity = DEFAULT_MAX_HERO_QUANTITY;
+        global_state.house_levels = DEFAULT_HOUSE_LEVELS;
+        global_state.hero_nft_base_uri = [0; MAX_HERO_NFT_BASE_URI_LEN];
 
         // Accounting
+        global_state.total_emitted = 0;
         global_state.total_mined = 0;
         global_state.total_burned = 0;
         global_state.reward_pool = 0;
+        global_state.total_tokens_minted = 0;
+        global_state.total_tokens_burned = 0;
+        global_state.total_tokens_deposited = 0;
+        global_state.total_coins_credited = 0;
         global_state.bump = ctx.bumps.global_state;
 
         msg!("Global state initialized with dynamic config");
@@ -72,10 +108,24 @@ pub mod solana_bomber {
         msg!("Halving interval: {}", halving_interval);
         msg!("Burn %: {} bps, Referral %: {} bps", burn_pct, referral_fee);
 
+        emit!(GlobalStateInitialized {
+            authority: global_state.authority,
+            dev_treasury,
+            reward_token_mint: global_state.reward_token_mint,
+            initial_house_price,
+            initial_bombcoin_per_block,
+            halving_interval,
+            burn_pct,
+            referral_fee,
+            rewards_precision,
+            max_supply: global_state.max_supply,
+        });
+
         Ok(())
     }
 
     /// Update dynamic game configuration (admin only)
+    #[allow(clippy::too_many_arguments)]
     pub fn update_game_config(
         ctx: Context<AdminAction>,
         initial_house_price: Option<u64>,
@@ -84,9 +134,15 @@ pub mod solana_bomber {
         burn_pct: Option<u16>,
         referral_fee: Option<u16>,
         rewards_precision: Option<u64>,
+        max_supply: Option<u64>,
     ) -> Result<()> {
         let global_state = &mut ctx.accounts.global_state;
 
+        // Settle emissions at the old rate before the schedule changes
+        if initial_bombcoin_per_block.is_some() || halving_interval.is_some() || max_supply.is_some() {
+            update_pool(global_state, Clock::get()?.unix_timestamp)?;
+        }
+
         if let Some(price) = initial_house_price {
             global_state.initial_house_price = price;
             msg!("Updated house price: {}", price);
@@ -109,17 +165,180 @@ pub mod solana_bomber {
         }
 
         if let Some(referral) = referral_fee {
-            require!(referral <= 10_000, GameError::InvalidReferralFee);
+            let upline_total: u32 = global_state.upline_referral_fees.iter().map(|&f| f as u32).sum();
+            require!(referral as u32 + upline_total <= 10_000, GameError::InvalidReferralFee);
             global_state.referral_fee = referral;
             msg!("Updated referral fee: {} bps", referral);
         }
 
         if let Some(precision) = rewards_precision {
+            // Accumulator and reward debts are scaled by precision, so it is fixed once mining starts
+            require!(
+                precision > 0 && !global_state.game_has_started,
+                GameError::InvalidRewardsPrecision
+            );
             global_state.rewards_precision = precision;
             msg!("Updated rewards precision: {}", precision);
         }
 
+        if let Some(supply) = max_supply {
+            require!(supply >= global_state.issued_supply()?, GameError::InvalidMaxSupply);
+            global_state.max_supply = supply;
+            msg!("Updated max supply: {}", supply);
+        }
+
         msg!("Game config updated");
+        emit!(ConfigUpdated {
+            initial_house_price,
+            initial_bombcoin_per_block,
+            halving_interval,
+            burn_pct,
+            referral_fee,
+            rewards_precision,
+            max_supply,
+        });
+        Ok(())
+    }
+
+    /// Switch halvings between supply-based and time-based (admin only)
+    pub fn set_emission_schedule(
+        ctx: Context<AdminAction>,
+        schedule: EmissionSchedule,
+    ) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+
+        // Settle emissions under the old schedule first
+        update_pool(global_state, Clock::get()?.unix_timestamp)?;
+        global_state.emission_schedule = schedule;
+
+        msg!("Emission schedule set to: {:?}", schedule);
+        emit!(EmissionScheduleSet { schedule });
+        Ok(())
+    }
+
+    /// Set the referral chain fees, direct referrer first (admin only)
+    /// e.g. [250, 100, 50] = 2.5% / 1% / 0.5%; omitted tiers are disabled
+    pub fn set_referral_tiers(ctx: Context<AdminAction>, tier_fees: Vec<u16>) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+
+        let total: u32 = tier_fees.iter().map(|&f| f as u32).sum();
+        require!(
+            !tier_fees.is_empty()
+                && tier_fees.len() <= MAX_REFERRAL_TIERS
+                && tier_fees.iter().all(|&f| f > 0)
+                && total <= 10_000,
+            GameError::InvalidReferralTiers
+        );
+
+        let mut fees = [0u16; MAX_REFERRAL_TIERS];
+        fees[..tier_fees.len()].copy_from_slice(&tier_fees);
+        global_state.referral_fee = fees[0];
+        global_state.upline_referral_fees.copy_from_slice(&fees[1..]);
+
+        msg!("Referral tiers set: {:?} bps", fees);
+        emit!(ReferralTiersSet { tier_fees: fees });
+        Ok(())
+    }
+
+    /// Configure hero fusion: heroes consumed, success chance and coin cost (admin only)
+    pub fn set_fusion_config(
+        ctx: Context<AdminAction>,
+        hero_count: u8,
+        success_bps: u16,
+        cost: u64,
+    ) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+
+        require!(
+            (2..=10).contains(&hero_count) && success_bps <= 10_000,
+            GameError::InvalidFusionConfig
+        );
+
+        global_state.fusion_hero_count = hero_count;
+        global_state.fusion_success_bps = success_bps;
+        global_state.fusion_cost = cost;
+
+        msg!(
+            "Fusion config: {} heroes, {} bps success, {} coins",
+            hero_count,
+            success_bps,
+            cost
+        );
+        emit!(FusionConfigUpdated {
+            hero_count,
+            success_bps,
+            cost,
+        });
+        Ok(())
+    }
+
+    /// Set the fee taken from filled marketplace listings (admin only)
+    pub fn set_marketplace_fee(ctx: Context<AdminAction>, fee_bps: u16) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+
+        require!(fee_bps <= 10_000, GameError::InvalidMarketplaceFee);
+        global_state.marketplace_fee_bps = fee_bps;
+
+        msg!("Marketplace fee set to {} bps", fee_bps);
+        emit!(MarketplaceFeeUpdated { fee_bps });
+        Ok(())
+    }
+
+    /// Set the coin price of a hero and the most heroes bought at once (admin only)
+    pub fn set_hero_price(ctx: Context<AdminAction>, price: u64, max_quantity: u8) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+
+        require!(
+            price > 0 && (1..=MAX_HERO_QUANTITY_CAP).contains(&max_quantity),
+            GameError::InvalidHeroPriceConfig
+        );
+        global_state.hero_price = price;
+        global_state.max_hero_quantity = max_quantity;
+
+        msg!("Hero price set to {} coins, up to {} per purchase", price, max_quantity);
+        emit!(HeroPriceUpdated { price, max_quantity });
+        Ok(())
+    }
+
+    /// Set the base URI of exported hero NFTs (admin only)
+    /// Stats are appended as a query string, so the URI can't carry one itself
+    pub fn set_hero_nft_base_uri(ctx: Context<AdminAction>, uri: String) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+
+        require!(valid_hero_nft_base_uri(&uri), GameError::InvalidHeroNftBaseUri);
+        let mut stored = [0u8; MAX_HERO_NFT_BASE_URI_LEN];
+        stored[..uri.len()].copy_from_slice(uri.as_bytes());
+        global_state.hero_nft_base_uri = stored;
+
+        msg!("Hero NFT base URI set to {}", uri);
+        emit!(HeroNftBaseUriUpdated { uri });
+        Ok(())
+    }
+
+    /// Replace one level of the house table: grid, restroom slots, upgrade cost
+    /// and cooldown (admin only). Houses pick up a new grid on their next upgrade.
+    pub fn set_house_level(ctx: Context<AdminAction>, level: u8, config: HouseLevelConfig) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+
+        require!(
+            (1..=MAX_HOUSE_LEVEL).contains(&(level as usize)),
+            GameError::InvalidHouseLevelConfig
+        );
+        let mut levels = global_state.house_levels;
+        levels[level as usize - 1] = config;
+        require!(valid_house_levels(&levels), GameError::InvalidHouseLevelConfig);
+        global_state.house_levels = levels;
+
+        msg!(
+            "House level {}: {}x{} grid, {} restroom slots, upgrade {} coins / {}s cooldown",
+            level,
+            config.grid_width,
+            config.grid_height,
+            config.max_restroom_slots,
+            config.upgrade_cost,
+            config.upgrade_cooldown
+        );
+        emit!(HouseLevelUpdated { level, config });
         Ok(())
     }
 
@@ -129,6 +348,7 @@ pub mod solana_bomber {
         global_state.dev_treasury = new_treasury;
 
         msg!("Treasury updated to: {}", new_treasury);
+        emit!(TreasuryUpdated { new_treasury });
         Ok(())
     }
 
@@ -141,8 +361,10 @@ pub mod solana_bomber {
 
         global_state.game_has_started = true;
         global_state.start_block = clock.unix_timestamp;
+        global_state.last_reward_time = clock.unix_timestamp;
 
         msg!("Game started at timestamp: {}", clock.unix_timestamp);
+        emit!(GameStarted { start_time: clock.unix_timestamp });
         Ok(())
     }
 
@@ -152,6 +374,7 @@ pub mod solana_bomber {
         global_state.paused = paused;
 
         msg!("Game paused: {}", paused);
+        emit!(PauseToggled { paused });
         Ok(())
     }
 
@@ -161,6 +384,7 @@ pub mod solana_bomber {
         global_state.minting_enabled = enabled;
 
         msg!("Hero minting enabled: {}", enabled);
+        emit!(MintingToggled { enabled });
         Ok(())
     }
 
@@ -170,6 +394,7 @@ pub mod solana_bomber {
         global_state.house_upgrades_enabled = enabled;
 
         msg!("House upgrades enabled: {}", enabled);
+        emit!(HouseUpgradesToggled { enabled });
         Ok(())
     }
 
@@ -201,20 +426,193 @@ pub mod solana_bomber {
         )?;
 
         msg!("Withdrawn {} tokens to treasury", amount);
+        emit!(TokenFundsWithdrawn {
+            treasury_token_account: ctx.accounts.treasury_token_account.key(),
+            amount,
+        });
+        Ok(())
+    }
+
+    /// Create the program's BOMBcoin treasury token account (admin only, one-time)
+    /// Receives the non-burned share of token payments; owned by the GlobalState PDA
+    pub fn initialize_token_treasury(ctx: Context<InitializeTokenTreasury>) -> Result<()> {
+        let token_treasury = ctx.accounts.token_treasury.key();
+
+        msg!("Token treasury initialized: {}", token_treasury);
+        emit!(TokenTreasuryInitialized { token_treasury });
+        Ok(())
+    }
+
+    /// Create the BOMBcoin staking pool and its vault (admin only, one-time)
+    /// Stakers share the reward pool pro-rata to their stake
+    pub fn initialize_stake_pool(ctx: Context<InitializeStakePool>) -> Result<()> {
+        let stake_pool = &mut ctx.accounts.stake_pool;
+        stake_pool.total_staked = 0;
+        stake_pool.acc_dividend_per_share = 0;
+        stake_pool.total_distributed = 0;
+        stake_pool.total_claimed = 0;
+        stake_pool.bump = ctx.bumps.stake_pool;
+
+        let stake_vault = ctx.accounts.stake_vault.key();
+        msg!("Stake pool initialized, vault: {}", stake_vault);
+        emit!(StakePoolInitialized { stake_vault });
+        Ok(())
+    }
+
+    /// Upgrade a GlobalState created by an older deployment to the current layout (admin only)
+    /// Runs every layout step from the stored version and reallocs in place
+    /// (authority pays the rent delta). Pre-accumulator layouts start the
+    /// incremental accumulator from now, keeping the existing accumulator value.
+    /// Token supply tracking starts from the mint's current supply
+    pub fn migrate_global_state(ctx: Context<MigrateGlobalState>) -> Result<()> {
+        let global_state_info = ctx.accounts.global_state.to_account_info();
+        let clock = Clock::get()?;
+
+        require_keys_eq!(*global_state_info.owner, crate::ID, GameError::InvalidOwner);
+
+        let (from_version, data) = {
+            let data = global_state_info.try_borrow_data()?;
+            upgrade_global_state(&data, clock.unix_timestamp, ctx.accounts.reward_token_mint.supply)?
+        };
+        let migrated = GlobalState::try_deserialize(&mut &data[..])?;
+
+        require_keys_eq!(
+            migrated.authority,
+            ctx.accounts.authority.key(),
+            GameError::Unauthorized
+        );
+
+        rewrite_account(&global_state_info, &ctx.accounts.authority.to_account_info(), &data)?;
+
+        msg!(
+            "Global state migrated from v{} to v{}: acc_per_power={}, last_reward_time={}",
+            from_version,
+            migrated.version,
+            migrated.cumulative_bombcoin_per_power,
+            migrated.last_reward_time
+        );
+        emit!(GlobalStateMigrated {
+            from_version,
+            version: migrated.version,
+            cumulative_bombcoin_per_power: migrated.cumulative_bombcoin_per_power,
+            last_reward_time: migrated.last_reward_time,
+        });
+        Ok(())
+    }
+
+    /// Upgrade a UserAccount created by an older deployment to the current layout
+    /// Anyone may run it (the admin can batch-migrate players); the payer funds
+    /// the rent for the larger account
+    pub fn migrate_user_account(ctx: Context<MigrateUserAccount>) -> Result<()> {
+        let user_account_info = ctx.accounts.user_account.to_account_info();
+
+        require_keys_eq!(*user_account_info.owner, crate::ID, GameError::InvalidOwner);
+
+        let (from_version, data) = {
+            let data = user_account_info.try_borrow_data()?;
+            upgrade_user_account(&data)?
+        };
+        let migrated = UserAccount::try_deserialize(&mut &data[..])?;
+
+        require_keys_eq!(
+            migrated.owner,
+            ctx.accounts.owner.key(),
+            GameError::InvalidOwner
+        );
+
+        rewrite_account(&user_account_info, &ctx.accounts.payer.to_account_info(), &data)?;
+
+        msg!(
+            "User account of {} migrated from v{} to v{}",
+            migrated.owner,
+            from_version,
+            migrated.version
+        );
+        emit!(UserAccountMigrated {
+            owner: migrated.owner,
+            from_version,
+            version: migrated.version,
+        });
+        Ok(())
+    }
+
+    /// Create the randomness feed account (admin only, one-time)
+    /// Stand-in for an oracle/VRF feed so commit-reveal works on a local validator
+    pub fn initialize_randomness_feed(
+        ctx: Context<InitializeRandomnessFeed>,
+        feed_authority: Pubkey,
+    ) -> Result<()> {
+        let randomness_feed = &mut ctx.accounts.randomness_feed;
+
+        randomness_feed.authority = feed_authority;
+        randomness_feed.sequence = 0;
+        randomness_feed.slot = 0;
+        randomness_feed.value = [0u8; 32];
+        randomness_feed.bump = ctx.bumps.randomness_feed;
+
+        msg!("Randomness feed initialized, authority: {}", feed_authority);
+        emit!(RandomnessFeedInitialized { feed_authority });
+        Ok(())
+    }
+
+    /// Toggle hero reveals between SlotHashes and the randomness feed (admin only)
+    pub fn toggle_randomness_feed(ctx: Context<AdminAction>, enabled: bool) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        global_state.use_randomness_feed = enabled;
+
+        msg!("Randomness feed enabled: {}", enabled);
+        emit!(RandomnessFeedToggled { enabled });
+        Ok(())
+    }
+
+    /// Publish a new randomness value (feed authority only)
+    pub fn publish_randomness(ctx: Context<PublishRandomness>, value: [u8; 32]) -> Result<()> {
+        let randomness_feed = &mut ctx.accounts.randomness_feed;
+        let clock = Clock::get()?;
+
+        randomness_feed.sequence = randomness_feed.sequence
+            .checked_add(1)
+            .ok_or(GameError::ArithmeticOverflow)?;
+        randomness_feed.slot = clock.slot;
+        randomness_feed.value = value;
+
+        msg!(
+            "Randomness #{} published at slot {}",
+            randomness_feed.sequence,
+            clock.slot
+        );
+        emit!(RandomnessPublished {
+            sequence: randomness_feed.sequence,
+            slot: clock.slot,
+        });
         Ok(())
     }
 
     /// Admin: Mint test coins to a user (for testing only)
+    /// Credits come out of the same `max_supply` budget as claims, since coins can be withdrawn as SPL
     pub fn admin_mint_test_coins(
         ctx: Context<AdminMintTestCoins>,
         amount: u64,
     ) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
         let user_account = &mut ctx.accounts.user_account;
 
+        // Rewards already emitted are spoken for, even before they are claimed
+        let issued_after = global_state.issued_supply()?
+            .checked_add(amount)
+            .ok_or(GameError::ArithmeticOverflow)?;
+        require!(issued_after <= global_state.max_supply, GameError::MaxSupplyReached);
+        global_state.total_coins_credited = global_state.total_coins_credited
+            .checked_add(amount)
+            .ok_or(GameError::ArithmeticOverflow)?;
         user_account.coin_balance = user_account.coin_balance.checked_add(amount)
             .ok_or(GameError::ArithmeticOverflow)?;
 
         msg!("Admin minted {} test coins to user: {}", amount, ctx.accounts.target_user.key());
+        emit!(TestCoinsMinted {
+            user: ctx.accounts.target_user.key(),
+            amount,
+        });
         Ok(())
     }
 
@@ -247,19 +645,23 @@ pub mod solana_bomber {
 
         // Initialize user account with grid system
         user_account.owner = ctx.accounts.user.key();
+        user_account.version = USER_ACCOUNT_VERSION;
         user_account.initialized_starter_house = true;
         user_account.house_level = 1;
         user_account.last_house_upgrade_timestamp = 0;
 
-        // Initialize 4x4 grid for level 1
-        let grid_dims = GridDimensions::for_level(1);
-        user_account.grid_width = grid_dims.width;
-        user_account.grid_height = grid_dims.height;
+        // Initialize the level 1 grid
+        let level_one = *global_state.house_level(1);
+        user_account.grid_width = level_one.grid_width;
+        user_account.grid_height = level_one.grid_height;
         user_account.house_occupied_coords = Vec::new();
 
         // Initialize vectors
         user_account.inventory = Vec::new();
         user_account.active_map = Vec::new();
+        user_account.pending_mint = None;
+        user_account.next_hero_id = 0;
+        user_account.hero_count = 0;
 
         // Economy
         user_account.coin_balance = 0;
@@ -271,6 +673,7 @@ pub mod solana_bomber {
         // Referral
         user_account.referrer = None;
         user_account.referral_bonus_paid = 0;
+        user_account.referral_earnings_by_tier = [0; MAX_REFERRAL_TIERS];
         user_account.referrals = Vec::new();
 
         user_account.bump = ctx.bumps.user_account;
@@ -278,102 +681,405 @@ pub mod solana_bomber {
         // Increment house count
         global_state.house_count += 1;
 
-        msg!("House purchased for {} lamports, initialized with 4x4 grid", entry_fee);
+        msg!(
+            "House purchased for {} lamports, initialized with {}x{} grid",
+            entry_fee,
+            user_account.grid_width,
+            user_account.grid_height
+        );
+        emit!(HousePurchased {
+            user: user_account.owner,
+            entry_fee,
+            grid_width: user_account.grid_width,
+            grid_height: user_account.grid_height,
+        });
         Ok(())
     }
 
     /// Set referrer for a user (can only be set once)
-    pub fn set_referrer(ctx: Context<SetReferrer>, referrer_pubkey: Pubkey) -> Result<()> {
+    /// The referrer must own a house; the user is added to their referral list
+    ///
+    /// Rejects chains that loop back to the user within the paid tiers (A→B→A).
+    /// The referrer's own uplines (all but the last paid tier) are passed as
+    /// remaining accounts, in chain order, so the check can read their referrers.
+    pub fn set_referrer<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SetReferrer<'info>>,
+        referrer_pubkey: Pubkey,
+    ) -> Result<()> {
         let user_account = &mut ctx.accounts.user_account;
+        let referrer_account = &mut ctx.accounts.referrer_account;
 
         require!(user_account.referrer.is_none(), GameError::ReferrerAlreadySet);
         require!(referrer_pubkey != user_account.owner, GameError::CannotReferSelf);
+        require!(referrer_account.initialized_starter_house, GameError::InvalidReferrer);
+        require!(
+            referrer_account.referrals.len() < UserAccount::MAX_REFERRALS,
+            GameError::ReferralListFull
+        );
+
+        // Cycle detection: the user must not appear among the referrer's uplines
+        let mut ancestor = referrer_account.referrer;
+        let mut remaining = ctx.remaining_accounts.iter();
+        for depth in 1..MAX_REFERRAL_TIERS {
+            let Some(ancestor_pubkey) = ancestor else { break };
+            require_keys_neq!(ancestor_pubkey, user_account.owner, GameError::ReferralCycle);
+
+            if depth + 1 == MAX_REFERRAL_TIERS {
+                break;
+            }
+
+            let ancestor_account: Account<'info, UserAccount> = Account::try_from(
+                remaining.next().ok_or(GameError::MissingReferrerAccount)?,
+            )?;
+            require_keys_eq!(ancestor_account.owner, ancestor_pubkey, GameError::InvalidReferrer);
+            ancestor = ancestor_account.referrer;
+        }
 
         user_account.referrer = Some(referrer_pubkey);
+        referrer_account.referrals.push(user_account.owner);
 
         msg!("Referrer set to: {}", referrer_pubkey);
+        emit!(ReferrerSet {
+            user: user_account.owner,
+            referrer: referrer_pubkey,
+        });
+
+        // The referee pays for the referrer's longer referral list
+        grow_user_account(referrer_account, &ctx.accounts.user.to_account_info())?;
+        Ok(())
+    }
+
+    // ========== Hero Pages ==========
+
+    /// Open an inventory page for heroes `page_index * 32 ..= page_index * 32 + 31`
+    /// Pages must exist before heroes with those IDs are revealed or received;
+    /// anyone may pay for one (e.g. a sender opening the receiver's page for a gift)
+    pub fn open_hero_page(ctx: Context<OpenHeroPage>, page_index: u16) -> Result<()> {
+        require!(page_index <= HeroPage::MAX_INDEX, GameError::InvalidHeroPage);
+
+        let hero_page = &mut ctx.accounts.hero_page;
+        hero_page.owner = ctx.accounts.user_account.owner;
+        hero_page.index = page_index;
+        hero_page.heroes = Default::default();
+        hero_page.bump = ctx.bumps.hero_page;
+
+        msg!("Hero page {} opened", page_index);
+        emit!(HeroPageOpened {
+            user: hero_page.owner,
+            page_index,
+        });
+        Ok(())
+    }
+
+    /// Close an empty inventory page and reclaim its rent (it can be reopened later)
+    pub fn close_hero_page(ctx: Context<CloseHeroPage>) -> Result<()> {
+        let hero_page = &ctx.accounts.hero_page;
+        require!(hero_page.hero_count() == 0, GameError::HeroPageNotEmpty);
+
+        msg!("Hero page {} closed", hero_page.index);
+        emit!(HeroPageClosed {
+            user: hero_page.owner,
+            page_index: hero_page.index,
+        });
+        Ok(())
+    }
+
+    /// Move heroes out of the legacy `UserAccount.inventory` into hero pages
+    /// Heroes keep their IDs; those whose page is not passed as a remaining
+    /// account stay behind for another call
+    pub fn migrate_inventory<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MigrateInventory<'info>>,
+    ) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        let mut pages = HeroPages::load_for(user_account.owner, ctx.remaining_accounts)?;
+
+        let legacy = std::mem::take(&mut user_account.inventory);
+        let mut moved = 0u16;
+        for hero in legacy {
+            if !pages.has_page_for(hero.id) {
+                user_account.inventory.push(hero);
+                continue;
+            }
+
+            // New IDs must not land on migrated heroes
+            user_account.next_hero_id = user_account.next_hero_id.max(hero.id.saturating_add(1));
+            store_hero(user_account, &mut pages, hero)?;
+            moved += 1;
+        }
+        pages.exit()?;
+
+        let remaining = user_account.inventory.len() as u16;
+        msg!("Migrated {} heroes to hero pages, {} remaining", moved, remaining);
+        emit!(InventoryMigrated {
+            user: user_account.owner,
+            moved,
+            remaining,
+        });
         Ok(())
     }
 
-    /// Buy heroes in bulk (1-10 at once, costs 100 coins per hero, applies burn split)
+    /// Buy heroes in bulk (1 to max_hero_quantity at once, hero_price coins each, applies burn split)
+    /// Commit phase: coins are charged now, heroes are rolled later in reveal_heroes
     pub fn buy_hero(ctx: Context<BuyHero>, quantity: u8) -> Result<()> {
         let global_state = &mut ctx.accounts.global_state;
         let user_account = &mut ctx.accounts.user_account;
+
+        // Calculate total cost (hero_price coins per hero)
+        let total_cost = hero_purchase_cost(global_state, user_account, quantity)?;
+        require!(user_account.coin_balance >= total_cost, GameError::InsufficientCoins);
+
+        // Deduct coins
+        user_account.coin_balance -= total_cost;
+
+        commit_hero_purchase(
+            global_state,
+            user_account,
+            &ctx.accounts.randomness_feed,
+            quantity,
+            total_cost,
+            false,
+        )?;
+        Ok(())
+    }
+
+    /// `buy_hero` paid in SPL BOMBcoin from the player's token account
+    /// The burn_pct share is burned and the rest goes to the token treasury
+    pub fn buy_hero_with_tokens(ctx: Context<BuyHeroWithTokens>, quantity: u8) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        let user_account = &mut ctx.accounts.user_account;
+
+        let total_cost = hero_purchase_cost(global_state, user_account, quantity)?;
+        let (burn_amount, treasury_amount) = commit_hero_purchase(
+            global_state,
+            user_account,
+            &ctx.accounts.randomness_feed,
+            quantity,
+            total_cost,
+            true,
+        )?;
+
+        settle_token_payment(
+            &ctx.accounts.token_program,
+            &ctx.accounts.reward_token_mint,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.token_treasury,
+            &ctx.accounts.user,
+            burn_amount,
+            treasury_amount,
+        )
+    }
+
+    /// Fuse N heroes of one rarity into a chance at one hero of the next tier
+    /// Commit phase: heroes and coins are consumed now, the attempt is rolled in reveal_heroes
+    /// MasterChef pattern applies if any consumed hero is mining
+    ///
+    /// Hero pages of the consumed heroes are passed as remaining accounts, plus
+    /// those of all mining heroes when a consumed hero is mining.
+    pub fn fuse_heroes<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FuseHeroes<'info>>,
+        hero_ids: Vec<u16>,
+    ) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        let user_account = &mut ctx.accounts.user_account;
         let clock = Clock::get()?;
+        let mut pages = HeroPages::load(user_account, ctx.remaining_accounts)?;
 
         require!(!global_state.paused, GameError::GamePaused);
         require!(global_state.minting_enabled, GameError::MintingDisabled);
-        require!(quantity >= 1 && quantity <= 10, GameError::InvalidHeroQuantity);
+        require!(user_account.pending_mint.is_none(), GameError::MintAlreadyPending);
 
-        // Calculate total cost (100 coins per hero)
-        let total_cost = (quantity as u64) * 100;
-        require!(user_account.coin_balance >= total_cost, GameError::InsufficientCoins);
+        // Exactly N distinct, existing heroes of one rarity
+        require!(
+            hero_ids.len() == global_state.fusion_hero_count as usize,
+            GameError::InvalidFusionHeroes
+        );
+        let mut rarities = Vec::with_capacity(hero_ids.len());
+        for (i, &hero_id) in hero_ids.iter().enumerate() {
+            let hero = pages.hero(hero_id)?;
+            require!(
+                !hero_ids[..i].contains(&hero_id),
+                GameError::InvalidFusionHeroes
+            );
+            rarities.push(hero.rarity);
+        }
 
-        // Deduct coins
-        user_account.coin_balance -= total_cost;
+        let consumed_rarity = rarities[0];
+        require!(
+            rarities.iter().all(|&rarity| rarity == consumed_rarity),
+            GameError::InvalidFusionHeroes
+        );
+        let target_rarity = consumed_rarity.next_tier().ok_or(GameError::MaxRarityReached)?;
+
+        let cost = global_state.fusion_cost;
+        require!(user_account.coin_balance >= cost, GameError::InsufficientCoins);
+
+        let pending_mint = commit_pending_mint(
+            global_state,
+            &ctx.accounts.randomness_feed,
+            clock.slot,
+            1,
+            Some(target_rarity),
+        )?;
+
+        // Harvest before any mining hero is consumed
+        let leaves_map = hero_ids
+            .iter()
+            .any(|idx| user_account.active_map.contains(idx));
+        if leaves_map {
+            update_pool(global_state, clock.unix_timestamp)?;
+            harvest_pending_rewards(global_state, user_account)?;
+        }
+
+        take_heroes(user_account, &mut pages, &hero_ids)?;
+
+        if leaves_map {
+            sync_player_power(global_state, user_account, &pages)?;
+        }
+        pages.exit()?;
 
-        // Apply burn split using dynamic burn_pct
-        let (burn_amount, treasury_amount) = calculate_burn_split(total_cost, global_state.burn_pct);
+        // Deduct coins and apply burn split like hero minting
+        user_account.coin_balance -= cost;
+        let (burn_amount, treasury_amount) = calculate_burn_split(cost, global_state.burn_pct);
         global_state.total_burned += burn_amount;
         global_state.reward_pool += treasury_amount;
 
-        // Mint heroes
-        for i in 0..quantity {
-            let hero_id = user_account.inventory.len() as u16;
-            // CRITICAL FIX: Add loop index to ensure unique randomness per hero in bulk mints
-            let modified_timestamp = clock.unix_timestamp.wrapping_add(i as i64);
-            let modified_slot = clock.slot.wrapping_add(i as u64);
+        user_account.pending_mint = Some(pending_mint);
 
-            let hero = generate_hero(
-                hero_id,
-                modified_timestamp,
-                modified_slot,
-                user_account.owner,
-                global_state.unique_heroes_count,
-            )?;
+        msg!(
+            "Fused {} {:?} heroes for {} coins, {:?} roll ({} bps) after slot {}",
+            hero_ids.len(),
+            consumed_rarity,
+            cost,
+            target_rarity,
+            pending_mint.fusion_success_bps,
+            pending_mint.target_slot
+        );
+        emit!(HeroesFused {
+            user: user_account.owner,
+            consumed_rarity,
+            consumed_count: hero_ids.len() as u8,
+            target_rarity,
+            cost,
+            target_slot: pending_mint.target_slot,
+        });
 
-            msg!(
-                "Minted hero #{}: {:?} skin #{}, Power: {}, Speed: {}, HP: {}, HMP: {:.2}",
-                hero_id,
-                hero.rarity,
-                hero.skin_id,
-                hero.power,
-                hero.speed,
-                hero.hp,
-                hero.calculate_hmp()
-            );
+        Ok(())
+    }
 
-            user_account.inventory.push(hero);
-            global_state.unique_heroes_count += 1;
-        }
+    /// Reveal a committed hero mint (second phase of buy_hero / fuse_heroes)
+    /// Heroes are rolled from the target slot's hash, or from the randomness feed
+    ///
+    /// The hero pages for the next IDs (from `next_hero_id`) are passed as
+    /// remaining accounts and must already be open.
+    pub fn reveal_heroes<'info>(ctx: Context<'_, '_, 'info, 'info, RevealHeroes<'info>>) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        let user_account = &mut ctx.accounts.user_account;
+        let clock = Clock::get()?;
+        let mut pages = HeroPages::load(user_account, ctx.remaining_accounts)?;
+
+        let pending = user_account.pending_mint.ok_or(GameError::NoPendingMint)?;
+
+        // Resolve the committed randomness. A stale commitment (randomness no longer
+        // available) only rolls Common heroes, so withholding a reveal never pays off.
+        let (randomness, stale) = if pending.use_feed {
+            let feed = ctx.accounts.randomness_feed
+                .as_ref()
+                .ok_or(GameError::InvalidRandomnessAccount)?;
+            require!(feed.sequence > pending.feed_sequence, GameError::RandomnessNotAvailable);
+            (feed.value, feed.sequence != pending.feed_sequence + 1)
+        } else {
+            require!(clock.slot > pending.target_slot, GameError::RandomnessNotAvailable);
+            let data = ctx.accounts.slot_hashes.try_borrow_data()?;
+            match lookup_slot_hash(&data, pending.target_slot)? {
+                SlotHashLookup::Found(hash) => (hash, false),
+                SlotHashLookup::Expired(hash) => (hash, true),
+                SlotHashLookup::NotYetAvailable => {
+                    return err!(GameError::RandomnessNotAvailable);
+                }
+            }
+        };
+        let minted = if let Some(target_rarity) = pending.fuse_into {
+            // Fusion: a stale commitment always fails
+            let success = !stale
+                && roll_fusion_success(
+                    &randomness,
+                    user_account.owner,
+                    pending.nonce,
+                    pending.fusion_success_bps,
+                );
+            if success {
+                mint_revealed_hero(
+                    global_state,
+                    user_account,
+                    &mut pages,
+                    &randomness,
+                    pending.nonce,
+                    clock.unix_timestamp,
+                    Some(target_rarity),
+                )?;
+            }
+
+            msg!("Fusion into {:?}: {}", target_rarity, if success { "success" } else { "failed" });
+            emit!(FusionResolved {
+                user: user_account.owner,
+                target_rarity,
+                success,
+            });
+            success as u8
+        } else {
+            let rarity_override = if stale { Some(HeroRarity::Common) } else { None };
+
+            // Mint heroes
+            for i in 0..pending.quantity {
+                mint_revealed_hero(
+                    global_state,
+                    user_account,
+                    &mut pages,
+                    &randomness,
+                    pending.nonce.wrapping_add(i as u64),
+                    clock.unix_timestamp,
+                    rarity_override,
+                )?;
+            }
+            pending.quantity
+        };
+
+        user_account.pending_mint = None;
+        pages.exit()?;
 
         msg!(
-            "Bought {} heroes for {} coins (burn: {}, treasury: {})",
-            quantity,
-            total_cost,
-            burn_amount,
-            treasury_amount
+            "Revealed {} heroes{}",
+            minted,
+            if stale { " (stale commitment, Common only)" } else { "" }
         );
+        emit!(HeroesRevealed {
+            user: user_account.owner,
+            quantity: minted,
+            stale,
+        });
 
         Ok(())
     }
 
     /// Place hero on grid at specific coordinates with optional restroom flag
-    pub fn place_hero_on_grid(
-        ctx: Context<ModifyGrid>,
-        hero_index: u16,
+    /// A hero pulled off the map stops mining (MasterChef pattern applies)
+    ///
+    /// The hero's page is passed as a remaining account, plus the pages of all
+    /// mining heroes when the hero is pulled off the map.
+    pub fn place_hero_on_grid<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ModifyGrid<'info>>,
+        hero_id: u16,
         x: u8,
         y: u8,
         is_restroom: bool,
     ) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
         let user_account = &mut ctx.accounts.user_account;
         let clock = Clock::get()?;
+        let mut pages = HeroPages::load(user_account, ctx.remaining_accounts)?;
 
         // Validate hero exists
-        require!(
-            (hero_index as usize) < user_account.inventory.len(),
-            GameError::InvalidHeroIndex
-        );
+        pages.hero(hero_id)?;
 
         // Validate coordinates
         require!(
@@ -389,42 +1095,54 @@ pub mod solana_bomber {
 
         // Check if hero is already on grid
         require!(
-            user_account.find_hero_on_grid(hero_index).is_none(),
+            user_account.find_hero_on_grid(hero_id).is_none(),
             GameError::HeroAlreadyOnMap
         );
 
-        // Remove from map if present
-        if let Some(pos) = user_account.active_map.iter().position(|&x| x == hero_index) {
-            user_account.active_map.remove(pos);
-        }
-
         // Validate restroom capacity
         if is_restroom {
-            let max_restroom = user_account.get_max_restroom_slots();
+            let max_restroom = global_state.house_level(user_account.house_level).max_restroom_slots as usize;
             let current_restroom = user_account.count_restroom_slots();
             require!(current_restroom < max_restroom, GameError::RestroomFull);
         }
 
+        // Remove from map if present (harvest before its power is dropped)
+        if let Some(pos) = user_account.active_map.iter().position(|&x| x == hero_id) {
+            update_pool(global_state, clock.unix_timestamp)?;
+            harvest_pending_rewards(global_state, user_account)?;
+            user_account.active_map.remove(pos);
+            sync_player_power(global_state, user_account, &pages)?;
+        }
+
         // Place hero on grid
         let tile = HouseTile {
             x,
             y,
-            hero_id: hero_index,
+            hero_id,
             is_restroom,
         };
         user_account.house_occupied_coords.push(tile);
 
         // Update hero timestamp
-        let hero = &mut user_account.inventory[hero_index as usize];
-        hero.last_action_time = clock.unix_timestamp;
+        pages.hero_mut(hero_id)?.last_action_time = clock.unix_timestamp;
+        pages.exit()?;
 
         msg!(
             "Hero {} placed on grid at ({}, {}) {}",
-            hero_index,
+            hero_id,
             x,
             y,
             if is_restroom { "(Restroom)" } else { "(Bench)" }
         );
+        emit!(HeroPlaced {
+            user: user_account.owner,
+            hero_id,
+            x,
+            y,
+            is_restroom,
+        });
+
+        grow_user_account(user_account, &ctx.accounts.user.to_account_info())?;
 
         Ok(())
     }
@@ -451,80 +1169,102 @@ pub mod solana_bomber {
         user_account.house_occupied_coords.remove(tile_pos.unwrap());
 
         msg!("Hero {} removed from grid at ({}, {})", hero_id, x, y);
+        emit!(HeroRemovedFromGrid {
+            user: user_account.owner,
+            hero_id,
+            x,
+            y,
+        });
 
         Ok(())
     }
 
     /// Move hero from grid to map (start mining)
-    pub fn move_hero_to_map(ctx: Context<MoveHeroToMap>, hero_index: u16) -> Result<()> {
-        let global_state = &ctx.accounts.global_state;
-        let user_account = &mut ctx.accounts.user_account;
-        let clock = Clock::get()?;
+    /// MasterChef pattern: update_pool → harvest → modify power → update debt
+    ///
+    /// Hero pages of the hero and of all mining heroes are passed as remaining accounts.
+    pub fn move_hero_to_map<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MoveHeroToMap<'info>>,
+        hero_id: u16,
+    ) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        let user_account = &mut ctx.accounts.user_account;
+        let clock = Clock::get()?;
+        let mut pages = HeroPages::load(user_account, ctx.remaining_accounts)?;
 
         require!(!global_state.paused, GameError::GamePaused);
 
         // Validate hero exists
-        require!(
-            (hero_index as usize) < user_account.inventory.len(),
-            GameError::InvalidHeroIndex
-        );
+        let hero = pages.hero(hero_id)?;
 
         // Check map capacity
-        require!(user_account.active_map.len() < 15, GameError::MapFull);
+        require!(
+            user_account.active_map.len() < UserAccount::MAX_MAP_HEROES,
+            GameError::MapFull
+        );
 
         // Hero must be on grid or in inventory (not already on map)
         require!(
-            !user_account.active_map.contains(&hero_index),
+            !user_account.active_map.contains(&hero_id),
             GameError::HeroAlreadyOnMap
         );
 
         // Check hero HP
-        require!(!user_account.inventory[hero_index as usize].is_sleeping(), GameError::HeroIsSleeping);
+        require!(!hero.is_sleeping(), GameError::HeroIsSleeping);
+
+        // STEP 1: Update global pool BEFORE any user action
+        update_pool(global_state, clock.unix_timestamp)?;
+
+        // STEP 2: Harvest pending rewards BEFORE changing user's power
+        harvest_pending_rewards(global_state, user_account)?;
 
         // Remove from grid if present
         if let Some(pos) = user_account
             .house_occupied_coords
             .iter()
-            .position(|tile| tile.hero_id == hero_index)
+            .position(|tile| tile.hero_id == hero_id)
         {
             user_account.house_occupied_coords.remove(pos);
         }
 
         // Set mining start time
-        user_account.inventory[hero_index as usize].last_action_time = clock.unix_timestamp;
+        pages.hero_mut(hero_id)?.last_action_time = clock.unix_timestamp;
 
         // Add to map
-        user_account.active_map.push(hero_index);
+        user_account.active_map.push(hero_id);
 
-        // Update player power (recalculate total HMP)
-        user_account.player_power = user_account
-            .active_map
-            .iter()
-            .filter_map(|&idx| user_account.inventory.get(idx as usize))
-            .filter(|h| h.is_active())
-            .map(|h| h.calculate_hmp() as u64)
-            .sum();
+        // STEP 3: Recalculate player power, sync global hash power and reward debt
+        sync_player_power(global_state, user_account, &pages)?;
+        pages.exit()?;
 
-        msg!("Hero {} moved to map, player power: {}", hero_index, user_account.player_power);
+        msg!("Hero {} moved to map, player power: {}", hero_id, user_account.player_power);
+        emit!(HeroMovedToMap {
+            user: user_account.owner,
+            hero_id,
+            player_power: user_account.player_power,
+        });
 
         Ok(())
     }
 
     /// Bulk place heroes on grid (multiple heroes in single transaction)
-    pub fn bulk_place_heroes(
-        ctx: Context<ModifyGrid>,
+    /// Heroes pulled off the map stop mining (MasterChef pattern applies)
+    ///
+    /// Hero pages of the placed heroes are passed as remaining accounts, plus
+    /// those of all mining heroes when a placed hero is pulled off the map.
+    pub fn bulk_place_heroes<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ModifyGrid<'info>>,
         placements: Vec<HeroPlacement>,
     ) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
         let user_account = &mut ctx.accounts.user_account;
         let clock = Clock::get()?;
+        let mut pages = HeroPages::load(user_account, ctx.remaining_accounts)?;
 
         // Pre-validate all placements before making any changes
-        for placement in &placements {
+        for (i, placement) in placements.iter().enumerate() {
             // Validate hero exists
-            require!(
-                (placement.hero_index as usize) < user_account.inventory.len(),
-                GameError::InvalidHeroIndex
-            );
+            pages.hero(placement.hero_id)?;
 
             // Validate coordinates
             require!(
@@ -534,13 +1274,15 @@ pub mod solana_bomber {
 
             // Validate position is not occupied (including by other heroes in this batch)
             require!(
-                !user_account.is_coord_occupied(placement.x, placement.y),
+                !user_account.is_coord_occupied(placement.x, placement.y)
+                    && !placements[..i].iter().any(|p| p.x == placement.x && p.y == placement.y),
                 GameError::GridPositionOccupied
             );
 
-            // Check if hero is already on grid
+            // Check if hero is already on grid (or placed twice in this batch)
             require!(
-                user_account.find_hero_on_grid(placement.hero_index).is_none(),
+                user_account.find_hero_on_grid(placement.hero_id).is_none()
+                    && !placements[..i].iter().any(|p| p.hero_id == placement.hero_id),
                 GameError::HeroAlreadyOnMap
             );
         }
@@ -548,19 +1290,28 @@ pub mod solana_bomber {
         // Validate total restroom capacity
         let current_restroom = user_account.count_restroom_slots();
         let new_restroom = placements.iter().filter(|p| p.is_restroom).count();
-        let max_restroom = user_account.get_max_restroom_slots();
+        let max_restroom = global_state.house_level(user_account.house_level).max_restroom_slots as usize;
         require!(
             current_restroom + new_restroom <= max_restroom,
             GameError::RestroomFull
         );
 
+        // Harvest before any mining hero is pulled off the map
+        let leaves_map = placements
+            .iter()
+            .any(|p| user_account.active_map.contains(&p.hero_id));
+        if leaves_map {
+            update_pool(global_state, clock.unix_timestamp)?;
+            harvest_pending_rewards(global_state, user_account)?;
+        }
+
         // All validations passed - now apply all placements
         for placement in placements {
             // Remove from map if present
             if let Some(pos) = user_account
                 .active_map
                 .iter()
-                .position(|&x| x == placement.hero_index)
+                .position(|&x| x == placement.hero_id)
             {
                 user_account.active_map.remove(pos);
             }
@@ -569,18 +1320,17 @@ pub mod solana_bomber {
             let tile = HouseTile {
                 x: placement.x,
                 y: placement.y,
-                hero_id: placement.hero_index,
+                hero_id: placement.hero_id,
                 is_restroom: placement.is_restroom,
             };
             user_account.house_occupied_coords.push(tile);
 
             // Update hero timestamp
-            let hero = &mut user_account.inventory[placement.hero_index as usize];
-            hero.last_action_time = clock.unix_timestamp;
+            pages.hero_mut(placement.hero_id)?.last_action_time = clock.unix_timestamp;
 
             msg!(
                 "Hero {} placed on grid at ({}, {}) {}",
-                placement.hero_index,
+                placement.hero_id,
                 placement.x,
                 placement.y,
                 if placement.is_restroom {
@@ -589,846 +1339,3355 @@ pub mod solana_bomber {
                     "(Bench)"
                 }
             );
+            emit!(HeroPlaced {
+                user: user_account.owner,
+                hero_id: placement.hero_id,
+                x: placement.x,
+                y: placement.y,
+                is_restroom: placement.is_restroom,
+            });
+        }
+
+        if leaves_map {
+            sync_player_power(global_state, user_account, &pages)?;
         }
+        pages.exit()?;
+
+        grow_user_account(user_account, &ctx.accounts.user.to_account_info())?;
 
         Ok(())
     }
 
     /// Bulk move heroes to map (multiple heroes in single transaction)
     /// MasterChef pattern: update_pool → harvest → modify power → update debt
-    pub fn bulk_move_to_map(
-        ctx: Context<MoveHeroToMap>,
-        hero_indices: Vec<u16>,
+    ///
+    /// Hero pages of the moved heroes and of all mining heroes are passed as remaining accounts.
+    pub fn bulk_move_to_map<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MoveHeroToMap<'info>>,
+        hero_ids: Vec<u16>,
     ) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        let user_account = &mut ctx.accounts.user_account;
         let clock = Clock::get()?;
         let current_time = clock.unix_timestamp;
+        let mut pages = HeroPages::load(user_account, ctx.remaining_accounts)?;
 
-        require!(!ctx.accounts.global_state.paused, GameError::GamePaused);
-
-        // STEP 1: Update global pool BEFORE any user action
-        update_pool(&mut ctx.accounts.global_state, current_time)?;
-
-        // STEP 2: Harvest pending rewards BEFORE changing user's power
-        let old_power = ctx.accounts.user_account.player_power;
-        harvest_pending_rewards(&ctx.accounts.global_state, &mut ctx.accounts.user_account)?;
+        require!(!global_state.paused, GameError::GamePaused);
 
         // Pre-validate all heroes before making any changes
-        for &hero_index in &hero_indices {
+        for (i, &hero_id) in hero_ids.iter().enumerate() {
             // Validate hero exists
-            require!(
-                (hero_index as usize) < ctx.accounts.user_account.inventory.len(),
-                GameError::InvalidHeroIndex
-            );
+            let hero = pages.hero(hero_id)?;
 
-            // Hero must not already be on map
+            // Hero must not already be on map (or listed twice in this batch)
             require!(
-                !ctx.accounts.user_account.active_map.contains(&hero_index),
+                !user_account.active_map.contains(&hero_id)
+                    && !hero_ids[..i].contains(&hero_id),
                 GameError::HeroAlreadyOnMap
             );
 
             // Check hero HP
-            require!(
-                !ctx.accounts.user_account.inventory[hero_index as usize].is_sleeping(),
-                GameError::HeroIsSleeping
-            );
+            require!(!hero.is_sleeping(), GameError::HeroIsSleeping);
         }
 
         // Check map capacity
-        let new_map_size = ctx.accounts.user_account.active_map.len() + hero_indices.len();
-        require!(new_map_size <= 15, GameError::MapFull);
+        let new_map_size = user_account.active_map.len() + hero_ids.len();
+        require!(new_map_size <= UserAccount::MAX_MAP_HEROES, GameError::MapFull);
+
+        // STEP 1: Update global pool BEFORE any user action
+        update_pool(global_state, current_time)?;
+
+        // STEP 2: Harvest pending rewards BEFORE changing user's power
+        harvest_pending_rewards(global_state, user_account)?;
 
-        // All validations passed - now move all heroes
-        for hero_index in hero_indices {
+        // STEP 3: All validations passed - now move all heroes
+        for &hero_id in hero_ids.iter() {
             // Remove from grid if present
-            if let Some(pos) = ctx.accounts.user_account
+            if let Some(pos) = user_account
                 .house_occupied_coords
                 .iter()
-                .position(|tile| tile.hero_id == hero_index)
+                .position(|tile| tile.hero_id == hero_id)
             {
-                ctx.accounts.user_account.house_occupied_coords.remove(pos);
+                user_account.house_occupied_coords.remove(pos);
             }
 
             // Set mining start time
-            ctx.accounts.user_account.inventory[hero_index as usize].last_action_time = current_time;
+            pages.hero_mut(hero_id)?.last_action_time = current_time;
 
             // Add to map
-            ctx.accounts.user_account.active_map.push(hero_index);
+            user_account.active_map.push(hero_id);
 
-            msg!("Hero {} moved to map", hero_index);
+            msg!("Hero {} moved to map", hero_id);
         }
 
-        // STEP 3: Calculate new player power after adding heroes
-        let new_power: u64 = ctx.accounts.user_account
-            .active_map
-            .iter()
-            .filter_map(|&idx| ctx.accounts.user_account.inventory.get(idx as usize))
-            .filter(|h| h.is_active())
-            .map(|h| h.calculate_hmp() as u64)
-            .sum();
-
-        ctx.accounts.user_account.player_power = new_power;
-
-        // STEP 4: Update global total_hash_power
-        // Properly handle both power increases and decreases
-        if new_power > old_power {
-            let power_delta = new_power - old_power;
-            ctx.accounts.global_state.total_hash_power = ctx.accounts.global_state.total_hash_power
-                .checked_add(power_delta)
-                .ok_or(GameError::ArithmeticOverflow)?;
-        } else if old_power > new_power {
-            let power_delta = old_power - new_power;
-            ctx.accounts.global_state.total_hash_power = ctx.accounts.global_state.total_hash_power
-                .saturating_sub(power_delta);
-        }
-        // If equal, no change needed
-
-        // STEP 5: Update user's reward_debt = new_power * acc_per_power
-        let precision = ctx.accounts.global_state.rewards_precision as u128;
-        ctx.accounts.user_account.reward_debt = (new_power as u128)
-            .checked_mul(ctx.accounts.global_state.cumulative_bombcoin_per_power)
-            .ok_or(GameError::ArithmeticOverflow)?;
+        // STEP 4: Recalculate player power, sync global hash power and reward debt
+        let (old_power, new_power) = sync_player_power(global_state, user_account, &pages)?;
+        pages.exit()?;
 
         msg!(
             "Bulk moved {} heroes to map: power {} → {}, global_power: {}, debt: {}",
-            ctx.accounts.user_account.active_map.len(),
+            user_account.active_map.len(),
             old_power,
             new_power,
-            ctx.accounts.global_state.total_hash_power,
-            ctx.accounts.user_account.reward_debt
+            global_state.total_hash_power,
+            user_account.reward_debt
         );
+        for hero_id in hero_ids {
+            emit!(HeroMovedToMap {
+                user: user_account.owner,
+                hero_id,
+                player_power: new_power,
+            });
+        }
 
         Ok(())
     }
 
     /// Remove a single hero from map (stops mining)
     /// MasterChef pattern: update_pool → harvest → modify power → update debt
-    pub fn remove_from_map(
-        ctx: Context<MoveHeroToMap>,
-        hero_index: u16,
+    ///
+    /// Hero pages of all mining heroes (including this one) are passed as remaining accounts.
+    pub fn remove_from_map<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MoveHeroToMap<'info>>,
+        hero_id: u16,
     ) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        let user_account = &mut ctx.accounts.user_account;
         let clock = Clock::get()?;
         let current_time = clock.unix_timestamp;
+        let mut pages = HeroPages::load(user_account, ctx.remaining_accounts)?;
 
-        require!(!ctx.accounts.global_state.paused, GameError::GamePaused);
+        require!(!global_state.paused, GameError::GamePaused);
 
         // Validate hero is on map
         require!(
-            ctx.accounts.user_account.active_map.contains(&hero_index),
+            user_account.active_map.contains(&hero_id),
             GameError::HeroNotOnMap
         );
 
         // STEP 1: Update global pool BEFORE any user action
-        update_pool(&mut ctx.accounts.global_state, current_time)?;
+        update_pool(global_state, current_time)?;
 
         // STEP 2: Harvest pending rewards BEFORE changing user's power
-        let old_power = ctx.accounts.user_account.player_power;
-        harvest_pending_rewards(&ctx.accounts.global_state, &mut ctx.accounts.user_account)?;
+        harvest_pending_rewards(global_state, user_account)?;
 
         // STEP 3: Remove hero from map
-        ctx.accounts.user_account.active_map.retain(|&idx| idx != hero_index);
+        user_account.active_map.retain(|&idx| idx != hero_id);
 
         // Update hero timestamp
-        ctx.accounts.user_account.inventory[hero_index as usize].last_action_time = current_time;
+        pages.hero_mut(hero_id)?.last_action_time = current_time;
 
-        // STEP 4: Calculate new player power after removing hero
-        let new_power: u64 = ctx.accounts.user_account
-            .active_map
-            .iter()
-            .filter_map(|&idx| ctx.accounts.user_account.inventory.get(idx as usize))
-            .filter(|h| h.is_active())
-            .map(|h| h.calculate_hmp() as u64)
-            .sum();
-
-        ctx.accounts.user_account.player_power = new_power;
-
-        // STEP 5: Update global total_hash_power
-        let power_delta = old_power.saturating_sub(new_power);
-        ctx.accounts.global_state.total_hash_power = ctx.accounts.global_state.total_hash_power
-            .saturating_sub(power_delta);
-
-        // STEP 6: Update user's reward_debt = new_power * acc_per_power
-        ctx.accounts.user_account.reward_debt = (new_power as u128)
-            .checked_mul(ctx.accounts.global_state.cumulative_bombcoin_per_power)
-            .ok_or(GameError::ArithmeticOverflow)?;
+        // STEP 4: Recalculate player power, sync global hash power and reward debt
+        let (old_power, new_power) = sync_player_power(global_state, user_account, &pages)?;
+        pages.exit()?;
 
         msg!(
             "Removed hero {} from map: power {} → {}, global_power: {}",
-            hero_index,
+            hero_id,
             old_power,
             new_power,
-            ctx.accounts.global_state.total_hash_power
+            global_state.total_hash_power
         );
+        emit!(HeroRemovedFromMap {
+            user: user_account.owner,
+            hero_id,
+            old_power,
+            new_power,
+            total_hash_power: global_state.total_hash_power,
+        });
 
         Ok(())
     }
 
     /// Claim mining rewards with MasterChef model
-    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
+    /// The net reward is paid into exactly one place: minted to the player's
+    /// token account (`Wallet`) or credited to `coin_balance` (`CoinBalance`).
+    /// Referral bonuses are always minted to the uplines' ATAs.
+    ///
+    /// Tier 1 referral uses the named referrer accounts; tiers 2+ are passed as
+    /// writable (UserAccount, ATA) pairs in remaining accounts, in chain order,
+    /// followed by the hero pages of all mining heroes
+    pub fn claim_rewards<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimRewards<'info>>,
+        destination: RewardDestination,
+    ) -> Result<()> {
         let clock = Clock::get()?;
         let current_time = clock.unix_timestamp;
+        let reward_mint = ctx.accounts.reward_token_mint.key();
 
         // Early validations
         require!(!ctx.accounts.global_state.paused, GameError::GamePaused);
 
+        // Referral bonuses may only go to the recorded uplines' ATAs
+        // and are tracked on their user accounts
+        let tier_fees = ctx.accounts.global_state.referral_tier_fees();
+        let mut uplines: Vec<(Account<'info, UserAccount>, Account<'info, TokenAccount>)> = Vec::new();
+        let mut remaining = ctx.remaining_accounts.iter();
+
+        if let Some(referrer_pubkey) = ctx.accounts.user_account.referrer {
+            let referrer_account = ctx.accounts.referrer_account
+                .as_ref()
+                .ok_or(GameError::MissingReferrerAccount)?;
+            require_keys_eq!(referrer_account.owner, referrer_pubkey, GameError::InvalidReferrer);
+
+            let referrer_token_account = ctx.accounts.referrer_token_account
+                .as_ref()
+                .ok_or(GameError::MissingReferrerTokenAccount)?;
+            require_referrer_ata(referrer_token_account, &referrer_pubkey, &reward_mint)?;
+
+            // Walk the chain above the direct referrer
+            let user_pubkey = ctx.accounts.user_account.owner;
+            let mut next_upline = referrer_account.referrer;
+            for &tier_fee in tier_fees.iter().skip(1) {
+                let Some(upline_pubkey) = next_upline else { break };
+
+                // Disabled tiers end the chain; it never pays the claimer back
+                if tier_fee == 0 || upline_pubkey == user_pubkey {
+                    break;
+                }
+
+                let upline_account: Account<'info, UserAccount> = Account::try_from(
+                    remaining.next().ok_or(GameError::MissingReferrerAccount)?,
+                )?;
+                require_keys_eq!(upline_account.owner, upline_pubkey, GameError::InvalidReferrer);
+
+                let upline_token_account: Account<'info, TokenAccount> = Account::try_from(
+                    remaining.next().ok_or(GameError::MissingReferrerTokenAccount)?,
+                )?;
+                require_referrer_ata(&upline_token_account, &upline_pubkey, &reward_mint)?;
+
+                next_upline = upline_account.referrer;
+                uplines.push((upline_account, upline_token_account));
+            }
+        }
+
+        // Hero pages follow the uplines
+        let mut pages = HeroPages::load(&ctx.accounts.user_account, remaining.as_slice())?;
+
         // Step 1: Update global pool (must be first!)
         update_pool(&mut ctx.accounts.global_state, current_time)?;
 
         // Step 2: Harvest any pending rewards (auto-claim before modifications)
         harvest_pending_rewards(&ctx.accounts.global_state, &mut ctx.accounts.user_account)?;
 
-        // Step 3: Apply HP drain to all mining heroes
-        let active_map_copy = ctx.accounts.user_account.active_map.clone();
-        for hero_index in active_map_copy {
-            let hero = &mut ctx.accounts.user_account.inventory[hero_index as usize];
-
+        // Step 3: Apply HP drain and accrue XP for all mining heroes
+        for &hero_id in ctx.accounts.user_account.active_map.iter() {
+            let hero = pages.hero_mut(hero_id)?;
             if !hero.is_sleeping() {
                 let elapsed = (current_time - hero.last_action_time) as u64;
-                let hp_drain = hero.calculate_hp_drain(elapsed);
-                hero.hp = hero.hp.saturating_sub(hp_drain);
+                hero.apply_mining(elapsed);
                 hero.last_action_time = current_time;
             }
         }
 
-        // Step 4: Get total pending rewards to mint
-        let gross_reward = ctx.accounts.user_account.player_pending_rewards;
+        // Step 4: Get total pending rewards to mint, capped by the remaining supply
+        let pending_reward = ctx.accounts.user_account.player_pending_rewards;
+        require!(pending_reward > 0, GameError::NoRewardsToClaim);
 
-        require!(gross_reward > 0, GameError::NoRewardsToClaim);
+        let mintable_supply = ctx.accounts.global_state.remaining_supply();
+        require!(mintable_supply > 0, GameError::MaxSupplyReached);
+        let gross_reward = pending_reward.min(mintable_supply);
+        let forfeited_reward = pending_reward - gross_reward;
 
-        // Calculate referral bonus using dynamic referral_fee
-        let referral_bonus = calculate_referral_bonus(gross_reward, ctx.accounts.global_state.referral_fee);
+        // Referral cut per tier: the full configured cut is withheld,
+        // but only tiers with an upline are minted
+        let tier_bonuses = calculate_tier_bonuses(gross_reward, &tier_fees);
+        let referral_bonus: u64 = tier_bonuses.iter().sum();
         let net_reward = gross_reward.saturating_sub(referral_bonus);
 
         // Handle referral bonus (mint to referrer if exists)
         let referrer = ctx.accounts.user_account.referrer;
+        let user_pubkey = ctx.accounts.user_account.owner;
         let global_state_bump = ctx.accounts.global_state.bump;
+        let mut tokens_minted = 0u64;
 
-        if let Some(referrer_pubkey) = referrer {
-            if referral_bonus > 0 {
-                let seeds = &[b"global_state".as_ref(), &[global_state_bump]];
-                let signer = &[&seeds[..]];
-
-                token::mint_to(
-                    CpiContext::new_with_signer(
-                        ctx.accounts.token_program.to_account_info(),
-                        MintTo {
-                            mint: ctx.accounts.reward_token_mint.to_account_info(),
-                            to: ctx.accounts.referrer_token_account.to_account_info(),
-                            authority: ctx.accounts.global_state.to_account_info(),
-                        },
-                        signer,
-                    ),
-                    referral_bonus,
+        if let (Some(referrer_pubkey), Some(referrer_token_account)) =
+            (referrer, ctx.accounts.referrer_token_account.as_ref())
+        {
+            if tier_bonuses[0] > 0 {
+                mint_reward(
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.reward_token_mint,
+                    referrer_token_account.to_account_info(),
+                    &ctx.accounts.global_state,
+                    global_state_bump,
+                    tier_bonuses[0],
                 )?;
+                tokens_minted += tier_bonuses[0];
 
-                msg!("Referral bonus: {} tokens to {}", referral_bonus, referrer_pubkey);
-            }
-        }
+                if let Some(referrer_account) = ctx.accounts.referrer_account.as_mut() {
+                    credit_referral_bonus(referrer_account, 0, tier_bonuses[0])?;
+                }
 
-        // Mint net reward to user
-        if net_reward > 0 {
-            let seeds = &[b"global_state".as_ref(), &[global_state_bump]];
-            let signer = &[&seeds[..]];
-
-            token::mint_to(
-                CpiContext::new_with_signer(
-                    ctx.accounts.token_program.to_account_info(),
-                    MintTo {
-                        mint: ctx.accounts.reward_token_mint.to_account_info(),
-                        to: ctx.accounts.user_token_account.to_account_info(),
-                        authority: ctx.accounts.global_state.to_account_info(),
-                    },
-                    signer,
-                ),
-                net_reward,
-            )?;
+                msg!("Referral bonus: {} tokens to {}", tier_bonuses[0], referrer_pubkey);
+                emit!(ReferralBonusPaid {
+                    user: user_pubkey,
+                    referrer: referrer_pubkey,
+                    tier: 1,
+                    amount: tier_bonuses[0],
+                });
+            }
         }
 
-        // Update global supply counter
-        ctx.accounts.global_state.total_mined += gross_reward;
-
-        // Add coins to internal balance for hero minting
-        ctx.accounts.user_account.coin_balance += net_reward;
+        // Upper tiers (accounts from remaining_accounts are persisted manually)
+        for (i, (upline_account, upline_token_account)) in uplines.iter_mut().enumerate() {
+            let tier = i + 1;
+            let bonus = tier_bonuses[tier];
+            if bonus == 0 {
+                continue;
+            }
 
-        // Reset pending rewards (they've been minted)
-        ctx.accounts.user_account.player_pending_rewards = 0;
+            mint_reward(
+                &ctx.accounts.token_program,
+                &ctx.accounts.reward_token_mint,
+                upline_token_account.to_account_info(),
+                &ctx.accounts.global_state,
+                global_state_bump,
+                bonus,
+            )?;
+            tokens_minted += bonus;
 
-        // Recalculate current total HMP for player power (only alive heroes count)
-        let old_power = ctx.accounts.user_account.player_power;
-        let current_total_hmp: u64 = ctx.accounts.user_account.active_map
-            .iter()
-            .filter_map(|&idx| ctx.accounts.user_account.inventory.get(idx as usize))
-            .filter(|h| h.is_active())
-            .map(|h| h.calculate_hmp() as u64)
-            .sum();
+            credit_referral_bonus(upline_account, tier, bonus)?;
+            upline_account.exit(&crate::ID)?;
 
-        ctx.accounts.user_account.player_power = current_total_hmp;
+            msg!("Tier {} referral bonus: {} tokens to {}", tier + 1, bonus, upline_account.owner);
+            emit!(ReferralBonusPaid {
+                user: user_pubkey,
+                referrer: upline_account.owner,
+                tier: (tier + 1) as u8,
+                amount: bonus,
+            });
+        }
 
-        // Update global total_hash_power if power changed (heroes died)
-        if current_total_hmp != old_power {
-            if current_total_hmp > old_power {
-                let power_delta = current_total_hmp - old_power;
-                ctx.accounts.global_state.total_hash_power = ctx.accounts.global_state.total_hash_power
-                    .checked_add(power_delta)
+        // Pay the net reward as SPL tokens or as in-game coins, never both
+        match destination {
+            RewardDestination::Wallet => {
+                if net_reward > 0 {
+                    mint_reward(
+                        &ctx.accounts.token_program,
+                        &ctx.accounts.reward_token_mint,
+                        ctx.accounts.user_token_account.to_account_info(),
+                        &ctx.accounts.global_state,
+                        global_state_bump,
+                        net_reward,
+                    )?;
+                    tokens_minted += net_reward;
+                }
+            }
+            RewardDestination::CoinBalance => {
+                ctx.accounts.user_account.coin_balance = ctx.accounts.user_account.coin_balance
+                    .checked_add(net_reward)
                     .ok_or(GameError::ArithmeticOverflow)?;
-            } else {
-                let power_delta = old_power - current_total_hmp;
-                ctx.accounts.global_state.total_hash_power = ctx.accounts.global_state.total_hash_power
-                    .saturating_sub(power_delta);
             }
         }
 
-        // Update reward_debt = user.power * acc_per_power
-        // This prevents double-claiming
-        let precision = ctx.accounts.global_state.rewards_precision as u128;
-        ctx.accounts.user_account.reward_debt = (current_total_hmp as u128)
-            .checked_mul(ctx.accounts.global_state.cumulative_bombcoin_per_power)
+        // Update global supply counter (coins credited in-game count as issued)
+        ctx.accounts.global_state.total_mined = ctx.accounts.global_state.total_mined
+            .checked_add(gross_reward)
             .ok_or(GameError::ArithmeticOverflow)?;
+        ctx.accounts.global_state.record_token_mint(tokens_minted)?;
+
+        // Reset pending rewards (they've been minted; anything above the cap never can be,
+        // so it is reported in the event for indexers reconciling total_emitted with total_mined)
+        ctx.accounts.user_account.player_pending_rewards = 0;
+        if forfeited_reward > 0 {
+            msg!("Forfeited {} coins above the remaining supply", forfeited_reward);
+        }
+
+        // Recalculate player power (only alive heroes count), sync global hash power
+        // and reset reward_debt = user.power * acc_per_power to prevent double-claiming
+        let (_, current_total_hmp) = sync_player_power(
+            &mut ctx.accounts.global_state,
+            &mut ctx.accounts.user_account,
+            &pages,
+        )?;
+        pages.exit()?;
 
         msg!(
-            "Claimed {} coins (net: {}, referral: {}), Current HMP: {}, New debt: {}",
+            "Claimed {} coins (net: {} to {:?}, referral: {}), Current HMP: {}, New debt: {}",
             gross_reward,
             net_reward,
+            destination,
             referral_bonus,
             current_total_hmp,
             ctx.accounts.user_account.reward_debt
         );
+        emit!(RewardsClaimed {
+            user: ctx.accounts.user_account.owner,
+            gross_reward,
+            net_reward,
+            destination,
+            referral_bonus,
+            referrer,
+            player_power: current_total_hmp,
+            reward_debt: ctx.accounts.user_account.reward_debt,
+            forfeited_reward,
+        });
 
         Ok(())
     }
 
-    /// Recover HP for heroes on grid (location-based multipliers: bench 1x, restroom 3x)
-    pub fn recover_hp(ctx: Context<RecoverHP>) -> Result<()> {
-        let user_account = &mut ctx.accounts.user_account;
-        let clock = Clock::get()?;
-        let current_time = clock.unix_timestamp;
+    /// Burn SPL BOMBcoin from the player's token account and credit `coin_balance`
+    /// The in-game balance is un-minted BOMBcoin, so the two never back each other
+    pub fn deposit_coins(ctx: Context<BridgeCoins>, amount: u64) -> Result<()> {
+        require!(!ctx.accounts.global_state.paused, GameError::GamePaused);
+        require!(amount > 0, GameError::InvalidAmount);
 
-        let house_occupied_copy = user_account.house_occupied_coords.clone();
+        token::burn(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::Burn {
+                    mint: ctx.accounts.reward_token_mint.to_account_info(),
+                    from: ctx.accounts.user_token_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
 
-        for tile in house_occupied_copy {
-            if tile.is_empty() {
-                continue;
-            }
+        let global_state = &mut ctx.accounts.global_state;
+        global_state.total_tokens_deposited = global_state.total_tokens_deposited
+            .checked_add(amount)
+            .ok_or(GameError::ArithmeticOverflow)?;
 
-            let hero_index = tile.hero_id;
-            let hero = &mut user_account.inventory[hero_index as usize];
-            let elapsed_seconds = (current_time - hero.last_action_time) as u64;
+        let user_account = &mut ctx.accounts.user_account;
+        user_account.coin_balance = user_account.coin_balance
+            .checked_add(amount)
+            .ok_or(GameError::ArithmeticOverflow)?;
 
-            if elapsed_seconds > 0 {
-                let location_multiplier = if tile.is_restroom { 3.0 } else { 1.0 };
+        msg!("Deposited {} BOMBcoin, coin balance: {}", amount, user_account.coin_balance);
+        emit!(CoinsDeposited {
+            user: user_account.owner,
+            amount,
+            coin_balance: user_account.coin_balance,
+        });
+        Ok(())
+    }
 
-                let recovery = hero.calculate_hp_recovery(elapsed_seconds, location_multiplier);
-                hero.hp = hero.hp.saturating_add(recovery).min(hero.max_hp);
-                hero.last_action_time = current_time;
+    /// Debit `coin_balance` and mint the same amount of SPL BOMBcoin to the player
+    pub fn withdraw_coins(ctx: Context<BridgeCoins>, amount: u64) -> Result<()> {
+        require!(!ctx.accounts.global_state.paused, GameError::GamePaused);
+        require!(amount > 0, GameError::InvalidAmount);
 
-                msg!(
-                    "Hero {} recovered {} HP ({}x multiplier)",
-                    hero_index,
-                    recovery,
-                    location_multiplier
-                );
-            }
-        }
+        let user_account = &mut ctx.accounts.user_account;
+        require!(user_account.coin_balance >= amount, GameError::InsufficientCoins);
+        user_account.coin_balance -= amount;
 
+        // Every coin is backed by the supply budget, so the token supply never passes the cap
+        let supply_after = ctx.accounts.global_state.expected_token_supply()
+            .checked_add(amount)
+            .ok_or(GameError::ArithmeticOverflow)?;
+        require!(
+            supply_after <= ctx.accounts.global_state.max_supply,
+            GameError::MaxSupplyReached
+        );
+
+        mint_reward(
+            &ctx.accounts.token_program,
+            &ctx.accounts.reward_token_mint,
+            ctx.accounts.user_token_account.to_account_info(),
+            &ctx.accounts.global_state,
+            ctx.accounts.global_state.bump,
+            amount,
+        )?;
+        ctx.accounts.global_state.record_token_mint(amount)?;
+
+        let user_account = &ctx.accounts.user_account;
+        msg!("Withdrew {} BOMBcoin, coin balance: {}", amount, user_account.coin_balance);
+        emit!(CoinsWithdrawn {
+            user: user_account.owner,
+            amount,
+            coin_balance: user_account.coin_balance,
+        });
         Ok(())
     }
 
-    /// Upgrade house to next level (expands grid, costs coins, has cooldown)
-    pub fn upgrade_house(ctx: Context<UpgradeHouse>) -> Result<()> {
-        let global_state = &ctx.accounts.global_state;
+    /// Level up a hero: spends XP-gated coins to raise power/speed/stamina (capped by rarity)
+    /// MasterChef pattern applies when the hero is mining, since its HMP changes
+    ///
+    /// The hero's page is passed as a remaining account, plus the pages of all
+    /// mining heroes when the hero is mining.
+    pub fn level_up_hero<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LevelUpHero<'info>>,
+        hero_id: u16,
+    ) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
         let user_account = &mut ctx.accounts.user_account;
         let clock = Clock::get()?;
+        let mut pages = HeroPages::load(user_account, ctx.remaining_accounts)?;
 
         require!(!global_state.paused, GameError::GamePaused);
-        require!(global_state.house_upgrades_enabled, GameError::HouseUpgradesDisabled);
-        require!(user_account.house_level < 6, GameError::MaxHouseLevelReached);
-
-        // Check cooldown
-        if user_account.last_house_upgrade_timestamp > 0 {
-            let cooldown = user_account.get_upgrade_cooldown();
-            let time_since_upgrade =
-                clock.unix_timestamp - user_account.last_house_upgrade_timestamp;
-            require!(time_since_upgrade >= cooldown, GameError::UpgradeCooldownActive);
-        }
+        let hero = pages.hero(hero_id)?;
+        require!(hero.level < MAX_HERO_LEVEL, GameError::MaxHeroLevelReached);
+        require!(hero.xp >= Hero::xp_for_level(hero.level + 1), GameError::InsufficientXp);
 
-        // Check cost
-        let cost = user_account.get_upgrade_cost();
+        let cost = hero.level_up_cost();
         require!(user_account.coin_balance >= cost, GameError::InsufficientCoins);
 
-        // Deduct coins
-        user_account.coin_balance -= cost;
+        // Settle rewards at the old HMP before it changes
+        let is_mining = user_account.active_map.contains(&hero_id);
+        if is_mining {
+            update_pool(global_state, clock.unix_timestamp)?;
+            harvest_pending_rewards(global_state, user_account)?;
+        }
 
-        // Upgrade house
-        user_account.house_level += 1;
-        user_account.last_house_upgrade_timestamp = clock.unix_timestamp;
+        let hero = pages.hero_mut(hero_id)?;
+        require!(hero.raise_stats(), GameError::HeroStatsAtCap);
+        hero.level += 1;
+        let (new_level, power, speed, stamina) = (hero.level, hero.power, hero.speed, hero.stamina);
+
+        // Deduct coins and apply burn split like hero minting
+        user_account.coin_balance -= cost;
+        let (burn_amount, treasury_amount) = calculate_burn_split(cost, global_state.burn_pct);
+        global_state.total_burned += burn_amount;
+        global_state.reward_pool += treasury_amount;
 
-        // Expand grid
-        let new_dims = GridDimensions::for_level(user_account.house_level);
-        user_account.grid_width = new_dims.width;
-        user_account.grid_height = new_dims.height;
+        // Recalculate HMP, sync global hash power and reward debt
+        if is_mining {
+            sync_player_power(global_state, user_account, &pages)?;
+        }
+        pages.exit()?;
 
         msg!(
-            "House upgraded to level {} ({}x{} grid) for {} coins",
-            user_account.house_level,
-            new_dims.width,
-            new_dims.height,
-            cost
+            "Hero {} leveled up to {} for {} coins (Power: {}, Speed: {}, Stamina: {})",
+            hero_id,
+            new_level,
+            cost,
+            power,
+            speed,
+            stamina
         );
+        emit!(HeroLeveledUp {
+            user: user_account.owner,
+            hero_id,
+            new_level,
+            power,
+            speed,
+            stamina,
+            cost,
+        });
 
         Ok(())
     }
 
-    // ========================================================================
-    // READ-ONLY VIEW FUNCTIONS (For Frontend)
-    // ========================================================================
-
-    /// Calculate pending rewards without claiming (read-only simulation)
-    pub fn pending_rewards(ctx: Context<ViewUserAccount>) -> Result<PendingRewardsData> {
-        let global_state = &ctx.accounts.global_state;
-        let user_account = &ctx.accounts.user_account;
+    /// Retire (burn) a hero for a rarity-based coin refund paid from the reward pool
+    /// Fails while the reward pool holds less than the refund
+    /// Frees the hero's inventory space; its ID is never reused
+    /// MasterChef pattern applies when the hero is mining, since its HMP leaves the pool
+    ///
+    /// The hero's page is passed as a remaining account, plus the pages of all
+    /// mining heroes when the hero is mining.
+    pub fn retire_hero<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RetireHero<'info>>,
+        hero_id: u16,
+    ) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        let user_account = &mut ctx.accounts.user_account;
         let clock = Clock::get()?;
+        let mut pages = HeroPages::load(user_account, ctx.remaining_accounts)?;
 
-        let current_time = clock.unix_timestamp;
-        let mut total_hmp: u64 = 0;
-        let mut earliest_time = current_time;
-        let mut active_hero_count = 0;
-
-        // Calculate total HMP (without mutating)
-        for &hero_index in &user_account.active_map {
-            if let Some(hero) = user_account.inventory.get(hero_index as usize) {
-                if hero.is_active() {
-                    total_hmp += hero.calculate_hmp() as u64;
-                    active_hero_count += 1;
-
-                    if hero.last_action_time < earliest_time {
-                        earliest_time = hero.last_action_time;
-                    }
-                }
-            }
+        require!(!global_state.paused, GameError::GamePaused);
+        let rarity = pages.hero(hero_id)?.rarity;
+
+        // Refund comes out of the treasury share of past coin spends; once that
+        // share has been paid out to stakers the hero can't be retired for less
+        let refund = rarity.retire_refund();
+        require!(global_state.reward_pool >= refund, GameError::InsufficientRewardPool);
+
+        // Settle rewards at the old HMP before the hero leaves the map
+        let is_mining = user_account.active_map.contains(&hero_id);
+        if is_mining {
+            update_pool(global_state, clock.unix_timestamp)?;
+            harvest_pending_rewards(global_state, user_account)?;
         }
 
-        if total_hmp == 0 {
-            return Ok(PendingRewardsData {
-                gross_reward: 0,
-                net_reward: 0,
-                referral_bonus: 0,
-                total_hmp,
-                active_hero_count,
-                elapsed_seconds: 0,
-                current_bombcoin_per_block: global_state.get_bombcoin_per_block(),
-            });
+        take_heroes(user_account, &mut pages, &[hero_id])?;
+
+        if is_mining {
+            sync_player_power(global_state, user_account, &pages)?;
         }
+        pages.exit()?;
 
-        // Calculate elapsed time
-        let elapsed_seconds = (current_time - earliest_time) as u64;
+        global_state.reward_pool -= refund;
+        user_account.coin_balance += refund;
 
-        // Get current reward rate
-        let bombcoin_per_block = global_state.get_bombcoin_per_block();
+        msg!("Hero {} ({:?}) retired for {} coins", hero_id, rarity, refund);
+        emit!(HeroRetired {
+            user: user_account.owner,
+            hero_id,
+            rarity,
+            refund,
+        });
 
-        // Calculate gross reward
-        let gross_reward = calculate_mining_reward(
-            elapsed_seconds,
-            total_hmp,
-            bombcoin_per_block,
-            global_state.rewards_precision,
+        Ok(())
+    }
+
+    /// Gift a hero to another player's initialized account
+    /// Stats, level, XP and HP are kept; the hero gets the receiver's next ID
+    /// Only heroes off the map and grid can be transferred
+    ///
+    /// The sender's page for the hero and the receiver's page for their next ID
+    /// are passed as remaining accounts.
+    pub fn transfer_hero<'info>(
+        ctx: Context<'_, '_, 'info, 'info, TransferHero<'info>>,
+        hero_id: u16,
+    ) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        let receiver_account = &mut ctx.accounts.receiver_account;
+        let clock = Clock::get()?;
+
+        require!(!ctx.accounts.global_state.paused, GameError::GamePaused);
+        require_keys_neq!(
+            receiver_account.owner,
+            user_account.owner,
+            GameError::CannotTransferToSelf
         );
+        let mut pages = HeroPages::load(user_account, ctx.remaining_accounts)?;
+        let mut receiver_pages = HeroPages::load(receiver_account, ctx.remaining_accounts)?;
 
-        // Calculate referral split
-        let referral_bonus = calculate_referral_bonus(gross_reward, global_state.referral_fee);
-        let net_reward = gross_reward.saturating_sub(referral_bonus);
+        pages.hero(hero_id)?;
+        require!(
+            !user_account.active_map.contains(&hero_id)
+                && user_account.find_hero_on_grid(hero_id).is_none(),
+            GameError::HeroNotTransferable
+        );
+        require!(
+            receiver_account.has_inventory_space(1),
+            GameError::InventoryFull
+        );
 
-        Ok(PendingRewardsData {
-            gross_reward,
-            net_reward,
-            referral_bonus,
-            total_hmp,
-            active_hero_count,
-            elapsed_seconds,
-            current_bombcoin_per_block: bombcoin_per_block,
-        })
-    }
+        let mut hero = take_heroes(user_account, &mut pages, &[hero_id])?.remove(0);
+        hero.id = receiver_account
+            .allocate_hero_id()
+            .ok_or(GameError::HeroIdsExhausted)?;
+        hero.last_action_time = clock.unix_timestamp;
 
-    /// Get comprehensive player stats
-    pub fn get_player_stats(ctx: Context<ViewUserAccount>) -> Result<PlayerStatsData> {
-        let user_account = &ctx.accounts.user_account;
-        let clock = Clock::get()?;
+        let new_hero_id = hero.id;
+        let rarity = hero.rarity;
+        store_hero(receiver_account, &mut receiver_pages, hero)?;
+        pages.exit()?;
+        receiver_pages.exit()?;
 
-        // Count heroes by location
-        let mut heroes_on_map = 0;
-        let mut heroes_on_grid = 0;
-        let mut heroes_in_restroom = 0;
-        let heroes_in_inventory = user_account.inventory.len();
-        let mut heroes_sleeping = 0;
+        msg!(
+            "Hero {} ({:?}) transferred to {} as hero {}",
+            hero_id,
+            rarity,
+            receiver_account.owner,
+            new_hero_id
+        );
+        emit!(HeroTransferred {
+            from: user_account.owner,
+            to: receiver_account.owner,
+            hero_id,
+            new_hero_id,
+            rarity,
+        });
 
-        for (idx, hero) in user_account.inventory.iter().enumerate() {
-            if hero.is_sleeping() {
-                heroes_sleeping += 1;
-            }
+        Ok(())
+    }
 
-            if user_account.active_map.contains(&(idx as u16)) {
-                heroes_on_map += 1;
-            }
+    /// Export a hero as a 1-of-1 NFT (SPL mint + Metaplex metadata and master edition)
+    /// The hero leaves the inventory and is held in an ExportedHero record until imported
+    /// MasterChef pattern applies when the hero is mining, since its HMP leaves the pool
+    ///
+    /// The hero's page is passed as a remaining account, plus the pages of all
+    /// mining heroes when the hero is mining.
+    pub fn export_hero<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExportHero<'info>>,
+        hero_id: u16,
+    ) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        let user_account = &mut ctx.accounts.user_account;
+        let clock = Clock::get()?;
+        let mut pages = HeroPages::load(user_account, ctx.remaining_accounts)?;
+
+        require!(!global_state.paused, GameError::GamePaused);
+        require!(!global_state.nft_base_uri().is_empty(), GameError::HeroNftBaseUriNotSet);
+        pages.hero(hero_id)?;
+
+        // Settle rewards at the old HMP before the hero leaves the map
+        let is_mining = user_account.active_map.contains(&hero_id);
+        if is_mining {
+            update_pool(global_state, clock.unix_timestamp)?;
+            harvest_pending_rewards(global_state, user_account)?;
         }
 
-        for tile in &user_account.house_occupied_coords {
-            if !tile.is_empty() {
-                heroes_on_grid += 1;
-                if tile.is_restroom {
-                    heroes_in_restroom += 1;
-                }
-            }
+        let hero = take_heroes(user_account, &mut pages, &[hero_id])?.remove(0);
+
+        if is_mining {
+            sync_player_power(global_state, user_account, &pages)?;
         }
+        pages.exit()?;
 
-        // House upgrade info
-        let next_upgrade_cost = user_account.get_upgrade_cost();
-        let upgrade_cooldown_remaining = if user_account.last_house_upgrade_timestamp > 0 {
-            let cooldown = user_account.get_upgrade_cooldown();
-            let elapsed = clock.unix_timestamp - user_account.last_house_upgrade_timestamp;
-            cooldown.saturating_sub(elapsed)
-        } else {
-            0
-        };
+        let seeds = &[b"global_state".as_ref(), &[global_state.bump]];
+        let signer = &[&seeds[..]];
 
-        // Grid info
-        let max_restroom_slots = user_account.get_max_restroom_slots();
-        let restroom_slots_used = user_account.count_restroom_slots();
+        // Mint the single token to the player
+        token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: ctx.accounts.hero_mint.to_account_info(),
+                    to: ctx.accounts.hero_token_account.to_account_info(),
+                    authority: global_state.to_account_info(),
+                },
+                signer,
+            ),
+            1,
+        )?;
 
-        Ok(PlayerStatsData {
-            owner: user_account.owner,
-            house_level: user_account.house_level,
-            grid_width: user_account.grid_width,
-            grid_height: user_account.grid_height,
-            coin_balance: user_account.coin_balance,
-            player_power: user_account.player_power,
-            heroes_total: heroes_in_inventory as u64,
-            heroes_on_map: heroes_on_map as u64,
-            heroes_on_grid: heroes_on_grid as u64,
-            heroes_in_restroom: heroes_in_restroom as u64,
-            heroes_sleeping: heroes_sleeping as u64,
-            max_restroom_slots: max_restroom_slots as u64,
-            restroom_slots_used: restroom_slots_used as u64,
-            next_upgrade_cost,
-            upgrade_cooldown_remaining,
-            can_upgrade: user_account.house_level < 6 && upgrade_cooldown_remaining == 0,
-            referrer: user_account.referrer,
-            referral_bonus_paid: user_account.referral_bonus_paid,
-            referral_count: user_account.referrals.len() as u64,
-        })
-    }
+        // Metadata mirrors skin, rarity and stats; update authority stays with the game
+        metadata::create_metadata_accounts_v3(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_metadata_program.to_account_info(),
+                CreateMetadataAccountsV3 {
+                    metadata: ctx.accounts.metadata.to_account_info(),
+                    mint: ctx.accounts.hero_mint.to_account_info(),
+                    mint_authority: global_state.to_account_info(),
+                    payer: ctx.accounts.user.to_account_info(),
+                    update_authority: global_state.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    rent: ctx.accounts.rent.to_account_info(),
+                },
+                signer,
+            ),
+            DataV2 {
+                name: hero_nft_name(&hero),
+                symbol: HERO_NFT_SYMBOL.to_string(),
+                uri: hero_nft_uri(global_state.nft_base_uri(), &hero),
+                seller_fee_basis_points: 0,
+                creators: None,
+                collection: None,
+                uses: None,
+            },
+            false,
+            true,
+            None,
+        )?;
 
-    /// Get detailed hero info by index
-    pub fn get_hero_details(
-        ctx: Context<ViewUserAccount>,
-        hero_index: u16,
-    ) -> Result<HeroDetailsData> {
-        let user_account = &ctx.accounts.user_account;
-        let clock = Clock::get()?;
+        // Master edition with zero prints locks the supply at one
+        metadata::create_master_edition_v3(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_metadata_program.to_account_info(),
+                CreateMasterEditionV3 {
+                    edition: ctx.accounts.master_edition.to_account_info(),
+                    mint: ctx.accounts.hero_mint.to_account_info(),
+                    update_authority: global_state.to_account_info(),
+                    mint_authority: global_state.to_account_info(),
+                    payer: ctx.accounts.user.to_account_info(),
+                    metadata: ctx.accounts.metadata.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    rent: ctx.accounts.rent.to_account_info(),
+                },
+                signer,
+            ),
+            Some(0),
+        )?;
 
-        require!(
-            (hero_index as usize) < user_account.inventory.len(),
-            GameError::InvalidHeroIndex
-        );
+        let exported_hero = &mut ctx.accounts.exported_hero;
+        exported_hero.mint = ctx.accounts.hero_mint.key();
+        exported_hero.exported_by = user_account.owner;
+        exported_hero.hero = hero;
+        exported_hero.bump = ctx.bumps.exported_hero;
 
-        let hero = &user_account.inventory[hero_index as usize];
+        msg!(
+            "Hero {} ({:?}) exported as NFT {}",
+            hero_id,
+            exported_hero.hero.rarity,
+            exported_hero.mint
+        );
+        emit!(HeroExported {
+            user: user_account.owner,
+            hero_id,
+            mint: exported_hero.mint,
+            rarity: exported_hero.hero.rarity,
+        });
 
-        // Find hero location
-        let is_on_map = user_account.active_map.contains(&hero_index);
-        let grid_tile = user_account.find_hero_on_grid(hero_index);
+        Ok(())
+    }
 
-        let (grid_x, grid_y, is_in_restroom) = if let Some(tile) = grid_tile {
-            (Some(tile.x), Some(tile.y), tile.is_restroom)
-        } else {
-            (None, None, false)
-        };
+    /// Import an exported hero: burns the NFT and re-inserts the hero into the caller's inventory
+    /// The hero gets a fresh ID in this inventory and returns off the map
+    ///
+    /// The page for the caller's next hero ID is passed as a remaining account.
+    pub fn import_hero<'info>(ctx: Context<'_, '_, 'info, 'info, ImportHero<'info>>) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        let clock = Clock::get()?;
+        let mut pages = HeroPages::load(user_account, ctx.remaining_accounts)?;
 
-        // Calculate time-based stats
-        let elapsed_since_action = (clock.unix_timestamp - hero.last_action_time) as u64;
+        require!(!ctx.accounts.global_state.paused, GameError::GamePaused);
+        require!(user_account.has_inventory_space(1), GameError::InventoryFull);
+
+        // Burn the token and close its metadata, edition and token account
+        metadata::burn_nft(
+            CpiContext::new(
+                ctx.accounts.token_metadata_program.to_account_info(),
+                BurnNft {
+                    metadata: ctx.accounts.metadata.to_account_info(),
+                    owner: ctx.accounts.user.to_account_info(),
+                    mint: ctx.accounts.hero_mint.to_account_info(),
+                    token: ctx.accounts.hero_token_account.to_account_info(),
+                    edition: ctx.accounts.master_edition.to_account_info(),
+                    spl_token: ctx.accounts.token_program.to_account_info(),
+                },
+            ),
+            None,
+        )?;
 
-        let estimated_hp_drain = if is_on_map {
-            hero.calculate_hp_drain(elapsed_since_action)
-        } else {
-            0
-        };
+        let mut hero = ctx.accounts.exported_hero.hero.clone();
+        hero.id = user_account
+            .allocate_hero_id()
+            .ok_or(GameError::HeroIdsExhausted)?;
+        hero.last_action_time = clock.unix_timestamp;
 
-        let estimated_hp_recovery = if grid_tile.is_some() && !is_on_map {
-            let multiplier = if is_in_restroom { 3.0 } else { 1.0 };
-            hero.calculate_hp_recovery(elapsed_since_action, multiplier)
-        } else {
-            0
-        };
+        let hero_id = hero.id;
+        let rarity = hero.rarity;
+        store_hero(user_account, &mut pages, hero)?;
+        pages.exit()?;
 
-        let estimated_current_hp = if is_on_map {
-            hero.hp.saturating_sub(estimated_hp_drain)
-        } else if grid_tile.is_some() {
-            hero.hp.saturating_add(estimated_hp_recovery).min(hero.max_hp)
-        } else {
-            hero.hp
-        };
+        msg!(
+            "NFT {} imported as hero {} ({:?})",
+            ctx.accounts.hero_mint.key(),
+            hero_id,
+            rarity
+        );
+        emit!(HeroImported {
+            user: user_account.owner,
+            hero_id,
+            mint: ctx.accounts.hero_mint.key(),
+            rarity,
+        });
 
-        Ok(HeroDetailsData {
-            id: hero.id,
-            skin_id: hero.skin_id,
-            rarity: hero.rarity,
-            power: hero.power,
-            speed: hero.speed,
-            stamina: hero.stamina,
-            max_stamina: hero.max_stamina,
-            bomb_number: hero.bomb_number,
-            bomb_range: hero.bomb_range,
-            hp: hero.hp,
-            max_hp: hero.max_hp,
-            estimated_current_hp,
-            hmp: hero.calculate_hmp() as u64,
-            is_sleeping: hero.is_sleeping(),
-            is_on_map,
-            is_on_grid: grid_tile.is_some(),
-            grid_x,
-            grid_y,
-            is_in_restroom,
-            last_action_time: hero.last_action_time,
-            elapsed_since_action,
-        })
+        Ok(())
     }
 
-    /// Get grid state with all tiles
-    pub fn get_grid_state(ctx: Context<ViewUserAccount>) -> Result<GridStateData> {
-        let user_account = &ctx.accounts.user_account;
-
-        let tiles: Vec<GridTileData> = user_account
-            .house_occupied_coords
-            .iter()
-            .map(|tile| GridTileData {
-                x: tile.x,
-                y: tile.y,
-                hero_id: if tile.is_empty() {
-                    None
-                } else {
-                    Some(tile.hero_id)
-                },
-                is_restroom: tile.is_restroom,
-                is_empty: tile.is_empty(),
-            })
-            .collect();
+    // ========== Marketplace ==========
+
+    /// List a hero for sale: escrows it out of the inventory into a listing PDA
+    /// Only heroes that are neither mining nor on the grid can be listed
+    ///
+    /// The hero's page is passed as a remaining account.
+    pub fn list_hero<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ListHero<'info>>,
+        hero_id: u16,
+        price: u64,
+        currency: ListingCurrency,
+    ) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        let clock = Clock::get()?;
+        let mut pages = HeroPages::load(user_account, ctx.remaining_accounts)?;
 
-        Ok(GridStateData {
-            grid_width: user_account.grid_width,
-            grid_height: user_account.grid_height,
-            house_level: user_account.house_level,
-            occupied_tiles: tiles,
-            total_tiles: (user_account.grid_width as u64) * (user_account.grid_height as u64),
-            tiles_occupied: user_account
-                .house_occupied_coords
-                .iter()
-                .filter(|t| !t.is_empty())
-                .count() as u64,
-        })
-    }
+        require!(!ctx.accounts.global_state.paused, GameError::GamePaused);
+        require!(price > 0, GameError::InvalidListingPrice);
+        pages.hero(hero_id)?;
+        require!(
+            !user_account.active_map.contains(&hero_id)
+                && user_account.find_hero_on_grid(hero_id).is_none(),
+            GameError::HeroNotListable
+        );
 
-    /// Get global game info
-    pub fn get_game_info(ctx: Context<ViewGlobalState>) -> Result<GameInfoData> {
-        let global_state = &ctx.accounts.global_state;
+        let hero = take_heroes(user_account, &mut pages, &[hero_id])?.remove(0);
+        let rarity = hero.rarity;
+        pages.exit()?;
+
+        let listing = &mut ctx.accounts.listing;
+        listing.seller = user_account.owner;
+        listing.hero = hero;
+        listing.price = price;
+        listing.currency = currency;
+        listing.created_at = clock.unix_timestamp;
+        listing.bump = ctx.bumps.listing;
+
+        msg!("Hero {} listed for {} ({:?})", hero_id, price, currency);
+        emit!(HeroListed {
+            seller: listing.seller,
+            listing: listing.key(),
+            hero_id,
+            rarity,
+            price,
+            currency,
+        });
 
-        Ok(GameInfoData {
-            authority: global_state.authority,
-            dev_treasury: global_state.dev_treasury,
-            reward_token_mint: global_state.reward_token_mint,
-            game_has_started: global_state.game_has_started,
-            paused: global_state.paused,
-            start_block: global_state.start_block,
-            house_count: global_state.house_count,
-            unique_heroes_count: global_state.unique_heroes_count,
-            total_hash_power: global_state.total_hash_power,
-            initial_house_price: global_state.initial_house_price,
-            initial_bombcoin_per_block: global_state.initial_bombcoin_per_block,
-            current_bombcoin_per_block: global_state.get_bombcoin_per_block(),
-            halving_interval: global_state.halving_interval,
-            burn_pct: global_state.burn_pct,
-            referral_fee: global_state.referral_fee,
-            rewards_precision: global_state.rewards_precision,
-            total_mined: global_state.total_mined,
-            total_burned: global_state.total_burned,
-            reward_pool: global_state.reward_pool,
-            blocks_until_next_halving: global_state.blocks_until_next_halving(),
-        })
+        Ok(())
     }
-}
 
-// ============================================================================
-// MASTERCHEF HELPER FUNCTIONS (Outside #[program] module)
-// ============================================================================
+    /// Cancel a listing: the hero returns to the seller's inventory under its old ID
+    ///
+    /// The page for that ID is passed as a remaining account.
+    pub fn cancel_listing<'info>(ctx: Context<'_, '_, 'info, 'info, CancelListing<'info>>) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        let listing = &ctx.accounts.listing;
+        let mut pages = HeroPages::load(user_account, ctx.remaining_accounts)?;
 
-/// Update global pool accumulator (MasterChef-style)
-/// MUST be called before ANY user action that affects power or rewards
-///
-/// Formula: acc_bombcoin_per_power += (elapsed_time * emission_rate) / total_hash_power
-///
-/// This ensures fair reward distribution based on % of global hash power
-fn update_pool(global_state: &mut GlobalState, current_time: i64) -> Result<()> {
-    // If no one is mining, nothing to update
-    if global_state.total_hash_power == 0 {
-        return Ok(());
-    }
+        require!(user_account.has_inventory_space(1), GameError::InventoryFull);
 
-    // If game hasn't started, nothing to update
-    if global_state.start_block == 0 {
-        return Ok(());
-    }
+        let hero_id = listing.hero.id;
+        store_hero(user_account, &mut pages, listing.hero.clone())?;
+        pages.exit()?;
 
-    // Calculate elapsed time since game start
-    let elapsed = (current_time - global_state.start_block) as u64;
+        msg!("Listing for hero {} cancelled", hero_id);
+        emit!(ListingCancelled {
+            seller: listing.seller,
+            listing: listing.key(),
+            hero_id,
+        });
 
-    // Get current emission rate (BOMBcoin per second)
-    // initial_bombcoin_per_block is actually per second in our model
-    let emission_rate = global_state.get_bombcoin_per_block();
+        Ok(())
+    }
 
-    // Calculate total coins emitted since game start
-    let total_emitted = elapsed.checked_mul(emission_rate)
-        .ok_or(GameError::ArithmeticOverflow)?;
+    /// Buy a listed hero: pays the seller, splits the marketplace fee into burn/treasury
+    /// (burn_pct semantics) and moves the hero into the buyer's inventory under a new ID
+    ///
+    /// The page for the buyer's next hero ID is passed as a remaining account.
+    pub fn buy_listing<'info>(ctx: Context<'_, '_, 'info, 'info, BuyListing<'info>>) -> Result<()> {
+        let global_state = &ctx.accounts.global_state;
+        let clock = Clock::get()?;
 
-    // Calculate acc_bombcoin_per_power using high precision
-    // Formula: (total_emitted * PRECISION) / total_hash_power
-    let precision = global_state.rewards_precision as u128;
-    let total_emitted_scaled = (total_emitted as u128).checked_mul(precision)
-        .ok_or(GameError::ArithmeticOverflow)?;
+        require!(!global_state.paused, GameError::GamePaused);
+        require!(
+            ctx.accounts.listing.seller != ctx.accounts.user.key(),
+            GameError::CannotBuyOwnListing
+        );
+        require!(
+            ctx.accounts.user_account.has_inventory_space(1),
+            GameError::InventoryFull
+        );
 
-    let new_acc = total_emitted_scaled.checked_div(global_state.total_hash_power as u128)
-        .ok_or(GameError::DivisionByZero)?;
+        let price = ctx.accounts.listing.price;
+        let currency = ctx.accounts.listing.currency;
+        let (fee, proceeds) = calculate_marketplace_fee(price, global_state.marketplace_fee_bps);
+        let (burn_amount, treasury_amount) = calculate_burn_split(fee, global_state.burn_pct);
+
+        match currency {
+            ListingCurrency::Sol => {
+                let buyer = ctx.accounts.user.to_account_info();
+                transfer_lamports(&buyer, &ctx.accounts.seller.to_account_info(), proceeds)?;
+                transfer_lamports(&buyer, &ctx.accounts.dev_treasury.to_account_info(), treasury_amount)?;
+                transfer_lamports(&buyer, &ctx.accounts.incinerator.to_account_info(), burn_amount)?;
+            }
+            ListingCurrency::Bombcoin => {
+                let (Some(mint), Some(buyer_tokens), Some(seller_tokens), Some(treasury_tokens)) = (
+                    ctx.accounts.reward_token_mint.as_ref(),
+                    ctx.accounts.buyer_token_account.as_ref(),
+                    ctx.accounts.seller_token_account.as_ref(),
+                    ctx.accounts.treasury_token_account.as_ref(),
+                ) else {
+                    return err!(GameError::MissingPaymentAccounts);
+                };
+
+                // Proceeds and treasury share must land in the seller's and treasury's ATAs
+                require_keys_eq!(
+                    seller_tokens.key(),
+                    get_associated_token_address(&ctx.accounts.seller.key(), &mint.key()),
+                    GameError::InvalidPaymentAccount
+                );
+                require_keys_eq!(
+                    treasury_tokens.key(),
+                    get_associated_token_address(&global_state.dev_treasury, &mint.key()),
+                    GameError::InvalidPaymentAccount
+                );
 
-    global_state.cumulative_bombcoin_per_power = new_acc;
+                let token_program = ctx.accounts.token_program.to_account_info();
+                let buyer = ctx.accounts.user.to_account_info();
+                for (to, amount) in [(seller_tokens, proceeds), (treasury_tokens, treasury_amount)] {
+                    if amount > 0 {
+                        token::transfer(
+                            CpiContext::new(
+                                token_program.clone(),
+                                token::Transfer {
+                                    from: buyer_tokens.to_account_info(),
+                                    to: to.to_account_info(),
+                                    authority: buyer.clone(),
+                                },
+                            ),
+                            amount,
+                        )?;
+                    }
+                }
+                if burn_amount > 0 {
+                    token::burn(
+                        CpiContext::new(
+                            token_program,
+                            token::Burn {
+                                mint: mint.to_account_info(),
+                                from: buyer_tokens.to_account_info(),
+                                authority: buyer,
+                            },
+                        ),
+                        burn_amount,
+                    )?;
+                }
+            }
+        }
+        if currency == ListingCurrency::Bombcoin {
+            ctx.accounts.global_state.record_token_burn(burn_amount)?;
+        }
 
-    msg!(
-        "Pool updated: acc_per_power={}, total_power={}, emission_rate={}/s",
-        new_acc,
-        global_state.total_hash_power,
-        emission_rate
-    );
+        // Hero joins the buyer's inventory under the buyer's next ID
+        let user_account = &mut ctx.accounts.user_account;
+        let mut pages = HeroPages::load(user_account, ctx.remaining_accounts)?;
+        let mut hero = ctx.accounts.listing.hero.clone();
+        hero.id = user_account
+            .allocate_hero_id()
+            .ok_or(GameError::HeroIdsExhausted)?;
+        hero.last_action_time = clock.unix_timestamp;
+        let hero_id = hero.id;
+        store_hero(user_account, &mut pages, hero)?;
+        pages.exit()?;
 
-    Ok(())
-}
+        msg!(
+            "Listing {} filled for {} ({:?}): fee {} (burn: {}, treasury: {})",
+            ctx.accounts.listing.key(),
+            price,
+            currency,
+            fee,
+            burn_amount,
+            treasury_amount
+        );
+        emit!(ListingFilled {
+            seller: ctx.accounts.listing.seller,
+            buyer: user_account.owner,
+            listing: ctx.accounts.listing.key(),
+            hero_id,
+            price,
+            currency,
+            burn_amount,
+            treasury_amount,
+        });
 
-/// Harvest pending rewards for a user (auto-claim before power changes)
-///
-/// Formula: pending = (user.power * pool.acc_bombcoin_per_power / PRECISION) - user.reward_debt
-///
-/// Adds pending rewards to player_pending_rewards for later minting
-fn harvest_pending_rewards(
-    global_state: &GlobalState,
-    user_account: &mut UserAccount,
-) -> Result<u64> {
-    // If user has no power, nothing to harvest
-    if user_account.player_power == 0 {
-        return Ok(0);
+        Ok(())
     }
 
-    let precision = global_state.rewards_precision as u128;
+    /// Recover HP for heroes on grid (location-based multipliers: bench 1x, restroom 3x)
+    /// Only grid heroes on the hero pages passed as remaining accounts recover
+    pub fn recover_hp<'info>(ctx: Context<'_, '_, 'info, 'info, RecoverHP<'info>>) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp;
+        let mut pages = HeroPages::load(user_account, ctx.remaining_accounts)?;
+
+        let house_occupied_copy = user_account.house_occupied_coords.clone();
 
-    // Calculate total earned: (user_power * acc_per_power) / PRECISION
-    let total_earned_scaled = (user_account.player_power as u128)
-        .checked_mul(global_state.cumulative_bombcoin_per_power)
-        .ok_or(GameError::ArithmeticOverflow)?;
+        for tile in house_occupied_copy {
+            if tile.is_empty() {
+                continue;
+            }
 
-    let total_earned = total_earned_scaled.checked_div(precision)
-        .ok_or(GameError::DivisionByZero)? as u64;
+            let hero_id = tile.hero_id;
+            if !pages.has_page_for(hero_id) {
+                continue;
+            }
+            let hero = pages.hero_mut(hero_id)?;
+            let elapsed_seconds = (current_time - hero.last_action_time) as u64;
 
-    // Calculate pending: total_earned - reward_debt
-    // reward_debt tracks what we've already paid out
-    let reward_debt_coins = (user_account.reward_debt.checked_div(precision)
-        .ok_or(GameError::DivisionByZero)?) as u64;
+            if elapsed_seconds > 0 {
+                let location_multiplier_bps = location_recovery_bps(tile.is_restroom);
 
-    let pending = total_earned.saturating_sub(reward_debt_coins);
+                let recovery = hero.calculate_hp_recovery(elapsed_seconds, location_multiplier_bps);
+                hero.hp = hero.hp.saturating_add(recovery).min(hero.max_hp);
+                hero.last_action_time = current_time;
 
-    if pending > 0 {
-        // Add to pending rewards (will be minted in claim_rewards)
-        user_account.player_pending_rewards = user_account.player_pending_rewards
-            .checked_add(pending)
-            .ok_or(GameError::ArithmeticOverflow)?;
+                msg!(
+                    "Hero {} recovered {} HP ({} bps multiplier)",
+                    hero_id,
+                    recovery,
+                    location_multiplier_bps
+                );
+                emit!(HpRecovered {
+                    user: user_account.owner,
+                    hero_id,
+                    recovered: recovery,
+                    is_restroom: tile.is_restroom,
+                });
+            }
+        }
+        pages.exit()?;
 
-        msg!("Harvested {} pending rewards for user", pending);
+        Ok(())
     }
 
-    Ok(pending)
-}
+    /// Upgrade house to next level (expands grid, costs coins, has cooldown)
+    pub fn upgrade_house(ctx: Context<UpgradeHouse>) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        let now = Clock::get()?.unix_timestamp;
 
-// ============================================================================
+        // Check cost
+        let (cost, next) = house_upgrade_cost(&ctx.accounts.global_state, user_account, now)?;
+        require!(user_account.coin_balance >= cost, GameError::InsufficientCoins);
+
+        // Deduct coins
+        user_account.coin_balance -= cost;
+
+        apply_house_upgrade(user_account, next, now, cost, None);
+        Ok(())
+    }
+
+    /// `upgrade_house` paid in SPL BOMBcoin from the player's token account
+    /// The burn_pct share is burned and the rest goes to the token treasury
+    pub fn upgrade_house_with_tokens(ctx: Context<UpgradeHouseWithTokens>) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        let user_account = &mut ctx.accounts.user_account;
+        let now = Clock::get()?.unix_timestamp;
+
+        let (cost, next) = house_upgrade_cost(global_state, user_account, now)?;
+        let (burn_amount, treasury_amount) = calculate_burn_split(cost, global_state.burn_pct);
+        global_state.record_token_burn(burn_amount)?;
+
+        apply_house_upgrade(user_account, next, now, cost, Some((burn_amount, treasury_amount)));
+
+        settle_token_payment(
+            &ctx.accounts.token_program,
+            &ctx.accounts.reward_token_mint,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.token_treasury,
+            &ctx.accounts.user,
+            burn_amount,
+            treasury_amount,
+        )
+    }
+
+    // ========== Staking ==========
+
+    /// Open the stake account of the signing wallet
+    pub fn open_stake_account(ctx: Context<OpenStakeAccount>) -> Result<()> {
+        let stake_account = &mut ctx.accounts.stake_account;
+        stake_account.owner = ctx.accounts.user.key();
+        stake_account.amount = 0;
+        stake_account.reward_debt = 0;
+        stake_account.pending_dividends = 0;
+        stake_account.bump = ctx.bumps.stake_account;
+
+        msg!("Stake account opened for {}", stake_account.owner);
+        emit!(StakeAccountOpened { user: stake_account.owner });
+        Ok(())
+    }
+
+    /// Stake SPL BOMBcoin into the vault
+    /// Dividends already in the reward pool go to the stakers before this one joins
+    pub fn stake(ctx: Context<StakeTokens>, amount: u64) -> Result<()> {
+        require!(!ctx.accounts.global_state.paused, GameError::GamePaused);
+        require!(amount > 0, GameError::InvalidAmount);
+
+        let stake_pool = &mut ctx.accounts.stake_pool;
+        let stake_account = &mut ctx.accounts.stake_account;
+        distribute_dividends(&mut ctx.accounts.global_state, stake_pool)?;
+        harvest_dividends(stake_pool, stake_account)?;
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.user_token_account.to_account_info(),
+                    to: ctx.accounts.stake_vault.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        stake_account.amount = stake_account.amount
+            .checked_add(amount)
+            .ok_or(GameError::ArithmeticOverflow)?;
+        stake_pool.total_staked = stake_pool.total_staked
+            .checked_add(amount)
+            .ok_or(GameError::ArithmeticOverflow)?;
+        sync_stake_debt(stake_pool, stake_account)?;
+
+        msg!("Staked {} BOMBcoin, stake: {}", amount, stake_account.amount);
+        emit!(Staked {
+            user: stake_account.owner,
+            amount,
+            stake: stake_account.amount,
+            total_staked: stake_pool.total_staked,
+        });
+        Ok(())
+    }
+
+    /// Withdraw staked BOMBcoin from the vault (allowed while paused)
+    /// Dividends earned so far stay claimable
+    pub fn unstake(ctx: Context<StakeTokens>, amount: u64) -> Result<()> {
+        require!(amount > 0, GameError::InvalidAmount);
+
+        let stake_pool = &mut ctx.accounts.stake_pool;
+        let stake_account = &mut ctx.accounts.stake_account;
+        require!(stake_account.amount >= amount, GameError::InsufficientStake);
+
+        distribute_dividends(&mut ctx.accounts.global_state, stake_pool)?;
+        harvest_dividends(stake_pool, stake_account)?;
+
+        stake_account.amount -= amount;
+        stake_pool.total_staked -= amount;
+        sync_stake_debt(stake_pool, stake_account)?;
+
+        let seeds = &[b"global_state".as_ref(), &[ctx.accounts.global_state.bump]];
+        let signer = &[&seeds[..]];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.stake_vault.to_account_info(),
+                    to: ctx.accounts.user_token_account.to_account_info(),
+                    authority: ctx.accounts.global_state.to_account_info(),
+                },
+                signer,
+            ),
+            amount,
+        )?;
+
+        let stake_account = &ctx.accounts.stake_account;
+        msg!("Unstaked {} BOMBcoin, stake: {}", amount, stake_account.amount);
+        emit!(Unstaked {
+            user: stake_account.owner,
+            amount,
+            stake: stake_account.amount,
+            total_staked: ctx.accounts.stake_pool.total_staked,
+        });
+        Ok(())
+    }
+
+    /// Mint the staker's dividends to their token account
+    pub fn claim_dividends(ctx: Context<StakeTokens>) -> Result<()> {
+        require!(!ctx.accounts.global_state.paused, GameError::GamePaused);
+
+        let stake_pool = &mut ctx.accounts.stake_pool;
+        let stake_account = &mut ctx.accounts.stake_account;
+        distribute_dividends(&mut ctx.accounts.global_state, stake_pool)?;
+        harvest_dividends(stake_pool, stake_account)?;
+
+        let amount = stake_account.pending_dividends;
+        require!(amount > 0, GameError::NoRewardsToClaim);
+        stake_account.pending_dividends = 0;
+        stake_pool.total_claimed = stake_pool.total_claimed
+            .checked_add(amount)
+            .ok_or(GameError::ArithmeticOverflow)?;
+
+        // The reward pool is un-minted BOMBcoin, like coin_balance
+        mint_reward(
+            &ctx.accounts.token_program,
+            &ctx.accounts.reward_token_mint,
+            ctx.accounts.user_token_account.to_account_info(),
+            &ctx.accounts.global_state,
+            ctx.accounts.global_state.bump,
+            amount,
+        )?;
+        ctx.accounts.global_state.record_token_mint(amount)?;
+
+        let user = ctx.accounts.stake_account.owner;
+        msg!("Claimed {} BOMBcoin in dividends", amount);
+        emit!(DividendsClaimed { user, amount });
+        Ok(())
+    }
+
+    // ========================================================================
+    // COMPUTE BENCHMARKS (`bench` feature only, never deployed)
+    // ========================================================================
+    // Zero-copy versions of claim_rewards, bulk_move_to_map and recover_hp,
+    // run against snapshots of a player's accounts and of the global state to
+    // compare compute units. They never write to the live accounts.
+
+    /// Snapshot the global state and the player's hot-path state into
+    /// a zero-copy GlobalStateZc and UserAccountZc
+    #[cfg(feature = "bench")]
+    pub fn bench_snapshot_user(ctx: Context<BenchSnapshotUser>) -> Result<()> {
+        let bump = ctx.bumps.global_state_zc;
+        ctx.accounts
+            .global_state_zc
+            .load_init()?
+            .copy_from(&ctx.accounts.global_state, bump)?;
+
+        let bump = ctx.bumps.user_account_zc;
+        ctx.accounts
+            .user_account_zc
+            .load_init()?
+            .copy_from(&ctx.accounts.user_account, bump);
+        Ok(())
+    }
+
+    /// Snapshot one of the player's hero pages into a zero-copy HeroPageZc
+    #[cfg(feature = "bench")]
+    pub fn bench_snapshot_page(ctx: Context<BenchSnapshotPage>, page_index: u16) -> Result<()> {
+        let hero_page = &ctx.accounts.hero_page;
+        let mut page = ctx.accounts.hero_page_zc.load_init()?;
+        page.owner = hero_page.owner;
+        page.index = page_index;
+        page.bump = ctx.bumps.hero_page_zc;
+        for hero in hero_page.heroes.iter().flatten() {
+            page.insert(hero);
+        }
+        Ok(())
+    }
+
+    /// `bulk_move_to_map` on the zero-copy accounts
+    /// Zero-copy pages of the moved heroes and of all mining heroes are passed as remaining accounts.
+    #[cfg(feature = "bench")]
+    pub fn bench_bulk_move_zc<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BenchZc<'info>>,
+        hero_ids: Vec<u16>,
+    ) -> Result<()> {
+        let mut global_state = ctx.accounts.global_state_zc.load_mut()?;
+        let mut user_account = ctx.accounts.user_account_zc.load_mut()?;
+        let current_time = Clock::get()?.unix_timestamp;
+        let pages = HeroPagesZc::load_for(user_account.owner, ctx.remaining_accounts)?;
+
+        require!(!global_state.is_paused(), GameError::GamePaused);
+
+        for (i, &hero_id) in hero_ids.iter().enumerate() {
+            let hero = pages.hero(hero_id)?;
+            require!(
+                !user_account.is_mining(hero_id) && !hero_ids[..i].contains(&hero_id),
+                GameError::HeroAlreadyOnMap
+            );
+            require!(!hero.is_sleeping(), GameError::HeroIsSleeping);
+        }
+
+        let new_map_size = user_account.active_map_len as usize + hero_ids.len();
+        require!(new_map_size <= UserAccount::MAX_MAP_HEROES, GameError::MapFull);
+
+        global_state.update_pool(current_time)?;
+        user_account.harvest(&global_state)?;
+
+        for &hero_id in hero_ids.iter() {
+            user_account.remove_from_grid(hero_id);
+            pages.update_hero(hero_id, |hero| hero.last_action_time = current_time)?;
+            user_account.push_to_map(hero_id);
+
+            msg!("Hero {} moved to map", hero_id);
+        }
+
+        let map_power = pages.map_power(user_account.active_map())?;
+        let (old_power, new_power) = user_account.sync_power(&mut global_state, map_power)?;
+
+        msg!(
+            "Bulk moved {} heroes to map: power {} → {}, global_power: {}, debt: {}",
+            { user_account.active_map_len },
+            old_power,
+            new_power,
+            { global_state.total_hash_power },
+            { user_account.reward_debt }
+        );
+        for hero_id in hero_ids {
+            emit!(HeroMovedToMap {
+                user: user_account.owner,
+                hero_id,
+                player_power: new_power,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// `claim_rewards(CoinBalance)` on the zero-copy accounts, without referral payouts
+    /// (benchmark against a player that has no referrer). Nothing is minted: the reward
+    /// only lands in the snapshot's coin balance.
+    /// Zero-copy pages of all mining heroes are passed as remaining accounts.
+    #[cfg(feature = "bench")]
+    pub fn bench_claim_zc<'info>(ctx: Context<'_, '_, 'info, 'info, BenchZc<'info>>) -> Result<()> {
+        let mut global_state = ctx.accounts.global_state_zc.load_mut()?;
+        let mut user_account = ctx.accounts.user_account_zc.load_mut()?;
+        let current_time = Clock::get()?.unix_timestamp;
+        let pages = HeroPagesZc::load_for(user_account.owner, ctx.remaining_accounts)?;
+
+        require!(!global_state.is_paused(), GameError::GamePaused);
+
+        global_state.update_pool(current_time)?;
+        user_account.harvest(&global_state)?;
+
+        for hero_id in user_account.active_map() {
+            pages.update_hero(hero_id, |hero| {
+                if !hero.is_sleeping() {
+                    let elapsed = (current_time - hero.last_action_time) as u64;
+                    hero.apply_mining(elapsed);
+                    hero.last_action_time = current_time;
+                }
+            })?;
+        }
+
+        let pending_reward = user_account.player_pending_rewards;
+        require!(pending_reward > 0, GameError::NoRewardsToClaim);
+
+        let mintable_supply = global_state.remaining_supply();
+        require!(mintable_supply > 0, GameError::MaxSupplyReached);
+        let gross_reward = pending_reward.min(mintable_supply);
+        let forfeited_reward = pending_reward - gross_reward;
+
+        let tier_bonuses = calculate_tier_bonuses(gross_reward, &global_state.referral_tier_fees());
+        let referral_bonus: u64 = tier_bonuses.iter().sum();
+        let net_reward = gross_reward.saturating_sub(referral_bonus);
+
+        global_state.total_mined = global_state.total_mined
+            .checked_add(gross_reward)
+            .ok_or(GameError::ArithmeticOverflow)?;
+        user_account.coin_balance = { user_account.coin_balance }
+            .checked_add(net_reward)
+            .ok_or(GameError::ArithmeticOverflow)?;
+        user_account.player_pending_rewards = 0;
+
+        let map_power = pages.map_power(user_account.active_map())?;
+        let (_, current_total_hmp) = user_account.sync_power(&mut global_state, map_power)?;
+
+        msg!(
+            "Claimed {} coins (net: {} to {:?}, referral: {}), Current HMP: {}, New debt: {}",
+            gross_reward,
+            net_reward,
+            RewardDestination::CoinBalance,
+            referral_bonus,
+            current_total_hmp,
+            { user_account.reward_debt }
+        );
+        emit!(RewardsClaimed {
+            user: user_account.owner,
+            gross_reward,
+            net_reward,
+            destination: RewardDestination::CoinBalance,
+            referral_bonus,
+            referrer: None,
+            player_power: current_total_hmp,
+            reward_debt: user_account.reward_debt,
+            forfeited_reward,
+        });
+
+        Ok(())
+    }
+
+    /// `recover_hp` on the zero-copy accounts
+    /// Only grid heroes on the zero-copy pages passed as remaining accounts recover
+    #[cfg(feature = "bench")]
+    pub fn bench_recover_zc<'info>(ctx: Context<'_, '_, 'info, 'info, BenchRecoverZc<'info>>) -> Result<()> {
+        let user_account = ctx.accounts.user_account_zc.load()?;
+        let current_time = Clock::get()?.unix_timestamp;
+        let pages = HeroPagesZc::load_for(user_account.owner, ctx.remaining_accounts)?;
+
+        for (_, hero_id, is_restroom) in user_account.occupied_tiles() {
+            if !pages.has_page_for(hero_id) {
+                continue;
+            }
+
+            let recovered = pages.update_hero(hero_id, |hero| {
+                let elapsed_seconds = (current_time - hero.last_action_time) as u64;
+                if elapsed_seconds == 0 {
+                    return None;
+                }
+
+                let location_multiplier_bps = location_recovery_bps(is_restroom);
+                let recovery = hero.calculate_hp_recovery(elapsed_seconds, location_multiplier_bps);
+                hero.hp = hero.hp.saturating_add(recovery).min(hero.max_hp);
+                hero.last_action_time = current_time;
+                Some((recovery, location_multiplier_bps))
+            })?;
+
+            if let Some((recovery, location_multiplier_bps)) = recovered {
+                msg!(
+                    "Hero {} recovered {} HP ({} bps multiplier)",
+                    hero_id,
+                    recovery,
+                    location_multiplier_bps
+                );
+                emit!(HpRecovered {
+                    user: user_account.owner,
+                    hero_id,
+                    recovered: recovery,
+                    is_restroom,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // READ-ONLY VIEW FUNCTIONS (For Frontend)
+    // ========================================================================
+
+    /// Calculate pending rewards without claiming (read-only simulation)
+    /// Mirrors update_pool + harvest_pending_rewards at the current time
+    /// Hero pages of all mining heroes are passed as remaining accounts
+    pub fn pending_rewards<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ViewUserAccount<'info>>,
+    ) -> Result<PendingRewardsData> {
+        let global_state = &ctx.accounts.global_state;
+        let user_account = &ctx.accounts.user_account;
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp;
+        let pages = HeroPages::load_for(user_account.owner, ctx.remaining_accounts)?;
+
+        // Count mining heroes (without mutating)
+        let mut active_hero_count = 0u16;
+        for &hero_id in user_account.active_map.iter() {
+            if pages.hero(hero_id)?.is_active() {
+                active_hero_count += 1;
+            }
+        }
+
+        // Project the accumulator forward to now
+        let (acc_per_power, _) = projected_acc_per_power(global_state, current_time)?;
+        let elapsed_seconds = if global_state.start_block == 0 {
+            0
+        } else {
+            current_time.saturating_sub(global_state.last_reward_time.max(global_state.start_block)).max(0) as u64
+        };
+
+        // Already harvested + not yet harvested
+        let harvestable = calculate_pending_reward(
+            user_account.player_power,
+            acc_per_power,
+            user_account.reward_debt,
+            global_state.rewards_precision,
+        )?;
+        let gross_reward = user_account.player_pending_rewards
+            .checked_add(harvestable)
+            .ok_or(GameError::ArithmeticOverflow)?;
+
+        // Calculate referral split (all tiers)
+        let referral_bonus: u64 = calculate_tier_bonuses(gross_reward, &global_state.referral_tier_fees())
+            .iter()
+            .sum();
+        let net_reward = gross_reward.saturating_sub(referral_bonus);
+
+        Ok(PendingRewardsData {
+            gross_reward,
+            net_reward,
+            referral_bonus,
+            total_hmp: user_account.player_power,
+            active_hero_count,
+            elapsed_seconds,
+            current_bombcoin_per_block: global_state.get_bombcoin_per_block(current_time),
+        })
+    }
+
+    /// Get comprehensive player stats
+    /// Sleeping heroes are counted on the hero pages passed as remaining accounts
+    pub fn get_player_stats<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ViewUserAccount<'info>>,
+    ) -> Result<PlayerStatsData> {
+        let global_state = &ctx.accounts.global_state;
+        let user_account = &ctx.accounts.user_account;
+        let clock = Clock::get()?;
+        let pages = HeroPages::load_for(user_account.owner, ctx.remaining_accounts)?;
+
+        // Count heroes by location
+        let heroes_on_map = user_account.active_map.len();
+        let mut heroes_on_grid = 0;
+        let mut heroes_in_restroom = 0;
+        let heroes_in_inventory = user_account.total_heroes();
+        let heroes_sleeping = pages
+            .heroes()
+            .chain(user_account.inventory.iter())
+            .filter(|hero| hero.is_sleeping())
+            .count();
+
+        for tile in &user_account.house_occupied_coords {
+            if !tile.is_empty() {
+                heroes_on_grid += 1;
+                if tile.is_restroom {
+                    heroes_in_restroom += 1;
+                }
+            }
+        }
+
+        // House upgrade info
+        let house_level = global_state.house_level(user_account.house_level);
+        let next_upgrade_cost = house_level.upgrade_cost;
+        let upgrade_cooldown_remaining = if user_account.last_house_upgrade_timestamp > 0 {
+            let cooldown = house_level.upgrade_cooldown;
+            let elapsed = clock.unix_timestamp - user_account.last_house_upgrade_timestamp;
+            cooldown.saturating_sub(elapsed)
+        } else {
+            0
+        };
+
+        // Grid info
+        let max_restroom_slots = house_level.max_restroom_slots;
+        let restroom_slots_used = user_account.count_restroom_slots();
+
+        Ok(PlayerStatsData {
+            owner: user_account.owner,
+            house_level: user_account.house_level,
+            grid_width: user_account.grid_width,
+            grid_height: user_account.grid_height,
+            coin_balance: user_account.coin_balance,
+            player_power: user_account.player_power,
+            heroes_total: heroes_in_inventory as u64,
+            heroes_on_map: heroes_on_map as u64,
+            heroes_on_grid: heroes_on_grid as u64,
+            heroes_in_restroom: heroes_in_restroom as u64,
+            heroes_sleeping: heroes_sleeping as u64,
+            max_restroom_slots: max_restroom_slots as u64,
+            restroom_slots_used: restroom_slots_used as u64,
+            next_upgrade_cost,
+            upgrade_cooldown_remaining,
+            can_upgrade: (user_account.house_level as usize) < MAX_HOUSE_LEVEL && upgrade_cooldown_remaining == 0,
+            referrer: user_account.referrer,
+            referral_bonus_paid: user_account.referral_bonus_paid,
+            referral_count: user_account.referrals.len() as u64,
+        })
+    }
+
+    /// Get referral chain fees and the player's referral earnings per tier
+    pub fn get_referral_info(ctx: Context<ViewUserAccount>) -> Result<ReferralInfoData> {
+        let global_state = &ctx.accounts.global_state;
+        let user_account = &ctx.accounts.user_account;
+
+        Ok(ReferralInfoData {
+            referrer: user_account.referrer,
+            referral_count: user_account.referrals.len() as u64,
+            referral_bonus_paid: user_account.referral_bonus_paid,
+            tier_fees: global_state.referral_tier_fees(),
+            earnings_by_tier: user_account.referral_earnings_by_tier,
+        })
+    }
+
+    /// Get account capacity: current size, bytes in use and remaining slots per collection
+    pub fn get_account_capacity(ctx: Context<ViewUserAccount>) -> Result<AccountCapacityData> {
+        let user_account = &ctx.accounts.user_account;
+        let grid_tiles = user_account.grid_width as u64 * user_account.grid_height as u64;
+
+        Ok(AccountCapacityData {
+            account_len: user_account.to_account_info().data_len() as u64,
+            required_len: user_account.required_len() as u64,
+            max_len: UserAccount::MAX_LEN as u64,
+            heroes: user_account.total_heroes() as u64,
+            max_heroes: UserAccount::MAX_HEROES as u64,
+            remaining_hero_slots: user_account.remaining_hero_slots() as u64,
+            remaining_map_slots: UserAccount::MAX_MAP_HEROES
+                .saturating_sub(user_account.active_map.len()) as u64,
+            remaining_grid_tiles: grid_tiles
+                .saturating_sub(user_account.house_occupied_coords.len() as u64),
+            referrals: user_account.referrals.len() as u64,
+            max_referrals: UserAccount::MAX_REFERRALS as u64,
+            remaining_referral_slots: UserAccount::MAX_REFERRALS
+                .saturating_sub(user_account.referrals.len()) as u64,
+        })
+    }
+
+    /// Get detailed hero info by ID (the hero's page is passed as a remaining account)
+    pub fn get_hero_details<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ViewUserAccount<'info>>,
+        hero_id: u16,
+    ) -> Result<HeroDetailsData> {
+        let user_account = &ctx.accounts.user_account;
+        let clock = Clock::get()?;
+        let pages = HeroPages::load_for(user_account.owner, ctx.remaining_accounts)?;
+
+        let hero = pages.hero(hero_id)?;
+
+        // Find hero location
+        let is_on_map = user_account.active_map.contains(&hero_id);
+        let grid_tile = user_account.find_hero_on_grid(hero_id);
+
+        let (grid_x, grid_y, is_in_restroom) = if let Some(tile) = grid_tile {
+            (Some(tile.x), Some(tile.y), tile.is_restroom)
+        } else {
+            (None, None, false)
+        };
+
+        // Calculate time-based stats
+        let elapsed_since_action = (clock.unix_timestamp - hero.last_action_time) as u64;
+
+        let estimated_hp_drain = if is_on_map {
+            hero.calculate_hp_drain(elapsed_since_action)
+        } else {
+            0
+        };
+
+        let estimated_hp_recovery = if grid_tile.is_some() && !is_on_map {
+            hero.calculate_hp_recovery(elapsed_since_action, location_recovery_bps(is_in_restroom))
+        } else {
+            0
+        };
+
+        let estimated_current_hp = if is_on_map {
+            hero.hp.saturating_sub(estimated_hp_drain)
+        } else if grid_tile.is_some() {
+            hero.hp.saturating_add(estimated_hp_recovery).min(hero.max_hp)
+        } else {
+            hero.hp
+        };
+
+        Ok(HeroDetailsData {
+            id: hero.id,
+            skin_id: hero.skin_id,
+            rarity: hero.rarity,
+            power: hero.power,
+            speed: hero.speed,
+            stamina: hero.stamina,
+            max_stamina: hero.max_stamina,
+            bomb_number: hero.bomb_number,
+            bomb_range: hero.bomb_range,
+            hp: hero.hp,
+            max_hp: hero.max_hp,
+            estimated_current_hp,
+            hmp: hero.calculate_hmp(),
+            is_sleeping: hero.is_sleeping(),
+            is_on_map,
+            is_on_grid: grid_tile.is_some(),
+            grid_x,
+            grid_y,
+            is_in_restroom,
+            last_action_time: hero.last_action_time,
+            elapsed_since_action,
+            level: hero.level,
+            xp: hero.xp,
+            xp_for_next_level: Hero::xp_for_level(hero.level.saturating_add(1)),
+            level_up_cost: hero.level_up_cost(),
+        })
+    }
+
+    /// Get grid state with all tiles
+    pub fn get_grid_state(ctx: Context<ViewUserAccount>) -> Result<GridStateData> {
+        let user_account = &ctx.accounts.user_account;
+
+        let tiles: Vec<GridTileData> = user_account
+            .house_occupied_coords
+            .iter()
+            .map(|tile| GridTileData {
+                x: tile.x,
+                y: tile.y,
+                hero_id: if tile.is_empty() {
+                    None
+                } else {
+                    Some(tile.hero_id)
+                },
+                is_restroom: tile.is_restroom,
+                is_empty: tile.is_empty(),
+            })
+            .collect();
+
+        Ok(GridStateData {
+            grid_width: user_account.grid_width,
+            grid_height: user_account.grid_height,
+            house_level: user_account.house_level,
+            occupied_tiles: tiles,
+            total_tiles: (user_account.grid_width as u64) * (user_account.grid_height as u64),
+            tiles_occupied: user_account
+                .house_occupied_coords
+                .iter()
+                .filter(|t| !t.is_empty())
+                .count() as u64,
+        })
+    }
+
+    /// Get global game info
+    pub fn get_game_info(ctx: Context<ViewGlobalState>) -> Result<GameInfoData> {
+        let global_state = &ctx.accounts.global_state;
+        let clock = Clock::get()?;
+
+        Ok(GameInfoData {
+            authority: global_state.authority,
+            dev_treasury: global_state.dev_treasury,
+            reward_token_mint: global_state.reward_token_mint,
+            game_has_started: global_state.game_has_started,
+            paused: global_state.paused,
+            start_block: global_state.start_block,
+            house_count: global_state.house_count,
+            unique_heroes_count: global_state.unique_heroes_count,
+            total_hash_power: global_state.total_hash_power,
+            initial_house_price: global_state.initial_house_price,
+            initial_bombcoin_per_block: global_state.initial_bombcoin_per_block,
+            current_bombcoin_per_block: global_state.get_bombcoin_per_block(clock.unix_timestamp),
+            halving_interval: global_state.halving_interval,
+            emission_schedule: global_state.emission_schedule,
+            burn_pct: global_state.burn_pct,
+            referral_fee: global_state.referral_fee,
+            referral_tier_fees: global_state.referral_tier_fees(),
+            rewards_precision: global_state.rewards_precision,
+            max_supply: global_state.max_supply,
+            fusion_hero_count: global_state.fusion_hero_count,
+            fusion_success_bps: global_state.fusion_success_bps,
+            fusion_cost: global_state.fusion_cost,
+            marketplace_fee_bps: global_state.marketplace_fee_bps,
+            hero_price: global_state.hero_price,
+            max_hero_quantity: global_state.max_hero_quantity,
+            house_levels: global_state.house_levels,
+            remaining_supply: global_state.remaining_supply(),
+            total_emitted: global_state.total_emitted,
+            total_mined: global_state.total_mined,
+            total_burned: global_state.total_burned,
+            reward_pool: global_state.reward_pool,
+            blocks_until_next_halving: global_state.blocks_until_next_halving(clock.unix_timestamp),
+        })
+    }
+
+    /// Reconcile the BOMBcoin counters with the mint: everything the program minted,
+    /// minus the burn_pct shares and deposits it burned, should equal the supply
+    /// (holders can still burn their own tokens, which shows up as `burned_outside_program`)
+    pub fn get_token_supply_info(ctx: Context<ViewTokenSupply>) -> Result<TokenSupplyData> {
+        let global_state = &ctx.accounts.global_state;
+        let mint_supply = ctx.accounts.reward_token_mint.supply;
+        let expected_supply = global_state.expected_token_supply();
+
+        Ok(TokenSupplyData {
+            mint_supply,
+            expected_supply,
+            total_tokens_minted: global_state.total_tokens_minted,
+            total_tokens_burned: global_state.total_tokens_burned,
+            total_tokens_deposited: global_state.total_tokens_deposited,
+            supply_reduction: global_state.total_tokens_minted.saturating_sub(mint_supply),
+            burned_outside_program: expected_supply.saturating_sub(mint_supply),
+            total_burned: global_state.total_burned,
+            coins_burned: global_state.total_burned.saturating_sub(global_state.total_tokens_burned),
+            reconciled: mint_supply == expected_supply,
+        })
+    }
+
+    /// Get a staker's stake and claimable dividends, counting the reward pool
+    /// that the next staking action would distribute
+    pub fn get_stake_info(ctx: Context<ViewStake>) -> Result<StakeInfoData> {
+        let global_state = &ctx.accounts.global_state;
+        let stake_pool = &ctx.accounts.stake_pool;
+        let stake_account = &ctx.accounts.stake_account;
+
+        let (delta, _) = dividend_distribution(global_state.reward_pool, stake_pool.total_staked)?;
+        let acc_dividend_per_share = stake_pool.acc_dividend_per_share
+            .checked_add(delta)
+            .ok_or(GameError::ArithmeticOverflow)?;
+        let harvestable = calculate_pending_reward(
+            stake_account.amount,
+            acc_dividend_per_share,
+            stake_account.reward_debt,
+            DIVIDEND_PRECISION,
+        )?;
+
+        Ok(StakeInfoData {
+            owner: stake_account.owner,
+            amount: stake_account.amount,
+            pending_dividends: stake_account.pending_dividends
+                .checked_add(harvestable)
+                .ok_or(GameError::ArithmeticOverflow)?,
+            total_staked: stake_pool.total_staked,
+            reward_pool: global_state.reward_pool,
+            total_distributed: stake_pool.total_distributed,
+            total_claimed: stake_pool.total_claimed,
+        })
+    }
+}
+
+// ============================================================================
+// MASTERCHEF HELPER FUNCTIONS (Outside #[program] module)
+// ============================================================================
+
+/// Accumulator value and coins emitted at `current_time`, without mutating state
+///
+/// Only the window since `last_reward_time` is priced at the current total power,
+/// so changes in power never reprice emissions that were already accumulated.
+/// Returns (acc_per_power, emitted_in_window)
+fn projected_acc_per_power(global_state: &GlobalState, current_time: i64) -> Result<(u128, u64)> {
+    let acc = global_state.cumulative_bombcoin_per_power;
+
+    // If game hasn't started, nothing to update
+    if global_state.start_block == 0 {
+        return Ok((acc, 0));
+    }
+
+    // If no one is mining, nothing is emitted for this window
+    if global_state.total_hash_power == 0 {
+        return Ok((acc, 0));
+    }
+
+    let last_reward_time = global_state.last_reward_time.max(global_state.start_block);
+    if current_time <= last_reward_time {
+        return Ok((acc, 0));
+    }
+
+    // Coins emitted in this window only, split at halving boundaries
+    // initial_bombcoin_per_block is actually per second in our model
+    let emitted = calculate_emission(
+        global_state.emission_schedule,
+        global_state.initial_bombcoin_per_block,
+        global_state.halving_interval,
+        global_state.start_block,
+        global_state.total_emitted,
+        last_reward_time,
+        current_time,
+    )?;
+
+    // The window that reaches the cap only emits the remainder; admin credits share the cap
+    let emitted = cap_emission(
+        emitted,
+        global_state.total_emitted.saturating_add(global_state.total_coins_credited),
+        global_state.max_supply,
+    );
+
+    let delta = reward_per_power_delta(
+        emitted,
+        global_state.total_hash_power,
+        global_state.rewards_precision,
+    )?;
+
+    let new_acc = acc.checked_add(delta).ok_or(GameError::ArithmeticOverflow)?;
+    Ok((new_acc, emitted))
+}
+
+/// Update global pool accumulator (MasterChef-style)
+/// MUST be called before ANY user action that affects power or rewards
+///
+/// Formula: acc_bombcoin_per_power += emission(last_reward_time, now) / total_hash_power
+/// where the emission only covers the window since the previous update
+///
+/// This ensures fair reward distribution based on % of global hash power
+fn update_pool(global_state: &mut GlobalState, current_time: i64) -> Result<()> {
+    // If game hasn't started, nothing to update
+    if global_state.start_block == 0 {
+        return Ok(());
+    }
+
+    if current_time <= global_state.last_reward_time {
+        return Ok(());
+    }
+
+    let (new_acc, emitted) = projected_acc_per_power(global_state, current_time)?;
+    global_state.cumulative_bombcoin_per_power = new_acc;
+    global_state.total_emitted = global_state.total_emitted
+        .checked_add(emitted)
+        .ok_or(GameError::ArithmeticOverflow)?;
+    global_state.last_reward_time = current_time;
+
+    msg!(
+        "Pool updated: acc_per_power={}, total_power={}, emitted={}, emission_rate={}/s",
+        new_acc,
+        global_state.total_hash_power,
+        emitted,
+        global_state.get_bombcoin_per_block(current_time)
+    );
+
+    Ok(())
+}
+
+/// Harvest pending rewards for a user (auto-claim before power changes)
+///
+/// Formula: pending = (user.power * pool.acc_bombcoin_per_power - user.reward_debt) / PRECISION
+///
+/// Adds pending rewards to player_pending_rewards for later minting
+fn harvest_pending_rewards(
+    global_state: &GlobalState,
+    user_account: &mut UserAccount,
+) -> Result<u64> {
+    // If user has no power, nothing to harvest
+    if user_account.player_power == 0 {
+        return Ok(0);
+    }
+
+    let pending = calculate_pending_reward(
+        user_account.player_power,
+        global_state.cumulative_bombcoin_per_power,
+        user_account.reward_debt,
+        global_state.rewards_precision,
+    )?;
+
+    if pending > 0 {
+        // Add to pending rewards (will be minted in claim_rewards)
+        user_account.player_pending_rewards = user_account.player_pending_rewards
+            .checked_add(pending)
+            .ok_or(GameError::ArithmeticOverflow)?;
+
+        // Mark everything up to now as harvested
+        user_account.reward_debt = (user_account.player_power as u128)
+            .checked_mul(global_state.cumulative_bombcoin_per_power)
+            .ok_or(GameError::ArithmeticOverflow)?;
+
+        msg!("Harvested {} pending rewards for user", pending);
+    }
+
+    Ok(pending)
+}
+
+/// Validate a hero purchase and return its total cost (hero_price coins per hero)
+fn hero_purchase_cost(global_state: &GlobalState, user_account: &UserAccount, quantity: u8) -> Result<u64> {
+    require!(!global_state.paused, GameError::GamePaused);
+    require!(global_state.minting_enabled, GameError::MintingDisabled);
+    require!(
+        (1..=global_state.max_hero_quantity).contains(&quantity),
+        GameError::InvalidHeroQuantity
+    );
+    require!(user_account.pending_mint.is_none(), GameError::MintAlreadyPending);
+    require!(
+        user_account.has_inventory_space(quantity as usize),
+        GameError::InventoryFull
+    );
+
+    (quantity as u64)
+        .checked_mul(global_state.hero_price)
+        .ok_or(error!(GameError::ArithmeticOverflow))
+}
+
+/// Commit a paid hero purchase and book its burn split
+/// Returns (burn_amount, treasury_amount)
+fn commit_hero_purchase(
+    global_state: &mut GlobalState,
+    user_account: &mut UserAccount,
+    randomness_feed: &Option<Account<RandomnessFeed>>,
+    quantity: u8,
+    total_cost: u64,
+    paid_in_tokens: bool,
+) -> Result<(u64, u64)> {
+    // Bind the mint to randomness that does not exist yet
+    let pending_mint = commit_pending_mint(
+        global_state,
+        randomness_feed,
+        Clock::get()?.slot,
+        quantity,
+        None,
+    )?;
+
+    // Apply burn split using dynamic burn_pct
+    // Coin payments feed the dividend pool; the treasury share of a token
+    // payment is real BOMBcoin and stays in the token treasury
+    let (burn_amount, treasury_amount) = calculate_burn_split(total_cost, global_state.burn_pct);
+    if paid_in_tokens {
+        global_state.record_token_burn(burn_amount)?;
+    } else {
+        global_state.total_burned += burn_amount;
+        global_state.reward_pool += treasury_amount;
+    }
+
+    user_account.pending_mint = Some(pending_mint);
+
+    msg!(
+        "Committed {} heroes for {} {} (burn: {}, treasury: {}), reveal after slot {}",
+        quantity,
+        total_cost,
+        if paid_in_tokens { "BOMBcoin" } else { "coins" },
+        burn_amount,
+        treasury_amount,
+        pending_mint.target_slot
+    );
+    emit!(HeroPurchaseCommitted {
+        user: user_account.owner,
+        quantity,
+        total_cost,
+        burn_amount,
+        treasury_amount,
+        target_slot: pending_mint.target_slot,
+        paid_in_tokens,
+    });
+
+    Ok((burn_amount, treasury_amount))
+}
+
+/// Validate a house upgrade (pause, toggle, max level, cooldown)
+/// Returns its cost and the table entry of the level it reaches
+fn house_upgrade_cost(
+    global_state: &GlobalState,
+    user_account: &UserAccount,
+    now: i64,
+) -> Result<(u64, HouseLevelConfig)> {
+    require!(!global_state.paused, GameError::GamePaused);
+    require!(global_state.house_upgrades_enabled, GameError::HouseUpgradesDisabled);
+    require!(
+        (user_account.house_level as usize) < MAX_HOUSE_LEVEL,
+        GameError::MaxHouseLevelReached
+    );
+    let current = global_state.house_level(user_account.house_level);
+    let next = *global_state.house_level(user_account.house_level + 1);
+
+    // Check cooldown
+    if user_account.last_house_upgrade_timestamp > 0 {
+        let time_since_upgrade = now - user_account.last_house_upgrade_timestamp;
+        require!(time_since_upgrade >= current.upgrade_cooldown, GameError::UpgradeCooldownActive);
+    }
+
+    // Tiles placed under an older table must still fit the new grid
+    require!(
+        user_account
+            .house_occupied_coords
+            .iter()
+            .all(|tile| tile.x < next.grid_width && tile.y < next.grid_height),
+        GameError::InvalidGridCoordinates
+    );
+
+    Ok((current.upgrade_cost, next))
+}
+
+/// Raise the house level and expand the grid once the upgrade is paid
+/// `token_split` is the (burn, treasury) split of a token payment
+fn apply_house_upgrade(
+    user_account: &mut UserAccount,
+    next: HouseLevelConfig,
+    now: i64,
+    cost: u64,
+    token_split: Option<(u64, u64)>,
+) {
+    // Upgrade house
+    user_account.house_level += 1;
+    user_account.last_house_upgrade_timestamp = now;
+
+    // Expand grid
+    user_account.grid_width = next.grid_width;
+    user_account.grid_height = next.grid_height;
+
+    let (burn_amount, treasury_amount) = token_split.unwrap_or_default();
+    msg!(
+        "House upgraded to level {} ({}x{} grid) for {} {}",
+        user_account.house_level,
+        next.grid_width,
+        next.grid_height,
+        cost,
+        if token_split.is_some() { "BOMBcoin" } else { "coins" }
+    );
+    emit!(HouseUpgraded {
+        user: user_account.owner,
+        new_level: user_account.house_level,
+        grid_width: next.grid_width,
+        grid_height: next.grid_height,
+        cost,
+        paid_in_tokens: token_split.is_some(),
+        burn_amount,
+        treasury_amount,
+    });
+}
+
+/// Take an SPL BOMBcoin payment from the player: burn one share with
+/// `token::burn` and move the rest into the token treasury
+fn settle_token_payment<'info>(
+    token_program: &Program<'info, Token>,
+    mint: &Account<'info, Mint>,
+    from: &Account<'info, TokenAccount>,
+    token_treasury: &Account<'info, TokenAccount>,
+    payer: &Signer<'info>,
+    burn_amount: u64,
+    treasury_amount: u64,
+) -> Result<()> {
+    if burn_amount > 0 {
+        token::burn(
+            CpiContext::new(
+                token_program.to_account_info(),
+                token::Burn {
+                    mint: mint.to_account_info(),
+                    from: from.to_account_info(),
+                    authority: payer.to_account_info(),
+                },
+            ),
+            burn_amount,
+        )?;
+    }
+    if treasury_amount > 0 {
+        token::transfer(
+            CpiContext::new(
+                token_program.to_account_info(),
+                token::Transfer {
+                    from: from.to_account_info(),
+                    to: token_treasury.to_account_info(),
+                    authority: payer.to_account_info(),
+                },
+            ),
+            treasury_amount,
+        )?;
+    }
+    Ok(())
+}
+
+/// Record a hero mint commitment bound to randomness that does not exist yet
+/// (a future slot hash, or the next randomness feed publication)
+fn commit_pending_mint(
+    global_state: &GlobalState,
+    randomness_feed: &Option<Account<RandomnessFeed>>,
+    current_slot: u64,
+    quantity: u8,
+    fuse_into: Option<HeroRarity>,
+) -> Result<PendingMint> {
+    let feed_sequence = if global_state.use_randomness_feed {
+        let feed = randomness_feed
+            .as_ref()
+            .ok_or(GameError::InvalidRandomnessAccount)?;
+        feed.sequence
+    } else {
+        0
+    };
+
+    Ok(PendingMint {
+        quantity,
+        target_slot: current_slot + REVEAL_DELAY_SLOTS,
+        use_feed: global_state.use_randomness_feed,
+        feed_sequence,
+        fuse_into,
+        fusion_success_bps: global_state.fusion_success_bps,
+        nonce: global_state.unique_heroes_count,
+    })
+}
+
+/// Roll one hero from revealed randomness and add it to the inventory
+/// `nonce` comes from the commitment and differs per hero, giving each its own stream
+fn mint_revealed_hero(
+    global_state: &mut GlobalState,
+    user_account: &mut UserAccount,
+    pages: &mut HeroPages,
+    randomness: &[u8; 32],
+    nonce: u64,
+    timestamp: i64,
+    rarity_override: Option<HeroRarity>,
+) -> Result<()> {
+    let hero_id = user_account
+        .allocate_hero_id()
+        .ok_or(GameError::HeroIdsExhausted)?;
+
+    let hero = generate_hero(
+        hero_id,
+        timestamp,
+        randomness,
+        user_account.owner,
+        nonce,
+        rarity_override,
+    )?;
+
+    let hmp = hero.calculate_hmp();
+    msg!(
+        "Minted hero #{}: {:?} skin #{}, Power: {}, Speed: {}, HP: {}, HMP: {}.{:02}",
+        hero_id,
+        hero.rarity,
+        hero.skin_id,
+        hero.power,
+        hero.speed,
+        hero.hp,
+        hmp / HMP_SCALE,
+        hmp % HMP_SCALE
+    );
+    emit!(HeroMinted {
+        user: user_account.owner,
+        hero_id,
+        rarity: hero.rarity,
+        skin_id: hero.skin_id,
+        power: hero.power,
+        speed: hero.speed,
+        stamina: hero.stamina,
+        max_hp: hero.max_hp,
+        bomb_number: hero.bomb_number,
+        bomb_range: hero.bomb_range,
+        hmp,
+    });
+
+    store_hero(user_account, pages, hero)?;
+    global_state.unique_heroes_count += 1;
+    Ok(())
+}
+
+/// Put a hero into its hero page and count it as owned
+fn store_hero(user_account: &mut UserAccount, pages: &mut HeroPages, hero: Hero) -> Result<()> {
+    pages.insert(hero)?;
+    user_account.hero_count += 1;
+    Ok(())
+}
+
+/// Take heroes out of their hero pages, dropping their map and grid entries
+fn take_heroes(
+    user_account: &mut UserAccount,
+    pages: &mut HeroPages,
+    hero_ids: &[u16],
+) -> Result<Vec<Hero>> {
+    let heroes = hero_ids
+        .iter()
+        .map(|&hero_id| pages.remove(hero_id))
+        .collect::<Result<Vec<_>>>()?;
+    user_account.release_heroes(hero_ids);
+    Ok(heroes)
+}
+
+/// Referral bonuses may only be paid to the upline's associated token account
+fn require_referrer_ata(
+    token_account: &Account<TokenAccount>,
+    referrer: &Pubkey,
+    mint: &Pubkey,
+) -> Result<()> {
+    require_keys_eq!(token_account.owner, *referrer, GameError::InvalidReferrerTokenAccount);
+    require_keys_eq!(
+        token_account.key(),
+        get_associated_token_address(referrer, mint),
+        GameError::InvalidReferrerTokenAccount
+    );
+    Ok(())
+}
+
+/// Record a referral bonus on the upline's account (tier 0 = direct referral)
+fn credit_referral_bonus(upline: &mut UserAccount, tier: usize, amount: u64) -> Result<()> {
+    upline.referral_bonus_paid = upline.referral_bonus_paid
+        .checked_add(amount)
+        .ok_or(GameError::ArithmeticOverflow)?;
+    upline.referral_earnings_by_tier[tier] = upline.referral_earnings_by_tier[tier]
+        .checked_add(amount)
+        .ok_or(GameError::ArithmeticOverflow)?;
+    Ok(())
+}
+
+/// Mint BOMBcoin to `to`, signed by the GlobalState PDA (mint authority)
+fn mint_reward<'info>(
+    token_program: &Program<'info, Token>,
+    mint: &Account<'info, Mint>,
+    to: AccountInfo<'info>,
+    global_state: &impl ToAccountInfo<'info>,
+    global_state_bump: u8,
+    amount: u64,
+) -> Result<()> {
+    let seeds = &[b"global_state".as_ref(), &[global_state_bump]];
+    let signer = &[&seeds[..]];
+
+    token::mint_to(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            MintTo {
+                mint: mint.to_account_info(),
+                to,
+                authority: global_state.to_account_info(),
+            },
+            signer,
+        ),
+        amount,
+    )
+}
+
+/// Move lamports from a signing wallet (no-op for zero)
+fn transfer_lamports<'info>(
+    from: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+
+    let ix = anchor_lang::solana_program::system_instruction::transfer(from.key, to.key, amount);
+    anchor_lang::solana_program::program::invoke(&ix, &[from.clone(), to.clone()])?;
+    Ok(())
+}
+
+/// Replace an account's data with migrated bytes, the payer funding the rent
+/// for any growth
+fn rewrite_account<'info>(
+    info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    data: &[u8],
+) -> Result<()> {
+    let rent_due = Rent::get()?
+        .minimum_balance(data.len())
+        .saturating_sub(info.lamports());
+    transfer_lamports(payer, info, rent_due)?;
+
+    info.resize(data.len())?;
+    info.try_borrow_mut_data()?.copy_from_slice(data);
+    Ok(())
+}
+
+/// Grow a UserAccount with `realloc` so its contents fit, the payer funding the rent delta
+/// Call after the account has been modified; no-op when the current space suffices
+fn grow_user_account<'info>(
+    user_account: &Account<'info, UserAccount>,
+    payer: &AccountInfo<'info>,
+) -> Result<()> {
+    let info = user_account.to_account_info();
+    let required_len = user_account.required_len();
+    if required_len <= info.data_len() {
+        return Ok(());
+    }
+
+    let rent_due = Rent::get()?
+        .minimum_balance(required_len)
+        .saturating_sub(info.lamports());
+    transfer_lamports(payer, &info, rent_due)?;
+    info.resize(required_len)?;
+
+    msg!("User account grown to {} bytes", required_len);
+    Ok(())
+}
+
+/// Recalculate player power from mining heroes, sync global hash power and reward debt
+/// Call after update_pool + harvest_pending_rewards, once the map has been modified
+/// The pages of all mining heroes must be loaded
+///
+/// Returns (old_power, new_power)
+fn sync_player_power(
+    global_state: &mut GlobalState,
+    user_account: &mut UserAccount,
+    pages: &HeroPages,
+) -> Result<(u64, u64)> {
+    let old_power = user_account.player_power;
+    let new_power = pages.map_power(&user_account.active_map)?;
+
+    global_state.total_hash_power = global_state.total_hash_power
+        .saturating_sub(old_power)
+        .checked_add(new_power)
+        .ok_or(GameError::ArithmeticOverflow)?;
+    user_account.player_power = new_power;
+
+    // reward_debt = new_power * acc_per_power, so only future emissions count
+    user_account.reward_debt = (new_power as u128)
+        .checked_mul(global_state.cumulative_bombcoin_per_power)
+        .ok_or(GameError::ArithmeticOverflow)?;
+
+    Ok((old_power, new_power))
+}
+
+/// Share the reward pool out over the current stakers (MasterChef-style)
+/// MUST be called before ANY change to a stake or a staker's dividends
+///
+/// Formula: acc_dividend_per_share += reward_pool / total_staked
+/// The pool waits while nothing is staked
+fn distribute_dividends(global_state: &mut GlobalState, stake_pool: &mut StakePool) -> Result<()> {
+    if stake_pool.total_staked == 0 || global_state.reward_pool == 0 {
+        return Ok(());
+    }
+
+    let (delta, distributed) = dividend_distribution(global_state.reward_pool, stake_pool.total_staked)?;
+    if distributed == 0 {
+        return Ok(());
+    }
+
+    stake_pool.acc_dividend_per_share = stake_pool.acc_dividend_per_share
+        .checked_add(delta)
+        .ok_or(GameError::ArithmeticOverflow)?;
+    stake_pool.total_distributed = stake_pool.total_distributed
+        .checked_add(distributed)
+        .ok_or(GameError::ArithmeticOverflow)?;
+    global_state.reward_pool -= distributed;
+
+    msg!(
+        "Distributed {} to {} staked, acc_per_share={}",
+        distributed,
+        stake_pool.total_staked,
+        stake_pool.acc_dividend_per_share
+    );
+    emit!(DividendsDistributed {
+        amount: distributed,
+        total_staked: stake_pool.total_staked,
+        acc_dividend_per_share: stake_pool.acc_dividend_per_share,
+    });
+    Ok(())
+}
+
+/// Move a staker's dividends since their last harvest into pending_dividends
+/// Call after distribute_dividends, before the stake changes
+fn harvest_dividends(stake_pool: &StakePool, stake_account: &mut StakeAccount) -> Result<u64> {
+    let pending = calculate_pending_reward(
+        stake_account.amount,
+        stake_pool.acc_dividend_per_share,
+        stake_account.reward_debt,
+        DIVIDEND_PRECISION,
+    )?;
+
+    if pending > 0 {
+        stake_account.pending_dividends = stake_account.pending_dividends
+            .checked_add(pending)
+            .ok_or(GameError::ArithmeticOverflow)?;
+    }
+    sync_stake_debt(stake_pool, stake_account)?;
+
+    Ok(pending)
+}
+
+/// reward_debt = amount * acc_dividend_per_share, so only future distributions count
+fn sync_stake_debt(stake_pool: &StakePool, stake_account: &mut StakeAccount) -> Result<()> {
+    stake_account.reward_debt = (stake_account.amount as u128)
+        .checked_mul(stake_pool.acc_dividend_per_share)
+        .ok_or(GameError::ArithmeticOverflow)?;
+    Ok(())
+}
+
+// ============================================================================
 // ACCOUNT CONTEXTS
 // ============================================================================
 
 #[derive(Accounts)]
-pub struct InitializeGlobalState<'info> {
+pub struct InitializeGlobalState<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = GlobalState::LEN,
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        init,
+        payer = authority,
+        mint::decimals = 6,
+        mint::authority = global_state,
+        seeds = [b"reward_token_mint"],
+        bump
+    )]
+    pub reward_token_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct PurchaseInitialHouse<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        init,
+        payer = user,
+        space = UserAccount::BASE_LEN,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    /// CHECK: Dev treasury receiving SOL
+    pub dev_treasury: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(referrer_pubkey: Pubkey)]
+pub struct SetReferrer<'info> {
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", referrer_pubkey.as_ref()],
+        bump = referrer_account.bump
+    )]
+    pub referrer_account: Account<'info, UserAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BuyHero<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        seeds = [b"randomness_feed"],
+        bump = randomness_feed.bump
+    )]
+    pub randomness_feed: Option<Account<'info, RandomnessFeed>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BuyHeroWithTokens<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        seeds = [b"randomness_feed"],
+        bump = randomness_feed.bump
+    )]
+    pub randomness_feed: Option<Account<'info, RandomnessFeed>>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_token_mint"],
+        bump
+    )]
+    pub reward_token_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.mint == reward_token_mint.key(),
+        constraint = user_token_account.owner == user.key()
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"token_treasury"],
+        bump
+    )]
+    pub token_treasury: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(page_index: u16)]
+pub struct OpenHeroPage<'info> {
+    /// Player the page is opened for (must own a house)
+    #[account(
+        seeds = [b"user_account", user_account.owner.as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.initialized_starter_house @ GameError::HouseNotInitialized
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        init,
+        payer = user,
+        space = HeroPage::LEN,
+        seeds = [b"hero_page", user_account.owner.as_ref(), &page_index.to_le_bytes()],
+        bump
+    )]
+    pub hero_page: Box<Account<'info, HeroPage>>,
+
+    /// Pays the page's rent
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseHeroPage<'info> {
+    #[account(
+        mut,
+        seeds = [b"hero_page", user.key().as_ref(), &hero_page.index.to_le_bytes()],
+        bump = hero_page.bump,
+        has_one = owner,
+        close = user
+    )]
+    pub hero_page: Box<Account<'info, HeroPage>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateInventory<'info> {
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FuseHeroes<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        seeds = [b"randomness_feed"],
+        bump = randomness_feed.bump
+    )]
+    pub randomness_feed: Option<Account<'info, RandomnessFeed>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RevealHeroes<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    /// CHECK: SlotHashes sysvar, parsed manually (too large to deserialize)
+    #[account(address = anchor_lang::solana_program::sysvar::slot_hashes::ID)]
+    pub slot_hashes: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"randomness_feed"],
+        bump = randomness_feed.bump
+    )]
+    pub randomness_feed: Option<Account<'info, RandomnessFeed>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ModifyGrid<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MoveHeroToMap<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_token_mint"],
+        bump
+    )]
+    pub reward_token_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.mint == reward_token_mint.key(),
+        constraint = user_token_account.owner == user.key()
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    /// Referrer's user account (tracks referral_bonus_paid); only required when the user has a referrer
+    #[account(mut)]
+    pub referrer_account: Option<Account<'info, UserAccount>>,
+
+    /// Referrer's ATA; only required when the user has a referrer
+    #[account(
+        mut,
+        constraint = referrer_token_account.mint == reward_token_mint.key()
+    )]
+    pub referrer_token_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct BridgeCoins<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_token_mint"],
+        bump
+    )]
+    pub reward_token_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.mint == reward_token_mint.key(),
+        constraint = user_token_account.owner == user.key()
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct OpenStakeAccount<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = StakeAccount::LEN,
+        seeds = [b"stake_account", user.key().as_ref()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct StakeTokens<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        seeds = [b"stake_pool"],
+        bump = stake_pool.bump
+    )]
+    pub stake_pool: Account<'info, StakePool>,
+
+    #[account(
+        mut,
+        seeds = [b"stake_account", user.key().as_ref()],
+        bump = stake_account.bump,
+        has_one = owner
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_token_mint"],
+        bump
+    )]
+    pub reward_token_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"stake_vault"],
+        bump
+    )]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.mint == reward_token_mint.key(),
+        constraint = user_token_account.owner == user.key()
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RecoverHP<'info> {
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Accounts)]
+pub struct BenchSnapshotUser<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
     #[account(
         init,
-        payer = authority,
-        space = GlobalState::LEN,
+        payer = user,
+        space = GlobalStateZc::LEN,
+        seeds = [b"global_state_zc", user.key().as_ref()],
+        bump
+    )]
+    pub global_state_zc: AccountLoader<'info, GlobalStateZc>,
+
+    #[account(
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        init,
+        payer = user,
+        space = UserAccountZc::LEN,
+        seeds = [b"user_account_zc", user.key().as_ref()],
+        bump
+    )]
+    pub user_account_zc: AccountLoader<'info, UserAccountZc>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Accounts)]
+#[instruction(page_index: u16)]
+pub struct BenchSnapshotPage<'info> {
+    #[account(
+        seeds = [b"hero_page", user.key().as_ref(), &page_index.to_le_bytes()],
+        bump = hero_page.bump,
+        has_one = owner
+    )]
+    pub hero_page: Box<Account<'info, HeroPage>>,
+
+    #[account(
+        init,
+        payer = user,
+        space = HeroPageZc::LEN,
+        seeds = [b"hero_page_zc", user.key().as_ref(), &page_index.to_le_bytes()],
+        bump
+    )]
+    pub hero_page_zc: AccountLoader<'info, HeroPageZc>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Accounts)]
+pub struct BenchZc<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state_zc", user.key().as_ref()],
+        bump = global_state_zc.load()?.bump
+    )]
+    pub global_state_zc: AccountLoader<'info, GlobalStateZc>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account_zc", user.key().as_ref()],
+        bump = user_account_zc.load()?.bump,
+        has_one = owner
+    )]
+    pub user_account_zc: AccountLoader<'info, UserAccountZc>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Accounts)]
+pub struct BenchRecoverZc<'info> {
+    #[account(
+        seeds = [b"user_account_zc", user.key().as_ref()],
+        bump = user_account_zc.load()?.bump,
+        has_one = owner
+    )]
+    pub user_account_zc: AccountLoader<'info, UserAccountZc>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpgradeHouse<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpgradeHouseWithTokens<'info> {
+    #[account(
+        mut,
         seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_token_mint"],
+        bump
+    )]
+    pub reward_token_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.mint == reward_token_mint.key(),
+        constraint = user_token_account.owner == user.key()
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"token_treasury"],
         bump
     )]
+    pub token_treasury: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct LevelUpHero<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RetireHero<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferHero<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    /// Receiving player's account (must own a house)
+    #[account(
+        mut,
+        seeds = [b"user_account", receiver_account.owner.as_ref()],
+        bump = receiver_account.bump,
+        constraint = receiver_account.initialized_starter_house @ GameError::HouseNotInitialized
+    )]
+    pub receiver_account: Account<'info, UserAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExportHero<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
     pub global_state: Account<'info, GlobalState>,
 
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump,
+        has_one = owner
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    /// Fresh keypair for the hero NFT mint
+    #[account(
+        init,
+        payer = user,
+        mint::decimals = 0,
+        mint::authority = global_state,
+        mint::freeze_authority = global_state
+    )]
+    pub hero_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = user,
+        associated_token::mint = hero_mint,
+        associated_token::authority = user
+    )]
+    pub hero_token_account: Account<'info, TokenAccount>,
+
     #[account(
         init,
-        payer = authority,
-        mint::decimals = 6,
-        mint::authority = global_state,
-        seeds = [b"reward_token_mint"],
+        payer = user,
+        space = ExportedHero::LEN,
+        seeds = [b"exported_hero", hero_mint.key().as_ref()],
         bump
     )]
-    pub reward_token_mint: Account<'info, Mint>,
+    pub exported_hero: Account<'info, ExportedHero>,
+
+    /// CHECK: Metadata PDA, created by the token metadata program
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), hero_mint.key().as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key()
+    )]
+    pub metadata: UncheckedAccount<'info>,
+
+    /// CHECK: Master edition PDA, created by the token metadata program
+    #[account(
+        mut,
+        seeds = [
+            b"metadata",
+            token_metadata_program.key().as_ref(),
+            hero_mint.key().as_ref(),
+            b"edition"
+        ],
+        bump,
+        seeds::program = token_metadata_program.key()
+    )]
+    pub master_edition: UncheckedAccount<'info>,
 
     #[account(mut)]
-    pub authority: Signer<'info>,
+    pub user: Signer<'info>,
 
-    pub system_program: Program<'info, System>,
+    pub owner: SystemAccount<'info>,
     pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_metadata_program: Program<'info, TokenMetadata>,
+    pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct PurchaseInitialHouse<'info> {
+pub struct ImportHero<'info> {
     #[account(
-        mut,
         seeds = [b"global_state"],
         bump = global_state.bump
     )]
     pub global_state: Account<'info, GlobalState>,
 
     #[account(
-        init,
-        payer = user,
-        space = UserAccount::MAX_LEN,
+        mut,
         seeds = [b"user_account", user.key().as_ref()],
-        bump
+        bump = user_account.bump,
+        has_one = owner
     )]
     pub user_account: Account<'info, UserAccount>,
 
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub hero_mint: Account<'info, Mint>,
 
-    #[account(mut)]
-    /// CHECK: Dev treasury receiving SOL
-    pub dev_treasury: AccountInfo<'info>,
+    /// Caller's token account holding the hero NFT
+    #[account(
+        mut,
+        constraint = hero_token_account.mint == hero_mint.key(),
+        constraint = hero_token_account.owner == user.key(),
+        constraint = hero_token_account.amount == 1 @ GameError::HeroTokenNotHeld
+    )]
+    pub hero_token_account: Account<'info, TokenAccount>,
 
-    pub system_program: Program<'info, System>,
-}
+    /// Only mints created by export_hero have a record; rent goes to the importer
+    #[account(
+        mut,
+        seeds = [b"exported_hero", hero_mint.key().as_ref()],
+        bump = exported_hero.bump,
+        close = user
+    )]
+    pub exported_hero: Account<'info, ExportedHero>,
 
-#[derive(Accounts)]
-pub struct SetReferrer<'info> {
+    /// CHECK: Metadata PDA, closed by the token metadata program
     #[account(
         mut,
-        seeds = [b"user_account", user.key().as_ref()],
-        bump = user_account.bump,
-        has_one = owner
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), hero_mint.key().as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key()
     )]
-    pub user_account: Account<'info, UserAccount>,
+    pub metadata: UncheckedAccount<'info>,
+
+    /// CHECK: Master edition PDA, closed by the token metadata program
+    #[account(
+        mut,
+        seeds = [
+            b"metadata",
+            token_metadata_program.key().as_ref(),
+            hero_mint.key().as_ref(),
+            b"edition"
+        ],
+        bump,
+        seeds::program = token_metadata_program.key()
+    )]
+    pub master_edition: UncheckedAccount<'info>,
 
     #[account(mut)]
     pub user: Signer<'info>,
 
     pub owner: SystemAccount<'info>,
+    pub token_program: Program<'info, Token>,
+    pub token_metadata_program: Program<'info, TokenMetadata>,
 }
 
 #[derive(Accounts)]
-pub struct BuyHero<'info> {
+#[instruction(hero_id: u16)]
+pub struct ListHero<'info> {
     #[account(
-        mut,
         seeds = [b"global_state"],
         bump = global_state.bump
     )]
@@ -1442,6 +4701,15 @@ pub struct BuyHero<'info> {
     )]
     pub user_account: Account<'info, UserAccount>,
 
+    #[account(
+        init,
+        payer = user,
+        space = HeroListing::LEN,
+        seeds = [b"listing", user.key().as_ref(), &hero_id.to_le_bytes()],
+        bump
+    )]
+    pub listing: Account<'info, HeroListing>,
+
     #[account(mut)]
     pub user: Signer<'info>,
 
@@ -1450,7 +4718,7 @@ pub struct BuyHero<'info> {
 }
 
 #[derive(Accounts)]
-pub struct ModifyGrid<'info> {
+pub struct CancelListing<'info> {
     #[account(
         mut,
         seeds = [b"user_account", user.key().as_ref()],
@@ -1459,6 +4727,14 @@ pub struct ModifyGrid<'info> {
     )]
     pub user_account: Account<'info, UserAccount>,
 
+    #[account(
+        mut,
+        seeds = [b"listing", user.key().as_ref(), &listing.hero.id.to_le_bytes()],
+        bump = listing.bump,
+        close = user
+    )]
+    pub listing: Account<'info, HeroListing>,
+
     #[account(mut)]
     pub user: Signer<'info>,
 
@@ -1466,7 +4742,7 @@ pub struct ModifyGrid<'info> {
 }
 
 #[derive(Accounts)]
-pub struct MoveHeroToMap<'info> {
+pub struct BuyListing<'info> {
     #[account(
         mut,
         seeds = [b"global_state"],
@@ -1474,6 +4750,7 @@ pub struct MoveHeroToMap<'info> {
     )]
     pub global_state: Account<'info, GlobalState>,
 
+    /// Buyer's account
     #[account(
         mut,
         seeds = [b"user_account", user.key().as_ref()],
@@ -1482,109 +4759,209 @@ pub struct MoveHeroToMap<'info> {
     )]
     pub user_account: Account<'info, UserAccount>,
 
+    /// Listing rent goes back to the seller
+    #[account(
+        mut,
+        seeds = [b"listing", seller.key().as_ref(), &listing.hero.id.to_le_bytes()],
+        bump = listing.bump,
+        has_one = seller,
+        close = seller
+    )]
+    pub listing: Account<'info, HeroListing>,
+
+    #[account(mut)]
+    pub seller: SystemAccount<'info>,
+
+    /// Treasury share of SOL fees
+    #[account(mut, address = global_state.dev_treasury)]
+    pub dev_treasury: SystemAccount<'info>,
+
+    /// CHECK: Incinerator, receives the burn share of SOL fees
+    #[account(mut, address = INCINERATOR)]
+    pub incinerator: UncheckedAccount<'info>,
+
+    /// BOMBcoin listings only
+    #[account(
+        mut,
+        seeds = [b"reward_token_mint"],
+        bump
+    )]
+    pub reward_token_mint: Option<Account<'info, Mint>>,
+
+    /// BOMBcoin listings only: buyer pays from here
+    #[account(
+        mut,
+        constraint = buyer_token_account.owner == user.key() @ GameError::InvalidPaymentAccount
+    )]
+    pub buyer_token_account: Option<Account<'info, TokenAccount>>,
+
+    /// BOMBcoin listings only: seller's ATA
+    #[account(mut)]
+    pub seller_token_account: Option<Account<'info, TokenAccount>>,
+
+    /// BOMBcoin listings only: dev treasury's ATA
+    #[account(mut)]
+    pub treasury_token_account: Option<Account<'info, TokenAccount>>,
+
     #[account(mut)]
     pub user: Signer<'info>,
 
     pub owner: SystemAccount<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct ClaimRewards<'info> {
+pub struct AdminAction<'info> {
     #[account(
         mut,
         seeds = [b"global_state"],
-        bump = global_state.bump
+        bump = global_state.bump,
+        has_one = authority
     )]
     pub global_state: Account<'info, GlobalState>,
 
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateGlobalState<'info> {
+    /// CHECK: Old layout cannot be deserialized as GlobalState; owner, discriminator
+    /// and authority are verified in the handler
     #[account(
         mut,
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: UncheckedAccount<'info>,
+
+    #[account(
         seeds = [b"reward_token_mint"],
         bump
     )]
     pub reward_token_mint: Account<'info, Mint>,
 
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateUserAccount<'info> {
+    /// CHECK: Old layouts cannot be deserialized as UserAccount; owner, discriminator
+    /// and stored owner are verified in the handler
     #[account(
         mut,
-        seeds = [b"user_account", user.key().as_ref()],
-        bump = user_account.bump,
-        has_one = owner
+        seeds = [b"user_account", owner.key().as_ref()],
+        bump
     )]
-    pub user_account: Account<'info, UserAccount>,
+    pub user_account: UncheckedAccount<'info>,
+
+    pub owner: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
 
+#[derive(Accounts)]
+pub struct InitializeRandomnessFeed<'info> {
     #[account(
-        mut,
-        constraint = user_token_account.mint == reward_token_mint.key(),
-        constraint = user_token_account.owner == user.key()
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        has_one = authority
     )]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub global_state: Account<'info, GlobalState>,
 
     #[account(
-        mut,
-        constraint = referrer_token_account.mint == reward_token_mint.key()
+        init,
+        payer = authority,
+        space = RandomnessFeed::LEN,
+        seeds = [b"randomness_feed"],
+        bump
     )]
-    pub referrer_token_account: Account<'info, TokenAccount>,
+    pub randomness_feed: Account<'info, RandomnessFeed>,
 
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub authority: Signer<'info>,
 
-    pub owner: SystemAccount<'info>,
-    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct RecoverHP<'info> {
+pub struct PublishRandomness<'info> {
     #[account(
         mut,
-        seeds = [b"user_account", user.key().as_ref()],
-        bump = user_account.bump,
-        has_one = owner
+        seeds = [b"randomness_feed"],
+        bump = randomness_feed.bump,
+        has_one = authority
     )]
-    pub user_account: Account<'info, UserAccount>,
-
-    #[account(mut)]
-    pub user: Signer<'info>,
+    pub randomness_feed: Account<'info, RandomnessFeed>,
 
-    pub owner: SystemAccount<'info>,
+    pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct UpgradeHouse<'info> {
+pub struct WithdrawTokenFunds<'info> {
     #[account(
         seeds = [b"global_state"],
-        bump = global_state.bump
+        bump = global_state.bump,
+        has_one = authority
     )]
     pub global_state: Account<'info, GlobalState>,
 
+    /// Only the token treasury can be drained (the stake vault holds stakers' tokens)
     #[account(
         mut,
-        seeds = [b"user_account", user.key().as_ref()],
-        bump = user_account.bump,
-        has_one = owner
+        seeds = [b"token_treasury"],
+        bump
     )]
-    pub user_account: Account<'info, UserAccount>,
+    pub program_token_account: Account<'info, TokenAccount>,
 
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub treasury_token_account: Account<'info, TokenAccount>,
 
-    pub owner: SystemAccount<'info>,
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-pub struct AdminAction<'info> {
+pub struct InitializeTokenTreasury<'info> {
     #[account(
-        mut,
         seeds = [b"global_state"],
         bump = global_state.bump,
         has_one = authority
     )]
     pub global_state: Account<'info, GlobalState>,
 
+    #[account(
+        seeds = [b"reward_token_mint"],
+        bump
+    )]
+    pub reward_token_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = reward_token_mint,
+        token::authority = global_state,
+        seeds = [b"token_treasury"],
+        bump
+    )]
+    pub token_treasury: Account<'info, TokenAccount>,
+
+    #[account(mut)]
     pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-pub struct WithdrawTokenFunds<'info> {
+pub struct InitializeStakePool<'info> {
     #[account(
         seeds = [b"global_state"],
         bump = global_state.bump,
@@ -1592,20 +4969,42 @@ pub struct WithdrawTokenFunds<'info> {
     )]
     pub global_state: Account<'info, GlobalState>,
 
-    #[account(mut)]
-    pub program_token_account: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [b"reward_token_mint"],
+        bump
+    )]
+    pub reward_token_mint: Account<'info, Mint>,
 
-    #[account(mut)]
-    pub treasury_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = authority,
+        space = StakePool::LEN,
+        seeds = [b"stake_pool"],
+        bump
+    )]
+    pub stake_pool: Account<'info, StakePool>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = reward_token_mint,
+        token::authority = global_state,
+        seeds = [b"stake_vault"],
+        bump
+    )]
+    pub stake_vault: Account<'info, TokenAccount>,
 
+    #[account(mut)]
     pub authority: Signer<'info>,
 
+    pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
 pub struct AdminMintTestCoins<'info> {
     #[account(
+        mut,
         seeds = [b"global_state"],
         bump = global_state.bump,
         has_one = authority
@@ -1649,13 +5048,49 @@ pub struct ViewGlobalState<'info> {
     pub global_state: Account<'info, GlobalState>,
 }
 
+#[derive(Accounts)]
+pub struct ViewTokenSupply<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        seeds = [b"reward_token_mint"],
+        bump
+    )]
+    pub reward_token_mint: Account<'info, Mint>,
+}
+
+#[derive(Accounts)]
+pub struct ViewStake<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        seeds = [b"stake_pool"],
+        bump = stake_pool.bump
+    )]
+    pub stake_pool: Account<'info, StakePool>,
+
+    #[account(
+        seeds = [b"stake_account", stake_account.owner.as_ref()],
+        bump = stake_account.bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+}
+
 // ============================================================================
 // VIEW FUNCTION RETURN DATA STRUCTURES
 // ============================================================================
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct HeroPlacement {
-    pub hero_index: u16,
+    pub hero_id: u16,
     pub x: u8,
     pub y: u8,
     pub is_restroom: bool,
@@ -1695,6 +5130,34 @@ pub struct PlayerStatsData {
     pub referral_count: u64,
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct AccountCapacityData {
+    /// Current account size in bytes
+    pub account_len: u64,
+    /// Bytes needed for the current contents (the account grows past account_len on demand)
+    pub required_len: u64,
+    /// Size with every collection at its cap
+    pub max_len: u64,
+    pub heroes: u64,
+    pub max_heroes: u64,
+    /// Counts heroes a pending mint will reveal as taken
+    pub remaining_hero_slots: u64,
+    pub remaining_map_slots: u64,
+    pub remaining_grid_tiles: u64,
+    pub referrals: u64,
+    pub max_referrals: u64,
+    pub remaining_referral_slots: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ReferralInfoData {
+    pub referrer: Option<Pubkey>,
+    pub referral_count: u64,
+    pub referral_bonus_paid: u64,
+    pub tier_fees: [u16; MAX_REFERRAL_TIERS],
+    pub earnings_by_tier: [u64; MAX_REFERRAL_TIERS],
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct HeroDetailsData {
     pub id: u16,
@@ -1709,6 +5172,7 @@ pub struct HeroDetailsData {
     pub hp: u32,
     pub max_hp: u32,
     pub estimated_current_hp: u32,
+    /// HMP in hundredths (HMP_SCALE)
     pub hmp: u64,
     pub is_sleeping: bool,
     pub is_on_map: bool,
@@ -1718,6 +5182,10 @@ pub struct HeroDetailsData {
     pub is_in_restroom: bool,
     pub last_action_time: i64,
     pub elapsed_since_action: u64,
+    pub level: u8,
+    pub xp: u64,
+    pub xp_for_next_level: u64,
+    pub level_up_cost: u64,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
@@ -1754,11 +5222,48 @@ pub struct GameInfoData {
     pub initial_bombcoin_per_block: u64,
     pub current_bombcoin_per_block: u64,
     pub halving_interval: u64,
+    pub emission_schedule: EmissionSchedule,
     pub burn_pct: u16,
     pub referral_fee: u16,
+    pub referral_tier_fees: [u16; MAX_REFERRAL_TIERS],
     pub rewards_precision: u64,
+    pub max_supply: u64,
+    pub fusion_hero_count: u8,
+    pub fusion_success_bps: u16,
+    pub fusion_cost: u64,
+    pub marketplace_fee_bps: u16,
+    pub hero_price: u64,
+    pub max_hero_quantity: u8,
+    pub house_levels: [HouseLevelConfig; MAX_HOUSE_LEVEL],
+    pub remaining_supply: u64,
+    pub total_emitted: u64,
     pub total_mined: u64,
     pub total_burned: u64,
     pub reward_pool: u64,
     pub blocks_until_next_halving: u64,
 }
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct TokenSupplyData {
+    pub mint_supply: u64,
+    pub expected_supply: u64,
+    pub total_tokens_minted: u64,
+    pub total_tokens_burned: u64,
+    pub total_tokens_deposited: u64,
+    pub supply_reduction: u64,
+    pub burned_outside_program: u64,
+    pub total_burned: u64,
+    pub coins_burned: u64,
+    pub reconciled: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct StakeInfoData {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub pending_dividends: u64,
+    pub total_staked: u64,
+    pub reward_pool: u64,
+    pub total_distributed: u64,
+    pub total_claimed: u64,
+}
@@ -0,0 +1,191 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::GameError;
+use crate::state::*;
+
+// ========== Layout history ==========
+//
+// GlobalState
+//   1: pre-accumulator layout (`GlobalStateV1`)
+//   2: incremental accumulator, no `version` byte
+//   3: `version` byte after `reward_token_mint`
+//   4: hero price and house level tables before the accounting fields
+//   5: SPL mint/burn/deposit counters before `bump`
+//   6: `total_coins_credited` before `bump`
+//   7: `hero_nft_base_uri` after the house level table
+//
+// UserAccount
+//   1: original layout (`UserAccountV1`), heroes without levels, no `version` byte
+//   2: `version` byte after `owner`, stable hero IDs and hero pages
+//   3: `nonce` at the end of `PendingMint`
+//
+// Each upgrade step turns the data of one version into the next, so an account
+// of any older version is brought forward by running the steps in order.
+
+/// total_emitted, total_mined, total_burned, reward_pool and bump at the end of a v3 GlobalState
+const V3_ACCOUNTING_LEN: usize = 8 + 8 + 8 + 8 + 1;
+
+/// Accounting fields from total_emitted through bump at the end of a v6 GlobalState
+const V6_ACCOUNTING_LEN: usize = 8 * 8 + 1;
+
+/// quantity, target_slot, use_feed and feed_sequence of a v2 PendingMint (before `fuse_into`)
+const V2_PENDING_MINT_HEAD_LEN: usize = 1 + 8 + 1 + 8;
+
+/// Layout version of GlobalState account data (discriminator included)
+pub fn global_state_version(data: &[u8]) -> Result<u8> {
+    check_discriminator(data, GlobalState::DISCRIMINATOR, GlobalState::VERSION_OFFSET)?;
+
+    if data.len() == GlobalStateV1::LEN {
+        return Ok(1);
+    }
+    Ok(match data[GlobalState::VERSION_OFFSET] {
+        // `use_randomness_feed` of the unversioned layout
+        0 | 1 => 2,
+        version => version,
+    })
+}
+
+/// Layout version of UserAccount account data (discriminator included)
+pub fn user_account_version(data: &[u8]) -> Result<u8> {
+    check_discriminator(data, UserAccount::DISCRIMINATOR, UserAccount::VERSION_OFFSET)?;
+
+    Ok(match data[UserAccount::VERSION_OFFSET] {
+        // `initialized_starter_house` of the unversioned layout
+        0 | 1 => 1,
+        version => version,
+    })
+}
+
+/// Upgrade GlobalState data to the current layout
+/// `token_supply` is the BOMBcoin mint's supply, counted as already minted
+/// Returns the version it was stored as and the migrated account data
+pub fn upgrade_global_state(data: &[u8], now: i64, token_supply: u64) -> Result<(u8, Vec<u8>)> {
+    let from_version = global_state_version(data)?;
+    require!(from_version <= GLOBAL_STATE_VERSION, GameError::UnknownAccountVersion);
+    require!(from_version < GLOBAL_STATE_VERSION, GameError::AlreadyMigrated);
+
+    let mut data = data.to_vec();
+    let mut version = from_version;
+    while version < GLOBAL_STATE_VERSION {
+        version = match version {
+            1 => {
+                // The typed upgrade already produces the current layout
+                let legacy = GlobalStateV1::deserialize(&mut &data[8..])?;
+                data.clear();
+                legacy.into_current(now, token_supply).try_serialize(&mut data)?;
+                GLOBAL_STATE_VERSION
+            }
+            2 => {
+                data.insert(GlobalState::VERSION_OFFSET, 3);
+                3
+            }
+            3 => {
+                // Start from the tables that used to be compiled in
+                let mut tables = Vec::new();
+                (DEFAULT_HERO_PRICE, DEFAULT_MAX_HERO_QUANTITY, DEFAULT_HOUSE_LEVELS).serialize(&mut tables)?;
+                let at = data.len() - V3_ACCOUNTING_LEN;
+                data.splice(at..at, tables);
+                data[GlobalState::VERSION_OFFSET] = 4;
+                4
+            }
+            4 => {
+                // Tokens already in circulation count as minted
+                let mut counters = Vec::new();
+                (token_supply, 0u64, 0u64).serialize(&mut counters)?;
+                let at = data.len() - 1;
+                data.splice(at..at, counters);
+                data[GlobalState::VERSION_OFFSET] = 5;
+                5
+            }
+            5 => {
+                // Credits were not tracked before; none count against the cap
+                let at = data.len() - 1;
+                data.splice(at..at, 0u64.to_le_bytes());
+                data[GlobalState::VERSION_OFFSET] = 6;
+                6
+            }
+            6 => {
+                // No base URI yet; NFT exports wait until the admin sets one
+                let at = data.len() - V6_ACCOUNTING_LEN;
+                data.splice(at..at, [0u8; MAX_HERO_NFT_BASE_URI_LEN]);
+                data[GlobalState::VERSION_OFFSET] = 7;
+                7
+            }
+            _ => return err!(GameError::UnknownAccountVersion),
+        };
+    }
+
+    // The result must parse as the current layout
+    GlobalState::try_deserialize(&mut &data[..])?;
+    Ok((from_version, data))
+}
+
+/// Upgrade UserAccount data to the current layout
+/// Returns the version it was stored as and the migrated account data
+/// (the account never shrinks; unused space at its end is kept)
+pub fn upgrade_user_account(data: &[u8]) -> Result<(u8, Vec<u8>)> {
+    let from_version = user_account_version(data)?;
+    require!(from_version <= USER_ACCOUNT_VERSION, GameError::UnknownAccountVersion);
+    require!(from_version < USER_ACCOUNT_VERSION, GameError::AlreadyMigrated);
+
+    let mut data = data.to_vec();
+    let mut version = from_version;
+    while version < USER_ACCOUNT_VERSION {
+        version = match version {
+            1 => {
+                // The typed upgrade already produces the current layout
+                let legacy = UserAccountV1::deserialize(&mut &data[8..])?;
+                let space = data.len();
+                data.clear();
+                legacy.into_current().try_serialize(&mut data)?;
+                data.resize(data.len().max(space), 0);
+                USER_ACCOUNT_VERSION
+            }
+            2 => {
+                // The rolls of a mint committed before nonces existed stay fixed at nonce 0;
+                // accounts without one grow by the same 8 bytes to keep the slot reserved
+                let at = v2_pending_mint_offset(&data)?;
+                if data[at] == 1 {
+                    // fuse_into is an Option<HeroRarity>, then fusion_success_bps
+                    let fuse_into = at + 1 + V2_PENDING_MINT_HEAD_LEN;
+                    let fuse_into_len = if data.get(fuse_into) == Some(&1) { 2 } else { 1 };
+                    let end = fuse_into + fuse_into_len + 2;
+                    require!(end <= data.len(), ErrorCode::AccountDidNotDeserialize);
+                    data.splice(end..end, 0u64.to_le_bytes());
+                } else {
+                    data.extend_from_slice(&0u64.to_le_bytes());
+                }
+                data[UserAccount::VERSION_OFFSET] = 3;
+                3
+            }
+            _ => return err!(GameError::UnknownAccountVersion),
+        };
+    }
+
+    UserAccount::try_deserialize(&mut &data[..])?;
+    Ok((from_version, data))
+}
+
+/// Offset of `pending_mint` in v2 UserAccount data, past the house fields and the
+/// tile, inventory and map vectors
+fn v2_pending_mint_offset(data: &[u8]) -> Result<usize> {
+    // version, initialized_starter_house, house_level, last_house_upgrade_timestamp, grid size
+    let mut at = UserAccount::VERSION_OFFSET + 1 + 1 + 1 + 8 + 1 + 1;
+    for item_len in [HouseTile::LEN, Hero::LEN, 2] {
+        let count = data
+            .get(at..at + 4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+            .ok_or(ErrorCode::AccountDidNotDeserialize)?;
+        at += 4 + count * item_len;
+    }
+    require!(at < data.len(), ErrorCode::AccountDidNotDeserialize);
+    Ok(at)
+}
+
+fn check_discriminator(data: &[u8], discriminator: &[u8], version_offset: usize) -> Result<()> {
+    require!(
+        data.len() > version_offset && data[..8] == *discriminator,
+        GameError::InvalidOwner
+    );
+    Ok(())
+}
@@ -1,4 +1,55 @@
 use anchor_lang::prelude::*;
+use crate::errors::GameError;
+use crate::utils::halved_rate;
+
+/// Default cap on BOMBcoin supply (100M tokens, 6 decimals)
+pub const MAX_BOMBCOIN_SUPPLY: u64 = 100_000_000 * 1_000_000;
+
+/// Depth of the referral chain paid on each claim (tier 1 = direct referrer)
+pub const MAX_REFERRAL_TIERS: usize = 3;
+
+/// Default fusion recipe: heroes consumed, success chance (bps) and coin cost
+pub const DEFAULT_FUSION_HERO_COUNT: u8 = 3;
+pub const DEFAULT_FUSION_SUCCESS_BPS: u16 = 5_000;
+pub const DEFAULT_FUSION_COST: u64 = 200;
+
+/// Default marketplace fee on filled listings (bps, split into burn/treasury by burn_pct)
+pub const DEFAULT_MARKETPLACE_FEE_BPS: u16 = 500;
+
+/// Default hero price (coins each) and most heroes bought in one purchase
+pub const DEFAULT_HERO_PRICE: u64 = 100;
+pub const DEFAULT_MAX_HERO_QUANTITY: u8 = 10;
+
+/// Upper bound on the configurable purchase quantity (one reveal must fit a transaction)
+pub const MAX_HERO_QUANTITY_CAP: u8 = 20;
+
+/// Highest house level
+pub const MAX_HOUSE_LEVEL: usize = 6;
+
+/// Current account layout versions (bump with every layout change and add a
+/// step to `migration.rs`)
+pub const GLOBAL_STATE_VERSION: u8 = 7;
+pub const USER_ACCOUNT_VERSION: u8 = 3;
+
+/// What drives the emission halvings (admin-selectable)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmissionSchedule {
+    /// Rate halves every `halving_interval` coins emitted
+    Supply,
+
+    /// Rate halves every `halving_interval` seconds since game start
+    Time,
+}
+
+/// Where a claim pays the player's net reward (chosen per claim)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RewardDestination {
+    /// Minted as SPL BOMBcoin to the player's token account
+    Wallet,
+
+    /// Credited to the in-game `coin_balance` (withdrawable later)
+    CoinBalance,
+}
 
 /// Global game state - singleton PDA with dynamic configuration
 #[account]
@@ -13,6 +64,13 @@ pub struct GlobalState {
     /// BOMBcoin SPL Token mint
     pub reward_token_mint: Pubkey,
 
+    /// Account layout version (GLOBAL_STATE_VERSION)
+    /// Unversioned layouts kept a bool here, so they read as 0 or 1
+    pub version: u8,
+
+    /// Reveal hero mints from the randomness feed instead of SlotHashes
+    pub use_randomness_feed: bool,
+
     // ========== Game State ==========
     /// Has the game started?
     pub game_has_started: bool,
@@ -41,6 +99,9 @@ pub struct GlobalState {
     /// Cumulative BOMBcoin per power (MasterChef-style accounting)
     pub cumulative_bombcoin_per_power: u128,
 
+    /// Timestamp up to which emissions have been added to the accumulator
+    pub last_reward_time: i64,
+
     // ========== Dynamic Economic Parameters ==========
     /// Initial house purchase price (SOL, in lamports)
     pub initial_house_price: u64,
@@ -48,20 +109,57 @@ pub struct GlobalState {
     /// Base reward rate (BOMBcoin per hour at phase 1)
     pub initial_bombcoin_per_block: u64,
 
-    /// Halving interval (supply milestone for rate reduction)
+    /// Halving interval (coins for Supply schedule, seconds for Time schedule)
     pub halving_interval: u64,
 
+    /// Whether halvings follow emitted supply or elapsed time
+    pub emission_schedule: EmissionSchedule,
+
     /// Burn percentage (0-10000, where 10000 = 100%)
     pub burn_pct: u16,
 
     /// Referral fee percentage (0-10000, where 10000 = 100%)
+    /// Paid to the direct referrer (tier 1)
     pub referral_fee: u16,
 
+    /// Referral fees for tiers 2 and 3 (bps, 0 = tier disabled)
+    pub upline_referral_fees: [u16; MAX_REFERRAL_TIERS - 1],
+
     /// Rewards calculation precision multiplier
     pub rewards_precision: u64,
 
+    /// Hard cap on BOMBcoin ever emitted and minted
+    pub max_supply: u64,
+
+    /// Heroes of one rarity consumed by a fusion
+    pub fusion_hero_count: u8,
+
+    /// Fusion success chance (0-10000, where 10000 = 100%)
+    pub fusion_success_bps: u16,
+
+    /// Coin cost of a fusion attempt
+    pub fusion_cost: u64,
+
+    /// Fee taken from every filled marketplace listing (0-10000, where 10000 = 100%)
+    pub marketplace_fee_bps: u16,
+
+    /// Coin price of one hero
+    pub hero_price: u64,
+
+    /// Most heroes bought in one purchase
+    pub max_hero_quantity: u8,
+
+    /// Grid, restroom, upgrade cost and cooldown per house level (level 1 first)
+    pub house_levels: [HouseLevelConfig; MAX_HOUSE_LEVEL],
+
+    /// Base URI of exported hero NFTs, zero-padded (all zero until the admin sets one)
+    pub hero_nft_base_uri: [u8; MAX_HERO_NFT_BASE_URI_LEN],
+
     // ========== Accounting ==========
-    /// Total BOMBcoin mined (for halving logic)
+    /// Total BOMBcoin emitted into the accumulator (drives supply-based halving)
+    pub total_emitted: u64,
+
+    /// Total BOMBcoin minted by claims
     pub total_mined: u64,
 
     /// Total coins burned from hero minting
@@ -70,6 +168,18 @@ pub struct GlobalState {
     /// Reward pool from minting fees
     pub reward_pool: u64,
 
+    /// SPL BOMBcoin minted by the program (claims, withdrawals, dividends)
+    pub total_tokens_minted: u64,
+
+    /// Part of `total_burned` burned as SPL BOMBcoin with `token::burn`
+    pub total_tokens_burned: u64,
+
+    /// SPL BOMBcoin burned by `deposit_coins` (converted, not destroyed)
+    pub total_tokens_deposited: u64,
+
+    /// Coins credited by `admin_mint_test_coins`; they count against `max_supply` like claims
+    pub total_coins_credited: u64,
+
     /// PDA bump seed
     pub bump: u8,
 }
@@ -79,6 +189,8 @@ impl GlobalState {
         32 + // authority
         32 + // dev_treasury
         32 + // reward_token_mint
+        1 + // version
+        1 + // use_randomness_feed
         1 + // game_has_started
         1 + // paused
         1 + // minting_enabled
@@ -88,44 +200,242 @@ impl GlobalState {
         8 + // unique_heroes_count
         8 + // total_hash_power
         16 + // cumulative_bombcoin_per_power
+        8 + // last_reward_time
         8 + // initial_house_price
         8 + // initial_bombcoin_per_block
         8 + // halving_interval
+        1 + // emission_schedule
         2 + // burn_pct
         2 + // referral_fee
+        2 * (MAX_REFERRAL_TIERS - 1) + // upline_referral_fees
         8 + // rewards_precision
+        8 + // max_supply
+        1 + // fusion_hero_count
+        2 + // fusion_success_bps
+        8 + // fusion_cost
+        2 + // marketplace_fee_bps
+        8 + // hero_price
+        1 + // max_hero_quantity
+        HouseLevelConfig::LEN * MAX_HOUSE_LEVEL + // house_levels
+        MAX_HERO_NFT_BASE_URI_LEN + // hero_nft_base_uri
+        8 + // total_emitted
         8 + // total_mined
         8 + // total_burned
         8 + // reward_pool
+        8 + // total_tokens_minted
+        8 + // total_tokens_burned
+        8 + // total_tokens_deposited
+        8 + // total_coins_credited
         1; // bump
 
-    /// Calculate current BOMBcoin per block based on halving
-    pub fn get_bombcoin_per_block(&self) -> u64 {
+    /// Offset of `version` in the account data
+    pub const VERSION_OFFSET: usize = 8 + 32 + 32 + 32;
+
+    /// Count SPL BOMBcoin minted by the program
+    pub fn record_token_mint(&mut self, amount: u64) -> Result<()> {
+        self.total_tokens_minted = self.total_tokens_minted
+            .checked_add(amount)
+            .ok_or(GameError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// Count a burn_pct share that was burned as SPL BOMBcoin
+    pub fn record_token_burn(&mut self, amount: u64) -> Result<()> {
+        self.total_burned = self.total_burned
+            .checked_add(amount)
+            .ok_or(GameError::ArithmeticOverflow)?;
+        self.total_tokens_burned = self.total_tokens_burned
+            .checked_add(amount)
+            .ok_or(GameError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// Mint supply the counters account for: minted minus everything the program burned
+    pub fn expected_token_supply(&self) -> u64 {
+        self.total_tokens_minted
+            .saturating_sub(self.total_tokens_burned)
+            .saturating_sub(self.total_tokens_deposited)
+    }
+
+    /// Base URI of exported hero NFTs (empty until the admin sets one)
+    pub fn nft_base_uri(&self) -> &str {
+        let len = self.hero_nft_base_uri
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(MAX_HERO_NFT_BASE_URI_LEN);
+        std::str::from_utf8(&self.hero_nft_base_uri[..len]).unwrap_or_default()
+    }
+
+    /// House table entry for `level` (clamped to 1..=MAX_HOUSE_LEVEL)
+    pub fn house_level(&self, level: u8) -> &HouseLevelConfig {
+        &self.house_levels[(level as usize).clamp(1, MAX_HOUSE_LEVEL) - 1]
+    }
+
+    /// Number of halvings applied at `current_time`
+    pub fn halvings(&self, current_time: i64) -> u64 {
         if self.halving_interval == 0 {
-            return self.initial_bombcoin_per_block;
+            return 0;
         }
 
-        let halvings = self.total_mined / self.halving_interval;
-        let mut rate = self.initial_bombcoin_per_block;
-
-        for _ in 0..halvings {
-            rate = rate / 2;
-            if rate == 0 {
-                break;
+        match self.emission_schedule {
+            EmissionSchedule::Supply => self.total_emitted / self.halving_interval,
+            EmissionSchedule::Time => {
+                if self.start_block == 0 {
+                    return 0;
+                }
+                let elapsed = current_time.saturating_sub(self.start_block).max(0) as u64;
+                elapsed / self.halving_interval
             }
         }
+    }
 
-        rate
+    /// Calculate current BOMBcoin per block based on halving
+    pub fn get_bombcoin_per_block(&self, current_time: i64) -> u64 {
+        halved_rate(self.initial_bombcoin_per_block, self.halvings(current_time))
     }
 
-    /// Calculate blocks until next halving
-    pub fn blocks_until_next_halving(&self) -> u64 {
+    /// Calculate distance to next halving (coins for Supply schedule, seconds for Time)
+    pub fn blocks_until_next_halving(&self, current_time: i64) -> u64 {
         if self.halving_interval == 0 {
             return u64::MAX;
         }
 
-        let next_milestone = ((self.total_mined / self.halving_interval) + 1) * self.halving_interval;
-        next_milestone.saturating_sub(self.total_mined)
+        let next_milestone = (self.halvings(current_time) + 1).saturating_mul(self.halving_interval);
+        match self.emission_schedule {
+            EmissionSchedule::Supply => next_milestone.saturating_sub(self.total_emitted),
+            EmissionSchedule::Time => {
+                let elapsed = current_time.saturating_sub(self.start_block).max(0) as u64;
+                next_milestone.saturating_sub(elapsed)
+            }
+        }
+    }
+
+    /// Referral fee for every tier, direct referrer first
+    pub fn referral_tier_fees(&self) -> [u16; MAX_REFERRAL_TIERS] {
+        let mut fees = [0u16; MAX_REFERRAL_TIERS];
+        fees[0] = self.referral_fee;
+        fees[1..].copy_from_slice(&self.upline_referral_fees);
+        fees
+    }
+
+    /// BOMBcoin that can still be minted before the cap is reached
+    pub fn remaining_supply(&self) -> u64 {
+        self.max_supply
+            .saturating_sub(self.total_mined)
+            .saturating_sub(self.total_coins_credited)
+    }
+
+    /// BOMBcoin issued against the cap: everything emitted (claimed or not) plus admin credits
+    pub fn issued_supply(&self) -> Result<u64> {
+        self.total_emitted
+            .max(self.total_mined)
+            .checked_add(self.total_coins_credited)
+            .ok_or(error!(GameError::ArithmeticOverflow))
+    }
+}
+
+/// GlobalState layout deployed before the incremental accumulator (version 1)
+/// Only read by `migrate_global_state` to upgrade accounts in place
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalStateV1 {
+    pub authority: Pubkey,
+    pub dev_treasury: Pubkey,
+    pub reward_token_mint: Pubkey,
+    pub game_has_started: bool,
+    pub paused: bool,
+    pub minting_enabled: bool,
+    pub house_upgrades_enabled: bool,
+    pub start_block: i64,
+    pub house_count: u64,
+    pub unique_heroes_count: u64,
+    pub total_hash_power: u64,
+    pub cumulative_bombcoin_per_power: u128,
+    pub initial_house_price: u64,
+    pub initial_bombcoin_per_block: u64,
+    pub halving_interval: u64,
+    pub burn_pct: u16,
+    pub referral_fee: u16,
+    pub rewards_precision: u64,
+    pub total_mined: u64,
+    pub total_burned: u64,
+    pub reward_pool: u64,
+    pub bump: u8,
+}
+
+impl GlobalStateV1 {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // authority
+        32 + // dev_treasury
+        32 + // reward_token_mint
+        1 + // game_has_started
+        1 + // paused
+        1 + // minting_enabled
+        1 + // house_upgrades_enabled
+        8 + // start_block
+        8 + // house_count
+        8 + // unique_heroes_count
+        8 + // total_hash_power
+        16 + // cumulative_bombcoin_per_power
+        8 + // initial_house_price
+        8 + // initial_bombcoin_per_block
+        8 + // halving_interval
+        2 + // burn_pct
+        2 + // referral_fee
+        8 + // rewards_precision
+        8 + // total_mined
+        8 + // total_burned
+        8 + // reward_pool
+        1; // bump
+
+    /// Upgrade to the current layout, starting the accumulator at `now`
+    /// The existing accumulator value is kept so recorded reward debts stay valid;
+    /// `token_supply` is the mint's current supply
+    pub fn into_current(self, now: i64, token_supply: u64) -> GlobalState {
+        GlobalState {
+            authority: self.authority,
+            dev_treasury: self.dev_treasury,
+            reward_token_mint: self.reward_token_mint,
+            version: GLOBAL_STATE_VERSION,
+            use_randomness_feed: false,
+            game_has_started: self.game_has_started,
+            paused: self.paused,
+            minting_enabled: self.minting_enabled,
+            house_upgrades_enabled: self.house_upgrades_enabled,
+            start_block: self.start_block,
+            house_count: self.house_count,
+            unique_heroes_count: self.unique_heroes_count,
+            total_hash_power: self.total_hash_power,
+            cumulative_bombcoin_per_power: self.cumulative_bombcoin_per_power,
+            last_reward_time: if self.game_has_started { now } else { 0 },
+            initial_house_price: self.initial_house_price,
+            initial_bombcoin_per_block: self.initial_bombcoin_per_block,
+            halving_interval: self.halving_interval,
+            emission_schedule: EmissionSchedule::Supply,
+            burn_pct: self.burn_pct,
+            referral_fee: self.referral_fee,
+            upline_referral_fees: [0; MAX_REFERRAL_TIERS - 1],
+            rewards_precision: self.rewards_precision,
+            max_supply: MAX_BOMBCOIN_SUPPLY,
+            fusion_hero_count: DEFAULT_FUSION_HERO_COUNT,
+            fusion_success_bps: DEFAULT_FUSION_SUCCESS_BPS,
+            fusion_cost: DEFAULT_FUSION_COST,
+            marketplace_fee_bps: DEFAULT_MARKETPLACE_FEE_BPS,
+            hero_price: DEFAULT_HERO_PRICE,
+            max_hero_quantity: DEFAULT_MAX_HERO_QUANTITY,
+            house_levels: DEFAULT_HOUSE_LEVELS,
+            hero_nft_base_uri: [0; MAX_HERO_NFT_BASE_URI_LEN],
+            // Best available estimate of what was emitted before tracking began
+            total_emitted: self.total_mined,
+            total_mined: self.total_mined,
+            total_burned: self.total_burned,
+            reward_pool: self.reward_pool,
+            // Tokens already in circulation count as minted
+            total_tokens_minted: token_supply,
+            total_tokens_burned: 0,
+            total_tokens_deposited: 0,
+            total_coins_credited: 0,
+            bump: self.bump,
+        }
     }
 }
 
@@ -138,7 +448,7 @@ pub struct HouseTile {
     /// Y coordinate in grid
     pub y: u8,
 
-    /// Hero inventory index (u16::MAX if empty)
+    /// Hero ID (u16::MAX if empty)
     pub hero_id: u16,
 
     /// Is this tile a restroom (boosted recovery)?
@@ -146,6 +456,11 @@ pub struct HouseTile {
 }
 
 impl HouseTile {
+    pub const LEN: usize = 1 + // x
+        1 + // y
+        2 + // hero_id
+        1; // is_restroom
+
     pub fn is_empty(&self) -> bool {
         self.hero_id == u16::MAX
     }
@@ -160,65 +475,58 @@ impl HouseTile {
     }
 }
 
-/// House grid dimensions by level
+/// House stats for one level (admin-configurable)
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
-pub struct GridDimensions {
-    pub width: u8,
-    pub height: u8,
-}
-
-impl GridDimensions {
-    /// Get grid size for a given house level
-    pub fn for_level(level: u8) -> Self {
-        match level {
-            1 => Self { width: 4, height: 4 },  // 16 tiles
-            2 => Self { width: 4, height: 6 },  // 24 tiles
-            3 => Self { width: 5, height: 6 },  // 30 tiles
-            4 => Self { width: 6, height: 6 },  // 36 tiles
-            5 => Self { width: 6, height: 7 },  // 42 tiles
-            6 => Self { width: 7, height: 7 },  // 49 tiles
-            _ => Self { width: 4, height: 4 },  // Default to level 1
-        }
-    }
+pub struct HouseLevelConfig {
+    /// Grid size at this level
+    pub grid_width: u8,
+    pub grid_height: u8,
 
-    /// Get maximum restroom slots for a given house level
-    pub fn max_restroom_slots(level: u8) -> usize {
-        match level {
-            1 => 4,
-            2 => 6,
-            3 => 8,
-            4 => 10,
-            5 => 12,
-            6 => 15,
-            _ => 4,
-        }
-    }
+    /// Restroom slots at this level
+    pub max_restroom_slots: u8,
+
+    /// Coins to upgrade to the next level (unused at the top level)
+    pub upgrade_cost: u64,
+
+    /// Seconds before a house that reached this level can upgrade again
+    pub upgrade_cooldown: i64,
+}
+
+impl HouseLevelConfig {
+    pub const LEN: usize = 1 + 1 + 1 + 8 + 8;
 
-    /// Get upgrade cost in coins for next level
-    pub fn upgrade_cost(current_level: u8) -> u64 {
-        match current_level {
-            1 => 500,
-            2 => 1000,
-            3 => 2000,
-            4 => 4000,
-            5 => 8000,
-            _ => 0,
+    pub const fn new(
+        grid_width: u8,
+        grid_height: u8,
+        max_restroom_slots: u8,
+        upgrade_cost: u64,
+        upgrade_cooldown: i64,
+    ) -> Self {
+        Self {
+            grid_width,
+            grid_height,
+            max_restroom_slots,
+            upgrade_cost,
+            upgrade_cooldown,
         }
     }
 
-    /// Get upgrade cooldown in seconds
-    pub fn upgrade_cooldown(current_level: u8) -> i64 {
-        match current_level {
-            1 => 3600,      // 1 hour
-            2 => 7200,      // 2 hours
-            3 => 14400,     // 4 hours
-            4 => 28800,     // 8 hours
-            5 => 57600,     // 16 hours
-            _ => 0,
-        }
+    /// Tiles in the grid
+    pub fn grid_tiles(&self) -> usize {
+        self.grid_width as usize * self.grid_height as usize
     }
 }
 
+/// Default house table, level 1 first
+pub const DEFAULT_HOUSE_LEVELS: [HouseLevelConfig; MAX_HOUSE_LEVEL] = [
+    HouseLevelConfig::new(4, 4, 4, 500, 3_600),    // 16 tiles, 1 hour
+    HouseLevelConfig::new(4, 6, 6, 1_000, 7_200),  // 24 tiles, 2 hours
+    HouseLevelConfig::new(5, 6, 8, 2_000, 14_400), // 30 tiles, 4 hours
+    HouseLevelConfig::new(6, 6, 10, 4_000, 28_800), // 36 tiles, 8 hours
+    HouseLevelConfig::new(6, 7, 12, 8_000, 57_600), // 42 tiles, 16 hours
+    HouseLevelConfig::new(7, 7, 15, 0, 0),         // 49 tiles, max level
+];
+
 /// Hero rarity tiers (affects drop rates and stat ranges)
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum HeroRarity {
@@ -231,7 +539,7 @@ pub enum HeroRarity {
 }
 
 impl HeroRarity {
-    /// Determine rarity from a random value (0-1000)
+    /// Determine rarity from a random value (0-999, each value is 0.1%)
     pub fn from_roll(roll: u16) -> Self {
         match roll {
             0..=499 => HeroRarity::Common,
@@ -244,6 +552,31 @@ impl HeroRarity {
         }
     }
 
+    /// Tier a fusion of this rarity produces (None for Legendary)
+    pub fn next_tier(&self) -> Option<Self> {
+        match self {
+            HeroRarity::Common => Some(HeroRarity::Uncommon),
+            HeroRarity::Uncommon => Some(HeroRarity::Rare),
+            HeroRarity::Rare => Some(HeroRarity::SuperRare),
+            HeroRarity::SuperRare => Some(HeroRarity::Epic),
+            HeroRarity::Epic => Some(HeroRarity::Legendary),
+            HeroRarity::Legendary => None,
+        }
+    }
+
+    /// Coins refunded when a hero of this rarity is retired
+    /// (paid from the reward pool; a hero costs DEFAULT_HERO_PRICE coins to mint by default)
+    pub fn retire_refund(&self) -> u64 {
+        match self {
+            HeroRarity::Common => 20,
+            HeroRarity::Uncommon => 40,
+            HeroRarity::Rare => 80,
+            HeroRarity::SuperRare => 160,
+            HeroRarity::Epic => 320,
+            HeroRarity::Legendary => 640,
+        }
+    }
+
     /// Get stat ranges for this rarity
     pub fn stat_range(&self) -> (u32, u32) {
         match self {
@@ -281,6 +614,25 @@ impl HeroRarity {
     }
 }
 
+/// Highest level a hero can reach
+pub const MAX_HERO_LEVEL: u8 = 10;
+
+/// XP earned per minute a hero spends mining with HP left
+pub const XP_PER_MINUTE: u64 = 1;
+
+/// Power, speed and stamina gained per level (capped by rarity)
+pub const STAT_GAIN_PER_LEVEL: u32 = 3;
+
+/// Coin cost of a level-up at level 1 for a Common hero
+pub const LEVEL_UP_BASE_COST: u64 = 50;
+
+/// Fixed-point scale of HMP (heroes report it in hundredths)
+pub const HMP_SCALE: u64 = 100;
+
+/// HP recovery multiplier on the bench and in a restroom (bps, 10000 = 1x)
+pub const BENCH_RECOVERY_BPS: u64 = 10_000;
+pub const RESTROOM_RECOVERY_BPS: u64 = 30_000;
+
 /// Individual hero (template-based with 9 skin archetypes)
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
 pub struct Hero {
@@ -319,15 +671,36 @@ pub struct Hero {
 
     /// Last action timestamp (for time-delta calculations)
     pub last_action_time: i64,
+
+    /// Current level (1 to MAX_HERO_LEVEL)
+    pub level: u8,
+
+    /// Lifetime experience from mining
+    pub xp: u64,
 }
 
 impl Hero {
-    /// Calculate HMP (Hero Mining Power)
+    pub const LEN: usize = 2 + // id
+        1 + // skin_id
+        1 + // rarity
+        4 + // power
+        4 + // speed
+        4 + // stamina
+        4 + // max_stamina
+        1 + // bomb_number
+        1 + // bomb_range
+        4 + // hp
+        4 + // max_hp
+        8 + // last_action_time
+        1 + // level
+        8; // xp
+
+    /// Calculate HMP (Hero Mining Power) in hundredths (HMP × HMP_SCALE)
     /// Formula: HMP = (Power × Bomb_Count) + (Bomb_Range × 0.5) + (Speed × 2)
-    pub fn calculate_hmp(&self) -> f64 {
-        let power_component = (self.power * self.bomb_number as u32) as f64;
-        let range_component = (self.bomb_range as f64) * 0.5;
-        let speed_component = (self.speed as f64) * 2.0;
+    pub fn calculate_hmp(&self) -> u64 {
+        let power_component = self.power as u64 * self.bomb_number as u64 * HMP_SCALE;
+        let range_component = self.bomb_range as u64 * HMP_SCALE / 2;
+        let speed_component = self.speed as u64 * 2 * HMP_SCALE;
         power_component + range_component + speed_component
     }
 
@@ -338,12 +711,57 @@ impl Hero {
         (minutes * self.speed as u64) as u32
     }
 
-    /// Calculate HP recovery over time
+    /// Calculate HP recovery over time (multiplier in bps, 10000 = 1x)
     /// Formula: HP Recovery = (Elapsed_Seconds / 120) × Stamina × Location_Multiplier
-    pub fn calculate_hp_recovery(&self, elapsed_seconds: u64, location_multiplier: f64) -> u32 {
+    pub fn calculate_hp_recovery(&self, elapsed_seconds: u64, location_multiplier_bps: u64) -> u32 {
         let ticks = elapsed_seconds / 120; // 120-second intervals
-        let base_recovery = (ticks * self.stamina as u64) as f64;
-        (base_recovery * location_multiplier) as u32
+        let recovery = ticks as u128 * self.stamina as u128 * location_multiplier_bps as u128 / 10_000;
+        recovery.min(u32::MAX as u128) as u32
+    }
+
+    /// Drain HP and accrue XP for `elapsed_seconds` spent on the map
+    /// XP only counts the minutes the hero mined before running out of HP
+    pub fn apply_mining(&mut self, elapsed_seconds: u64) {
+        if self.is_sleeping() {
+            return;
+        }
+
+        let hp_drain = self.calculate_hp_drain(elapsed_seconds);
+        let mined_minutes = if hp_drain > self.hp && self.speed > 0 {
+            (self.hp as u64).div_ceil(self.speed as u64)
+        } else {
+            elapsed_seconds / 60
+        };
+
+        self.xp = self.xp.saturating_add(mined_minutes.saturating_mul(XP_PER_MINUTE));
+        self.hp = self.hp.saturating_sub(hp_drain);
+    }
+
+    /// Total XP needed to reach `level`
+    /// Formula: 30 × level × (level - 1), i.e. 1h of mining for level 2, 3h for level 3...
+    pub fn xp_for_level(level: u8) -> u64 {
+        let level = level as u64;
+        30 * level * level.saturating_sub(1)
+    }
+
+    /// Coin cost to go from the current level to the next
+    /// Formula: LEVEL_UP_BASE_COST × level × rarity tier (Common = 1 … Legendary = 6)
+    pub fn level_up_cost(&self) -> u64 {
+        LEVEL_UP_BASE_COST * self.level as u64 * (self.rarity as u64 + 1)
+    }
+
+    /// Raise power, speed and stamina by one level's gain, capped by rarity
+    /// Returns false if every stat was already at the rarity cap
+    pub fn raise_stats(&mut self) -> bool {
+        let (_, stat_max) = self.rarity.stat_range();
+        let before = (self.power, self.speed, self.stamina);
+
+        self.power = self.power.saturating_add(STAT_GAIN_PER_LEVEL).min(stat_max).max(self.power);
+        self.speed = self.speed.saturating_add(STAT_GAIN_PER_LEVEL).min(stat_max).max(self.speed);
+        self.stamina = self.stamina.saturating_add(STAT_GAIN_PER_LEVEL).min(stat_max).max(self.stamina);
+        self.max_stamina = self.max_stamina.max(self.stamina);
+
+        (self.power, self.speed, self.stamina) != before
     }
 
     /// Check if hero is sleeping (HP = 0)
@@ -357,12 +775,320 @@ impl Hero {
     }
 }
 
+/// Symbol of exported hero NFTs
+pub const HERO_NFT_SYMBOL: &str = "BHERO";
+
+/// Longest hero NFT base URI; the stats query string appended to it must still fit
+/// Metaplex's 200-byte URI limit
+pub const MAX_HERO_NFT_BASE_URI_LEN: usize = 72;
+
+/// Hero held outside the game as a 1-of-1 NFT
+///
+/// Created by `export_hero` and closed by `import_hero`. The token metadata
+/// mirrors the stats for wallets and marketplaces, but this record is what
+/// gets re-inserted on import.
+#[account]
+pub struct ExportedHero {
+    /// NFT mint representing the hero
+    pub mint: Pubkey,
+
+    /// Wallet that exported the hero
+    pub exported_by: Pubkey,
+
+    /// Hero as it left the inventory
+    pub hero: Hero,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl ExportedHero {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // mint
+        32 + // exported_by
+        Hero::LEN + // hero
+        1; // bump
+}
+
+/// Heroes held by one inventory page
+pub const HEROES_PER_PAGE: usize = 32;
+
+/// One page of a player's hero inventory
+///
+/// Seeds: [b"hero_page", owner, page index]. Hero `id` always lives on page
+/// `id / HEROES_PER_PAGE` in slot `id % HEROES_PER_PAGE`, so instructions only
+/// load the pages holding the heroes they touch. IDs are never reused, so the
+/// slot of a hero that left the inventory stays empty.
+#[account]
+pub struct HeroPage {
+    /// Owner wallet
+    pub owner: Pubkey,
+
+    /// Page index (first hero ID on the page / HEROES_PER_PAGE)
+    pub index: u16,
+
+    /// Hero slots, indexed by hero ID within the page
+    pub heroes: [Option<Hero>; HEROES_PER_PAGE],
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl HeroPage {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // owner
+        2 + // index
+        HEROES_PER_PAGE * (1 + Hero::LEN) + // heroes ([Option<Hero>; HEROES_PER_PAGE])
+        1; // bump
+
+    /// Highest page index (hero ID u16::MAX is never issued)
+    pub const MAX_INDEX: u16 = (u16::MAX - 1) / HEROES_PER_PAGE as u16;
+
+    /// Index of the page holding this hero ID
+    pub fn index_of(hero_id: u16) -> u16 {
+        hero_id / HEROES_PER_PAGE as u16
+    }
+
+    /// Hero with this ID, if it is on this page
+    pub fn hero(&self, hero_id: u16) -> Option<&Hero> {
+        if Self::index_of(hero_id) != self.index {
+            return None;
+        }
+        self.heroes[hero_id as usize % HEROES_PER_PAGE].as_ref()
+    }
+
+    /// Mutable hero with this ID, if it is on this page
+    pub fn hero_mut(&mut self, hero_id: u16) -> Option<&mut Hero> {
+        if Self::index_of(hero_id) != self.index {
+            return None;
+        }
+        self.heroes[hero_id as usize % HEROES_PER_PAGE].as_mut()
+    }
+
+    /// Put a hero into its slot; false if it belongs to another page or the slot is taken
+    pub fn insert(&mut self, hero: Hero) -> bool {
+        if Self::index_of(hero.id) != self.index {
+            return false;
+        }
+        let slot = &mut self.heroes[hero.id as usize % HEROES_PER_PAGE];
+        if slot.is_some() {
+            return false;
+        }
+        *slot = Some(hero);
+        true
+    }
+
+    /// Take the hero with this ID out of its slot
+    pub fn remove(&mut self, hero_id: u16) -> Option<Hero> {
+        if Self::index_of(hero_id) != self.index {
+            return None;
+        }
+        self.heroes[hero_id as usize % HEROES_PER_PAGE].take()
+    }
+
+    /// Number of heroes on the page
+    pub fn hero_count(&self) -> usize {
+        self.heroes.iter().filter(|slot| slot.is_some()).count()
+    }
+}
+
+/// Incinerator address; lamports sent here are burned at the end of the block
+pub const INCINERATOR: Pubkey = pubkey!("1nc1nerator11111111111111111111111111111111");
+
+/// What a marketplace listing is priced in
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListingCurrency {
+    /// BOMBcoin SPL tokens (fee burn portion is burned with token::burn)
+    Bombcoin,
+
+    /// Lamports (fee burn portion is sent to the incinerator)
+    Sol,
+}
+
+/// Hero escrowed for sale on the marketplace
+///
+/// Seeds: [b"listing", seller, hero id]. The hero is out of the seller's
+/// inventory while listed; cancelling puts it back under the same ID.
+#[account]
+pub struct HeroListing {
+    /// Seller wallet (receives the price minus the marketplace fee)
+    pub seller: Pubkey,
+
+    /// Escrowed hero
+    pub hero: Hero,
+
+    /// Asking price (BOMBcoin base units or lamports)
+    pub price: u64,
+
+    /// Currency of `price`
+    pub currency: ListingCurrency,
+
+    /// Listing timestamp
+    pub created_at: i64,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl HeroListing {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // seller
+        Hero::LEN + // hero
+        8 + // price
+        1 + // currency
+        8 + // created_at
+        1; // bump
+}
+
+/// Slots between a hero mint commitment and the slot whose hash reveals it
+pub const REVEAL_DELAY_SLOTS: u64 = 2;
+
+/// Hero mint paid for in `buy_hero` and finalized in `reveal_heroes`
+///
+/// Rarity is rolled from randomness that does not exist yet at commit time,
+/// so the player cannot pick a favorable slot or timestamp to mint in.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PendingMint {
+    /// Number of heroes paid for
+    pub quantity: u8,
+
+    /// Slot whose hash seeds the reveal (SlotHashes source)
+    pub target_slot: u64,
+
+    /// Randomness source chosen at commit time
+    pub use_feed: bool,
+
+    /// Feed sequence that seeds the reveal (randomness feed source)
+    pub feed_sequence: u64,
+
+    /// Fusion attempt: rarity produced on success (quantity is 1)
+    pub fuse_into: Option<HeroRarity>,
+
+    /// Fusion success chance locked in at commit time (bps)
+    pub fusion_success_bps: u16,
+
+    /// Global hero count at commit time; hero `i` of the batch rolls from
+    /// `nonce + i`, so nothing that changes before the reveal feeds the rolls
+    pub nonce: u64,
+}
+
+impl PendingMint {
+    pub const LEN: usize = 1 + // quantity
+        8 + // target_slot
+        1 + // use_feed
+        8 + // feed_sequence
+        1 + 1 + // fuse_into (Option<HeroRarity>)
+        2 + // fusion_success_bps
+        8; // nonce
+}
+
+/// Oracle-style randomness account
+///
+/// Local stand-in for a VRF feed: the feed authority publishes a fresh value,
+/// and each publication bumps `sequence`. A pending mint is bound to the first
+/// publication after its commit.
+#[account]
+pub struct RandomnessFeed {
+    /// Authority allowed to publish randomness
+    pub authority: Pubkey,
+
+    /// Number of values published so far
+    pub sequence: u64,
+
+    /// Slot of the latest publication
+    pub slot: u64,
+
+    /// Latest published randomness
+    pub value: [u8; 32],
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl RandomnessFeed {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // authority
+        8 + // sequence
+        8 + // slot
+        32 + // value
+        1; // bump
+}
+
+/// Scale of `StakePool::acc_dividend_per_share`
+pub const DIVIDEND_PRECISION: u64 = 1_000_000_000_000;
+
+/// BOMBcoin staking pool that pays `GlobalState.reward_pool` out as dividends
+///
+/// Seeds: [b"stake_pool"]. Staked tokens sit in the stake vault token account
+/// ([b"stake_vault"], authority: global state). Whatever has reached the reward
+/// pool is shared pro-rata over the tokens staked at the time it is distributed.
+#[account]
+pub struct StakePool {
+    /// BOMBcoin currently staked in the vault
+    pub total_staked: u64,
+
+    /// Cumulative dividends per staked token, scaled by DIVIDEND_PRECISION
+    pub acc_dividend_per_share: u128,
+
+    /// Dividends moved out of the reward pool to stakers
+    pub total_distributed: u64,
+
+    /// Dividends minted to stakers
+    pub total_claimed: u64,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl StakePool {
+    pub const LEN: usize = 8 + // discriminator
+        8 + // total_staked
+        16 + // acc_dividend_per_share
+        8 + // total_distributed
+        8 + // total_claimed
+        1; // bump
+}
+
+/// One wallet's stake in the StakePool
+///
+/// Seeds: [b"stake_account", owner]
+#[account]
+pub struct StakeAccount {
+    /// Staker wallet
+    pub owner: Pubkey,
+
+    /// BOMBcoin staked
+    pub amount: u64,
+
+    /// amount * acc_dividend_per_share when dividends were last harvested
+    pub reward_debt: u128,
+
+    /// Dividends harvested but not yet claimed
+    pub pending_dividends: u64,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl StakeAccount {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // owner
+        8 + // amount
+        16 + // reward_debt
+        8 + // pending_dividends
+        1; // bump
+}
+
 /// Per-player account - stores all game state for one user
 #[account]
 pub struct UserAccount {
     /// Owner wallet
     pub owner: Pubkey,
 
+    /// Account layout version (USER_ACCOUNT_VERSION)
+    /// Unversioned layouts kept a bool here, so they read as 0 or 1
+    pub version: u8,
+
     // ========== House System ==========
     /// Has player purchased initial house?
     pub initialized_starter_house: bool,
@@ -382,13 +1108,23 @@ pub struct UserAccount {
     pub house_occupied_coords: Vec<HouseTile>,
 
     // ========== Heroes ==========
-    /// All owned heroes (unlimited capacity)
+    /// Legacy hero storage; heroes now live in HeroPage accounts and
+    /// `migrate_inventory` moves these out
     pub inventory: Vec<Hero>,
 
     /// Heroes currently active on map (mining)
-    /// Stores indices into inventory Vec
+    /// Stores stable hero IDs (not inventory positions)
     pub active_map: Vec<u16>,
 
+    /// Paid hero mint waiting to be revealed (commit-reveal)
+    pub pending_mint: Option<PendingMint>,
+
+    /// ID assigned to the next minted hero (IDs are never reused)
+    pub next_hero_id: u16,
+
+    /// Heroes held in hero pages
+    pub hero_count: u16,
+
     // ========== Economy ==========
     /// In-game coin balance (not SPL tokens)
     pub coin_balance: u64,
@@ -412,6 +1148,9 @@ pub struct UserAccount {
     /// Total bonus paid from referrals
     pub referral_bonus_paid: u64,
 
+    /// Referral bonus earned per tier (index 0 = from direct referrals)
+    pub referral_earnings_by_tier: [u64; MAX_REFERRAL_TIERS],
+
     /// List of users referred by this player
     pub referrals: Vec<Pubkey>,
 
@@ -420,18 +1159,34 @@ pub struct UserAccount {
 }
 
 impl UserAccount {
-    // Maximum size estimate (variable due to Vecs)
-    // Base size + reasonable maximums for vectors
-    pub const MAX_LEN: usize = 8 + // discriminator
+    /// Maximum number of referred users tracked per player
+    pub const MAX_REFERRALS: usize = 100;
+
+    /// Maximum number of heroes owned (across all hero pages)
+    pub const MAX_HEROES: usize = 32 * HEROES_PER_PAGE;
+
+    /// Maximum number of heroes mining at once
+    pub const MAX_MAP_HEROES: usize = 15;
+
+    /// Grid tiles at the highest house level (7x7)
+    pub const MAX_GRID_TILES: usize = 49;
+
+    /// Size with every collection empty; the account starts here and grows
+    /// with `realloc` as tiles, map heroes and referrals are added
+    pub const BASE_LEN: usize = 8 + // discriminator
         32 + // owner
+        1 + // version
         1 + // initialized_starter_house
         1 + // house_level
         8 + // last_house_upgrade_timestamp
         1 + // grid_width
         1 + // grid_height
-        4 + (30 * 6) + // house_occupied_coords (max ~30 tiles, reduced from 50)
-        4 + (50 * 120) + // inventory (max ~50 heroes, reduced from 100)
-        4 + (15 * 2) + // active_map (max 15 heroes)
+        4 + // house_occupied_coords (Vec length prefix)
+        4 + // inventory (Vec length prefix)
+        4 + // active_map (Vec length prefix)
+        1 + PendingMint::LEN + // pending_mint (Option<PendingMint>, always reserved)
+        2 + // next_hero_id
+        2 + // hero_count
         8 + // coin_balance
         8 + // player_power
         8 + // player_pending_rewards
@@ -439,22 +1194,38 @@ impl UserAccount {
         16 + // reward_debt
         1 + 32 + // referrer (Option<Pubkey>)
         8 + // referral_bonus_paid
-        4 + (20 * 32) + // referrals (max ~20 referrals, reduced from 50)
+        8 * MAX_REFERRAL_TIERS + // referral_earnings_by_tier
+        4 + // referrals (Vec length prefix)
         1; // bump
 
-    /// Get current house upgrade cost
-    pub fn get_upgrade_cost(&self) -> u64 {
-        GridDimensions::upgrade_cost(self.house_level)
+    /// Size with every collection at its cap (heroes live in hero pages,
+    /// the legacy inventory only shrinks)
+    pub const MAX_LEN: usize = Self::BASE_LEN +
+        Self::MAX_GRID_TILES * HouseTile::LEN +
+        Self::MAX_MAP_HEROES * 2 +
+        Self::MAX_REFERRALS * 32;
+
+    /// Offset of `version` in the account data
+    pub const VERSION_OFFSET: usize = 8 + 32;
+
+    /// Bytes needed for the current contents
+    pub fn required_len(&self) -> usize {
+        Self::BASE_LEN
+            + self.house_occupied_coords.len() * HouseTile::LEN
+            + self.inventory.len() * Hero::LEN
+            + self.active_map.len() * 2
+            + self.referrals.len() * 32
     }
 
-    /// Get current house upgrade cooldown duration
-    pub fn get_upgrade_cooldown(&self) -> i64 {
-        GridDimensions::upgrade_cooldown(self.house_level)
+    /// Heroes owned, in hero pages or not yet migrated
+    pub fn total_heroes(&self) -> usize {
+        self.hero_count as usize + self.inventory.len()
     }
 
-    /// Get max restroom slots for current house level
-    pub fn get_max_restroom_slots(&self) -> usize {
-        GridDimensions::max_restroom_slots(self.house_level)
+    /// Heroes that can still be added, counting those a pending mint will reveal
+    pub fn remaining_hero_slots(&self) -> usize {
+        let pending_heroes = self.pending_mint.map_or(0, |p| p.quantity as usize);
+        Self::MAX_HEROES.saturating_sub(self.total_heroes() + pending_heroes)
     }
 
     /// Count current restroom slots in use
@@ -465,11 +1236,34 @@ impl UserAccount {
             .count()
     }
 
-    /// Find hero on grid by inventory index
-    pub fn find_hero_on_grid(&self, hero_index: u16) -> Option<&HouseTile> {
+    /// Whether the inventory can take `count` more heroes
+    pub fn has_inventory_space(&self, count: usize) -> bool {
+        count <= self.remaining_hero_slots()
+    }
+
+    /// Reserve the next hero ID (None once the ID space is exhausted)
+    pub fn allocate_hero_id(&mut self) -> Option<u16> {
+        let id = self.next_hero_id;
+        // u16::MAX marks an empty grid tile, so it is never handed out
+        let next = id.checked_add(1)?;
+        self.next_hero_id = next;
+        Some(id)
+    }
+
+    /// Find hero on grid by hero ID
+    pub fn find_hero_on_grid(&self, hero_id: u16) -> Option<&HouseTile> {
         self.house_occupied_coords
             .iter()
-            .find(|tile| tile.hero_id == hero_index)
+            .find(|tile| tile.hero_id == hero_id)
+    }
+
+    /// Drop the map and grid entries of heroes leaving the hero pages
+    /// IDs are stable, so the remaining references stay valid
+    pub fn release_heroes(&mut self, hero_ids: &[u16]) {
+        self.active_map.retain(|id| !hero_ids.contains(id));
+        self.house_occupied_coords
+            .retain(|tile| !hero_ids.contains(&tile.hero_id));
+        self.hero_count = self.hero_count.saturating_sub(hero_ids.len() as u16);
     }
 
     /// Check if coordinate is valid for current grid
@@ -484,3 +1278,149 @@ impl UserAccount {
             .any(|tile| tile.x == x && tile.y == y && !tile.is_empty())
     }
 }
+
+/// Hero layout deployed before hero levels (no `level` / `xp`)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct HeroV1 {
+    pub id: u16,
+    pub skin_id: u8,
+    pub rarity: HeroRarity,
+    pub power: u32,
+    pub speed: u32,
+    pub stamina: u32,
+    pub max_stamina: u32,
+    pub bomb_number: u8,
+    pub bomb_range: u8,
+    pub hp: u32,
+    pub max_hp: u32,
+    pub last_action_time: i64,
+}
+
+impl HeroV1 {
+    /// Upgrade to the current layout at level 1 without XP
+    pub fn into_current(self) -> Hero {
+        Hero {
+            id: self.id,
+            skin_id: self.skin_id,
+            rarity: self.rarity,
+            power: self.power,
+            speed: self.speed,
+            stamina: self.stamina,
+            max_stamina: self.max_stamina,
+            bomb_number: self.bomb_number,
+            bomb_range: self.bomb_range,
+            hp: self.hp,
+            max_hp: self.max_hp,
+            last_action_time: self.last_action_time,
+            level: 1,
+            xp: 0,
+        }
+    }
+}
+
+/// UserAccount layout of the original deployment (version 1)
+/// Only read by `migrate_user_account` to upgrade accounts in place
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct UserAccountV1 {
+    pub owner: Pubkey,
+    pub initialized_starter_house: bool,
+    pub house_level: u8,
+    pub last_house_upgrade_timestamp: i64,
+    pub grid_width: u8,
+    pub grid_height: u8,
+    /// Tiles point at inventory positions
+    pub house_occupied_coords: Vec<HouseTile>,
+    pub inventory: Vec<HeroV1>,
+    /// Inventory positions of the mining heroes
+    pub active_map: Vec<u16>,
+    pub coin_balance: u64,
+    pub player_power: u64,
+    pub player_pending_rewards: u64,
+    pub last_reward_block: i64,
+    pub reward_debt: u128,
+    pub referrer: Option<Pubkey>,
+    pub referral_bonus_paid: u64,
+    pub referrals: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+impl UserAccountV1 {
+    /// Space every version 1 account was created with
+    pub const LEN: usize = 8 + // discriminator
+        32 + // owner
+        1 + // initialized_starter_house
+        1 + // house_level
+        8 + // last_house_upgrade_timestamp
+        1 + // grid_width
+        1 + // grid_height
+        4 + (30 * 6) + // house_occupied_coords
+        4 + (50 * 120) + // inventory
+        4 + (15 * 2) + // active_map
+        8 + // coin_balance
+        8 + // player_power
+        8 + // player_pending_rewards
+        8 + // last_reward_block
+        16 + // reward_debt
+        1 + 32 + // referrer (Option<Pubkey>)
+        8 + // referral_bonus_paid
+        4 + (20 * 32) + // referrals
+        1; // bump
+
+    /// Upgrade to the current layout
+    /// Heroes stay in the legacy inventory for `migrate_inventory`; tiles and the map
+    /// switch from inventory positions to hero IDs. Power and reward debt are kept
+    /// as-is, matching the accumulator kept by `GlobalStateV1::into_current`.
+    pub fn into_current(self) -> UserAccount {
+        let inventory: Vec<Hero> = self.inventory.into_iter().map(HeroV1::into_current).collect();
+        let hero_id_at = |index: u16| inventory.get(index as usize).map(|hero| hero.id);
+
+        let house_occupied_coords = self
+            .house_occupied_coords
+            .into_iter()
+            .map(|tile| HouseTile {
+                hero_id: if tile.is_empty() {
+                    tile.hero_id
+                } else {
+                    hero_id_at(tile.hero_id).unwrap_or(u16::MAX)
+                },
+                ..tile
+            })
+            .collect();
+        let active_map = self.active_map.into_iter().filter_map(hero_id_at).collect();
+
+        // New IDs must not land on existing heroes
+        let next_hero_id = inventory
+            .iter()
+            .map(|hero| hero.id.saturating_add(1))
+            .fold(inventory.len() as u16, u16::max);
+
+        let mut referral_earnings_by_tier = [0; MAX_REFERRAL_TIERS];
+        referral_earnings_by_tier[0] = self.referral_bonus_paid;
+
+        UserAccount {
+            owner: self.owner,
+            version: USER_ACCOUNT_VERSION,
+            initialized_starter_house: self.initialized_starter_house,
+            house_level: self.house_level,
+            last_house_upgrade_timestamp: self.last_house_upgrade_timestamp,
+            grid_width: self.grid_width,
+            grid_height: self.grid_height,
+            house_occupied_coords,
+            inventory,
+            active_map,
+            pending_mint: None,
+            next_hero_id,
+            hero_count: 0,
+            coin_balance: self.coin_balance,
+            player_power: self.player_power,
+            player_pending_rewards: self.player_pending_rewards,
+            last_reward_block: self.last_reward_block,
+            reward_debt: self.reward_debt,
+            referrer: self.referrer,
+            referral_bonus_paid: self.referral_bonus_paid,
+            referral_earnings_by_tier,
+            referrals: self.referrals,
+            bump: self.bump,
+        }
+    }
+}
@@ -1,37 +1,90 @@
 use anchor_lang::prelude::*;
+use solana_sha256_hasher::hashv;
 use crate::state::*;
+use crate::errors::GameError;
+
+/// Deterministic SHA-256 stream for hero rolls
+///
+/// The seed is hashed once from all entropy inputs, then expanded in counter
+/// mode: block_n = sha256(seed || n). Every roll consumes fresh bytes, so stats
+/// are independent of each other and of the rarity roll.
+pub struct HeroRng {
+    seed: [u8; 32],
+    counter: u64,
+    block: [u8; 32],
+    offset: usize,
+}
+
+impl HeroRng {
+    /// Seed the stream from a list of entropy inputs
+    pub fn new(inputs: &[&[u8]]) -> Self {
+        Self {
+            seed: hashv(inputs).to_bytes(),
+            counter: 0,
+            block: [0u8; 32],
+            offset: 32,
+        }
+    }
+
+    /// Next 4 bytes of the stream as a u32
+    pub fn next_u32(&mut self) -> u32 {
+        if self.offset + 4 > self.block.len() {
+            self.block = hashv(&[&self.seed, &self.counter.to_le_bytes()]).to_bytes();
+            self.counter += 1;
+            self.offset = 0;
+        }
+
+        let bytes: [u8; 4] = self.block[self.offset..self.offset + 4].try_into().unwrap();
+        self.offset += 4;
+        u32::from_le_bytes(bytes)
+    }
+
+    /// Uniform u32 within [min, max] inclusive
+    /// Rejection sampling drops the tail of the u32 space that would bias a plain modulo
+    pub fn range_u32(&mut self, min: u32, max: u32) -> u32 {
+        if min >= max {
+            return min;
+        }
+
+        let span = (max - min) as u64 + 1;
+        let space = 1u64 << 32;
+        let zone = space - (space % span);
+
+        loop {
+            let value = self.next_u32() as u64;
+            if value < zone {
+                return min + (value % span) as u32;
+            }
+        }
+    }
+
+    /// Uniform u8 within [min, max] inclusive
+    pub fn range_u8(&mut self, min: u8, max: u8) -> u8 {
+        self.range_u32(min as u32, max as u32) as u8
+    }
+}
 
 /// Generate a random hero with template-based skin and rarity-constrained stats
-/// Uses timestamp + slot + user pubkey + hero count as pseudo-randomness source
+/// Entropy: committed randomness + user pubkey + the hero's nonce from the commitment.
+/// Nothing read at reveal time (timestamp, hero id, global counters) feeds the rolls,
+/// so delaying a reveal cannot be used to grind for a better hero.
+/// `rarity_override` skips the rarity roll and only rolls stats for that tier
 pub fn generate_hero(
     id: u16,
     timestamp: i64,
-    slot: u64,
+    randomness: &[u8; 32],
     user_pubkey: Pubkey,
-    global_hero_count: u64,
+    nonce: u64,
+    rarity_override: Option<HeroRarity>,
 ) -> Result<Hero> {
-    // Create seed from multiple entropy sources
-    let mut seed_data = Vec::new();
-    seed_data.extend_from_slice(&timestamp.to_le_bytes());
-    seed_data.extend_from_slice(&slot.to_le_bytes());
-    seed_data.extend_from_slice(user_pubkey.as_ref());
-    seed_data.extend_from_slice(&id.to_le_bytes());
-    seed_data.extend_from_slice(&global_hero_count.to_le_bytes());
-
-    // Use simple XOR hash for pseudo-random generation
-    let mut seed_bytes = [0u8; 32];
-    for (i, chunk) in seed_data.chunks(32).enumerate() {
-        for (j, &byte) in chunk.iter().enumerate() {
-            seed_bytes[j] ^= byte.wrapping_add((i as u8).wrapping_mul(17));
-        }
-    }
+    let mut rng = HeroRng::new(&[randomness, user_pubkey.as_ref(), &nonce.to_le_bytes()]);
 
-    // Roll for rarity (0-1000 for better precision)
-    let rarity_roll = u16::from_le_bytes([seed_bytes[0], seed_bytes[1]]) % 1001;
-    let rarity = HeroRarity::from_roll(rarity_roll);
+    // Roll for rarity (0-999, one draw per 0.1%)
+    let rarity_roll = rng.range_u32(0, 999) as u16;
+    let rarity = rarity_override.unwrap_or_else(|| HeroRarity::from_roll(rarity_roll));
 
     // Roll for skin template (1-9)
-    let skin_id = (u8::from_le_bytes([seed_bytes[2]]) % 9) + 1;
+    let skin_id = rng.range_u8(1, 9);
 
     // Get rarity-constrained stat ranges
     let (stat_min, stat_max) = rarity.stat_range();
@@ -39,35 +92,11 @@ pub fn generate_hero(
     let (range_min, range_max) = rarity.bomb_range_range();
 
     // Generate stats within rarity ranges
-    let power = generate_stat_in_range(
-        &seed_bytes[3..7],
-        stat_min,
-        stat_max,
-    );
-
-    let speed = generate_stat_in_range(
-        &seed_bytes[7..11],
-        stat_min,
-        stat_max,
-    );
-
-    let stamina = generate_stat_in_range(
-        &seed_bytes[11..15],
-        stat_min,
-        stat_max,
-    );
-
-    let bomb_number = generate_stat_in_range_u8(
-        &seed_bytes[15..17],
-        bomb_min,
-        bomb_max,
-    );
-
-    let bomb_range = generate_stat_in_range_u8(
-        &seed_bytes[17..19],
-        range_min,
-        range_max,
-    );
+    let power = rng.range_u32(stat_min, stat_max);
+    let speed = rng.range_u32(stat_min, stat_max);
+    let stamina = rng.range_u32(stat_min, stat_max);
+    let bomb_number = rng.range_u8(bomb_min, bomb_max);
+    let bomb_range = rng.range_u8(range_min, range_max);
 
     // HP scales with rarity (2x to 5x the base stats)
     let hp_multiplier = match rarity {
@@ -95,35 +124,115 @@ pub fn generate_hero(
         hp,
         max_hp,
         last_action_time: timestamp,
+        level: 1,
+        xp: 0,
     })
 }
 
-/// Generate a random u32 within a range [min, max] inclusive
-fn generate_stat_in_range(seed: &[u8], min: u32, max: u32) -> u32 {
-    if min == max {
-        return min;
-    }
-
-    let seed_value = u32::from_le_bytes([
-        seed[0],
-        seed[1],
-        seed.get(2).copied().unwrap_or(0),
-        seed.get(3).copied().unwrap_or(0),
+/// Roll a fusion attempt against `success_bps` (0-10000)
+/// Seeded only from the commitment (randomness and nonce), on its own stream so it
+/// is independent of the stats rolled for the result
+pub fn roll_fusion_success(
+    randomness: &[u8; 32],
+    user_pubkey: Pubkey,
+    nonce: u64,
+    success_bps: u16,
+) -> bool {
+    let mut rng = HeroRng::new(&[
+        b"fusion",
+        randomness,
+        user_pubkey.as_ref(),
+        &nonce.to_le_bytes(),
     ]);
+    rng.range_u32(0, 9_999) < success_bps as u32
+}
 
-    let range_size = max - min + 1;
-    min + (seed_value % range_size)
+/// NFT name of an exported hero (fits the 32-byte metadata limit)
+pub fn hero_nft_name(hero: &Hero) -> String {
+    format!("Bomber Hero {:?} #{}", hero.rarity, hero.skin_id)
 }
 
-/// Generate a random u8 within a range [min, max] inclusive
-fn generate_stat_in_range_u8(seed: &[u8], min: u8, max: u8) -> u8 {
-    if min == max {
-        return min;
+/// NFT URI of an exported hero: the base URI with skin, rarity and stats as query parameters
+pub fn hero_nft_uri(base_uri: &str, hero: &Hero) -> String {
+    format!(
+        "{}?skin={}&rarity={:?}&level={}&power={}&speed={}&stamina={}&hp={}&bombs={}&range={}",
+        base_uri,
+        hero.skin_id,
+        hero.rarity,
+        hero.level,
+        hero.power,
+        hero.speed,
+        hero.stamina,
+        hero.max_hp,
+        hero.bomb_number,
+        hero.bomb_range
+    )
+}
+
+/// Outcome of looking up a committed slot in the SlotHashes sysvar
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlotHashLookup {
+    /// Hash of the first recorded slot at or after the target
+    Found([u8; 32]),
+
+    /// Target slot has not been recorded yet
+    NotYetAvailable,
+
+    /// Target slot fell out of the sysvar window (carries the newest hash)
+    Expired([u8; 32]),
+}
+
+/// Find the hash for `target_slot` in raw SlotHashes sysvar data
+/// Layout: u64 entry count, then (u64 slot, [u8; 32] hash) entries, newest first.
+/// Skipped slots resolve to the next recorded slot after the target.
+pub fn lookup_slot_hash(data: &[u8], target_slot: u64) -> Result<SlotHashLookup> {
+    const ENTRY_LEN: usize = 8 + 32;
+
+    let count_bytes: [u8; 8] = data
+        .get(..8)
+        .and_then(|b| b.try_into().ok())
+        .ok_or(GameError::InvalidRandomnessAccount)?;
+    let count = u64::from_le_bytes(count_bytes) as usize;
+
+    let read_entry = |i: usize| -> Result<(u64, [u8; 32])> {
+        let start = 8 + i * ENTRY_LEN;
+        let entry = data
+            .get(start..start + ENTRY_LEN)
+            .ok_or(GameError::InvalidRandomnessAccount)?;
+        let slot = u64::from_le_bytes(entry[..8].try_into().unwrap());
+        let hash: [u8; 32] = entry[8..].try_into().unwrap();
+        Ok((slot, hash))
+    };
+
+    if count == 0 {
+        return Ok(SlotHashLookup::NotYetAvailable);
+    }
+
+    let (newest_slot, newest_hash) = read_entry(0)?;
+    if newest_slot < target_slot {
+        return Ok(SlotHashLookup::NotYetAvailable);
+    }
+
+    // Walk back from the newest entry until we pass the target
+    let mut candidate = newest_hash;
+    for i in 1..count {
+        let (slot, hash) = read_entry(i)?;
+        if slot < target_slot {
+            return Ok(SlotHashLookup::Found(candidate));
+        }
+        candidate = hash;
+        if slot == target_slot {
+            return Ok(SlotHashLookup::Found(candidate));
+        }
     }
 
-    let seed_value = u16::from_le_bytes([seed[0], seed[1]]);
-    let range_size = (max - min + 1) as u16;
-    min + (seed_value % range_size) as u8
+    // Every recorded slot is newer than the target - it has aged out
+    let (oldest_slot, _) = read_entry(count - 1)?;
+    if oldest_slot == target_slot {
+        Ok(SlotHashLookup::Found(candidate))
+    } else {
+        Ok(SlotHashLookup::Expired(newest_hash))
+    }
 }
 
 /// Calculate burn and treasury split based on percentage
@@ -135,12 +244,50 @@ pub fn calculate_burn_split(total: u64, burn_pct: u16) -> (u64, u64) {
     (burn_amount, treasury_amount)
 }
 
+/// Whether a house table (level 1 first) is usable: every grid fits the
+/// account's tile space and its restroom slots, and no grid shrinks on upgrade
+pub fn valid_house_levels(levels: &[HouseLevelConfig]) -> bool {
+    let fits = levels.iter().all(|level| {
+        level.grid_width > 0
+            && level.grid_height > 0
+            && level.grid_tiles() <= UserAccount::MAX_GRID_TILES
+            && level.max_restroom_slots as usize <= level.grid_tiles()
+            && level.upgrade_cooldown >= 0
+    });
+    let grows = levels.windows(2).all(|pair| {
+        pair[1].grid_width >= pair[0].grid_width && pair[1].grid_height >= pair[0].grid_height
+    });
+    fits && grows
+}
+
+/// Whether a hero NFT base URI is usable: non-empty, printable ASCII that fits
+/// the stored space, without its own query string or fragment
+pub fn valid_hero_nft_base_uri(uri: &str) -> bool {
+    (1..=MAX_HERO_NFT_BASE_URI_LEN).contains(&uri.len())
+        && uri.bytes().all(|byte| byte.is_ascii_graphic() && byte != b'?' && byte != b'#')
+}
+
+/// Split a listing price into (marketplace fee, seller proceeds)
+/// fee_bps is 0-10000 where 10000 = 100%
+pub fn calculate_marketplace_fee(price: u64, fee_bps: u16) -> (u64, u64) {
+    let fee = (price as u128 * fee_bps as u128 / 10_000) as u64;
+    (fee, price - fee)
+}
+
 /// Calculate referral bonus
 pub fn calculate_referral_bonus(amount: u64, referral_pct: u16) -> u64 {
     // referral_pct is 0-10000 where 10000 = 100%
     (amount as u128 * referral_pct as u128 / 10_000) as u64
 }
 
+/// Referral bonus for every tier of the chain (same rounding as a single tier)
+pub fn calculate_tier_bonuses(
+    amount: u64,
+    tier_fees: &[u16; MAX_REFERRAL_TIERS],
+) -> [u64; MAX_REFERRAL_TIERS] {
+    tier_fees.map(|fee| calculate_referral_bonus(amount, fee))
+}
+
 /// Calculate reward based on time elapsed and power (time-based mining)
 /// Returns gross reward before fees
 pub fn calculate_mining_reward(
@@ -148,24 +295,188 @@ pub fn calculate_mining_reward(
     player_power: u64,
     bombcoin_per_block: u64,
     rewards_precision: u64,
-) -> u64 {
-    // Convert to hours for more intuitive rates
-    let elapsed_hours = elapsed_seconds as f64 / 3600.0;
-
-    // Reward = Hours × Power × Rate
-    let reward = (elapsed_hours * player_power as f64 * bombcoin_per_block as f64) as u64;
+) -> Result<u64> {
+    // Reward = Hours × Power × Rate, multiplied out before converting seconds to hours
+    let reward = (elapsed_seconds as u128)
+        .checked_mul(player_power as u128)
+        .and_then(|r| r.checked_mul(bombcoin_per_block as u128))
+        .ok_or(GameError::ArithmeticOverflow)?
+        / 3600;
 
     // Apply precision multiplier if needed
-    if rewards_precision > 1 {
-        reward * rewards_precision
+    let reward = reward
+        .checked_mul(rewards_precision.max(1) as u128)
+        .ok_or(GameError::ArithmeticOverflow)?;
+
+    u64::try_from(reward).map_err(|_| error!(GameError::ArithmeticOverflow))
+}
+
+/// HP recovery multiplier of a grid tile (bps, 10000 = 1x)
+pub fn location_recovery_bps(is_restroom: bool) -> u64 {
+    if is_restroom {
+        RESTROOM_RECOVERY_BPS
     } else {
-        reward
+        BENCH_RECOVERY_BPS
+    }
+}
+
+/// Mining power of a set of heroes from their summed HMP (in hundredths)
+/// Summing before rounding down keeps the half points from bomb range
+pub fn mining_power(total_hmp: u64) -> u64 {
+    total_hmp / HMP_SCALE
+}
+
+/// Emission rate after `halvings` halvings (zero once fully halved away)
+pub fn halved_rate(initial_rate: u64, halvings: u64) -> u64 {
+    if halvings >= u64::BITS as u64 {
+        return 0;
     }
+    initial_rate >> halvings
+}
+
+/// Coins emitted between `from` and `to` (seconds), integrated piecewise so each
+/// part of the window is priced at the rate of the halving era it falls in.
+/// `emitted_before` is the supply already emitted (Supply schedule milestones).
+pub fn calculate_emission(
+    schedule: EmissionSchedule,
+    initial_rate: u64,
+    halving_interval: u64,
+    start_time: i64,
+    emitted_before: u64,
+    from: i64,
+    to: i64,
+) -> Result<u64> {
+    if to <= from {
+        return Ok(0);
+    }
+
+    // No halving configured: flat rate for the whole window
+    if halving_interval == 0 {
+        return ((to - from) as u64)
+            .checked_mul(initial_rate)
+            .ok_or(error!(GameError::ArithmeticOverflow));
+    }
+
+    let mut emitted: u64 = 0;
+
+    match schedule {
+        EmissionSchedule::Time => {
+            // Eras are fixed time windows since game start
+            let mut t = from.max(start_time);
+            while t < to {
+                let era = (t - start_time) as u64 / halving_interval;
+                let rate = halved_rate(initial_rate, era);
+                if rate == 0 {
+                    break;
+                }
+
+                let era_end = (era + 1)
+                    .checked_mul(halving_interval)
+                    .and_then(|offset| start_time.checked_add(offset as i64))
+                    .unwrap_or(i64::MAX);
+                let segment_end = to.min(era_end);
+
+                let segment = ((segment_end - t) as u64)
+                    .checked_mul(rate)
+                    .ok_or(GameError::ArithmeticOverflow)?;
+                emitted = emitted.checked_add(segment).ok_or(GameError::ArithmeticOverflow)?;
+                t = segment_end;
+            }
+        }
+        EmissionSchedule::Supply => {
+            // Eras are supply milestones; find when each milestone is crossed
+            let mut seconds_left = (to - from) as u64;
+            let mut supply = emitted_before;
+            while seconds_left > 0 {
+                let era = supply / halving_interval;
+                let rate = halved_rate(initial_rate, era);
+                if rate == 0 {
+                    break;
+                }
+
+                let to_milestone = (era + 1).saturating_mul(halving_interval) - supply;
+                let full_window = seconds_left.saturating_mul(rate);
+                if full_window <= to_milestone {
+                    emitted = emitted.checked_add(full_window).ok_or(GameError::ArithmeticOverflow)?;
+                    break;
+                }
+
+                // Emit exactly up to the milestone, then continue at the halved rate
+                let seconds_to_milestone = to_milestone.div_ceil(rate);
+                emitted = emitted.checked_add(to_milestone).ok_or(GameError::ArithmeticOverflow)?;
+                supply = supply.checked_add(to_milestone).ok_or(GameError::ArithmeticOverflow)?;
+                seconds_left -= seconds_to_milestone;
+            }
+        }
+    }
+
+    Ok(emitted)
+}
+
+/// Clamp an emission window so total emissions never exceed `max_supply`
+/// (the window that crosses the cap emits only the remainder)
+pub fn cap_emission(emitted: u64, total_emitted: u64, max_supply: u64) -> u64 {
+    emitted.min(max_supply.saturating_sub(total_emitted))
+}
+
+/// Accumulator increase when `emitted` coins are shared by `total_power` (MasterChef)
+/// Formula: delta = emitted * PRECISION / total_power (rounded down, never over-allocates)
+pub fn reward_per_power_delta(emitted: u64, total_power: u64, precision: u64) -> Result<u128> {
+    if total_power == 0 {
+        return Ok(0);
+    }
+
+    let delta = (emitted as u128)
+        .checked_mul(precision as u128)
+        .ok_or(GameError::ArithmeticOverflow)?
+        .checked_div(total_power as u128)
+        .ok_or(GameError::DivisionByZero)?;
+
+    Ok(delta)
+}
+
+/// Share `pool` out over `total_staked` tokens
+/// Returns (accumulator increase, amount distributed); the rounding remainder
+/// is never allocated and stays in the pool for the next distribution
+pub fn dividend_distribution(pool: u64, total_staked: u64) -> Result<(u128, u64)> {
+    let delta = reward_per_power_delta(pool, total_staked, DIVIDEND_PRECISION)?;
+    let distributed = delta
+        .checked_mul(total_staked as u128)
+        .ok_or(GameError::ArithmeticOverflow)?
+        / DIVIDEND_PRECISION as u128;
+
+    Ok((delta, distributed as u64))
+}
+
+/// Rewards earned by `power` since its debt was recorded
+/// Formula: pending = (power * acc_per_power - reward_debt) / PRECISION
+pub fn calculate_pending_reward(
+    power: u64,
+    acc_per_power: u128,
+    reward_debt: u128,
+    precision: u64,
+) -> Result<u64> {
+    let earned_scaled = (power as u128)
+        .checked_mul(acc_per_power)
+        .ok_or(GameError::ArithmeticOverflow)?;
+
+    // Subtract before scaling down so rounding can never pay out an extra coin
+    let pending = earned_scaled
+        .saturating_sub(reward_debt)
+        .checked_div(precision as u128)
+        .ok_or(GameError::DivisionByZero)?;
+
+    u64::try_from(pending).map_err(|_| error!(GameError::ArithmeticOverflow))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::migration::*;
+    #[cfg(feature = "bench")]
+    use crate::zero_copy::*;
+    #[cfg(feature = "bench")]
+    use anchor_lang::__private::bytemuck;
 
     #[test]
     fn test_rarity_distribution() {
@@ -199,10 +510,27 @@ mod tests {
             hp: 100,
             max_hp: 100,
             last_action_time: 0,
+            level: 1,
+            xp: 0,
         };
 
         // HMP = (10 * 1) + (2 * 0.5) + (10 * 2) = 10 + 1 + 20 = 31
-        assert_eq!(hero.calculate_hmp(), 31.0);
+        assert_eq!(hero.calculate_hmp(), 31 * HMP_SCALE);
+
+        // Odd bomb range keeps its half point: 10 + 1.5 + 20 = 31.5
+        let odd_range = Hero { bomb_range: 3, ..hero.clone() };
+        assert_eq!(odd_range.calculate_hmp(), 3_150);
+
+        // Half points add up across heroes before power is rounded down
+        assert_eq!(mining_power(2 * odd_range.calculate_hmp()), 63);
+        assert_eq!(mining_power(odd_range.calculate_hmp()), 31);
+
+        // Products that overflowed u32 stay exact
+        let maxed = Hero { power: u32::MAX, bomb_number: u8::MAX, ..hero };
+        assert_eq!(
+            maxed.calculate_hmp(),
+            (u32::MAX as u64 * 255 + 1 + 20) * HMP_SCALE
+        );
     }
 
     #[test]
@@ -220,6 +548,8 @@ mod tests {
             hp: 300,
             max_hp: 300,
             last_action_time: 0,
+            level: 1,
+            xp: 0,
         };
 
         // Speed 5 = 5 HP per minute
@@ -247,19 +577,30 @@ mod tests {
             hp: 0,
             max_hp: 100,
             last_action_time: 0,
+            level: 1,
+            xp: 0,
         };
 
         // Bench (1.0x): 120 seconds = 1 tick, 10 stamina = 10 HP
-        let recovery_bench = hero.calculate_hp_recovery(120, 1.0);
+        let recovery_bench = hero.calculate_hp_recovery(120, BENCH_RECOVERY_BPS);
         assert_eq!(recovery_bench, 10);
 
         // Restroom (3.0x): 120 seconds = 1 tick, 10 stamina * 3 = 30 HP
-        let recovery_restroom = hero.calculate_hp_recovery(120, 3.0);
+        let recovery_restroom = hero.calculate_hp_recovery(120, RESTROOM_RECOVERY_BPS);
         assert_eq!(recovery_restroom, 30);
 
         // 240 seconds = 2 ticks, bench = 20 HP
-        let recovery_2_ticks = hero.calculate_hp_recovery(240, 1.0);
+        let recovery_2_ticks = hero.calculate_hp_recovery(240, location_recovery_bps(false));
         assert_eq!(recovery_2_ticks, 20);
+
+        // Partial ticks and partial HP round down
+        assert_eq!(hero.calculate_hp_recovery(119, RESTROOM_RECOVERY_BPS), 0);
+        let low_stamina = Hero { stamina: 3, ..hero.clone() };
+        assert_eq!(low_stamina.calculate_hp_recovery(120, 15_000), 4);
+
+        // Saturates instead of wrapping
+        let tireless = Hero { stamina: u32::MAX, ..hero };
+        assert_eq!(tireless.calculate_hp_recovery(u64::MAX, RESTROOM_RECOVERY_BPS), u32::MAX);
     }
 
     #[test]
@@ -292,15 +633,1071 @@ mod tests {
     #[test]
     fn test_mining_reward() {
         // 1 hour, 100 power, 10 coins/block rate, precision 1
-        let reward = calculate_mining_reward(3600, 100, 10, 1);
+        let reward = calculate_mining_reward(3600, 100, 10, 1).unwrap();
         assert_eq!(reward, 1000); // 1 hour * 100 power * 10 rate = 1000
 
         // 2 hours
-        let reward_2h = calculate_mining_reward(7200, 100, 10, 1);
+        let reward_2h = calculate_mining_reward(7200, 100, 10, 1).unwrap();
         assert_eq!(reward_2h, 2000);
 
         // Half hour (1800 seconds)
-        let reward_half = calculate_mining_reward(1800, 100, 10, 1);
+        let reward_half = calculate_mining_reward(1800, 100, 10, 1).unwrap();
         assert_eq!(reward_half, 500);
+
+        // Fractions of a coin round down before the precision multiplier
+        assert_eq!(calculate_mining_reward(1, 100, 10, 1).unwrap(), 0);
+        assert_eq!(calculate_mining_reward(37, 100, 10, 1_000).unwrap(), 10_000);
+        assert_eq!(calculate_mining_reward(3600, 100, 10, 0).unwrap(), 1000);
+
+        // Overflow is an error, not a wrap
+        assert!(calculate_mining_reward(u64::MAX, u64::MAX, u64::MAX, 1).is_err());
+        assert!(calculate_mining_reward(3600, u64::MAX, 1, 2).is_err());
+    }
+
+    #[test]
+    fn test_fixed_point_matches_float_formulas() {
+        // The integer versions reproduce the former f64 results, scaled
+        for seed in 0..500u64 {
+            let hero = generate_hero(0, 0, &[7u8; 32], Pubkey::default(), seed, None).unwrap();
+
+            let hmp = (hero.power * hero.bomb_number as u32) as f64
+                + hero.bomb_range as f64 * 0.5
+                + hero.speed as f64 * 2.0;
+            assert_eq!(hero.calculate_hmp() as f64, hmp * HMP_SCALE as f64);
+
+            for (elapsed, is_restroom) in [(119, false), (600, false), (3_600, true), (86_400, true)] {
+                let multiplier = if is_restroom { 3.0 } else { 1.0 };
+                let recovery = ((elapsed / 120 * hero.stamina as u64) as f64 * multiplier) as u32;
+                assert_eq!(
+                    hero.calculate_hp_recovery(elapsed, location_recovery_bps(is_restroom)),
+                    recovery
+                );
+            }
+        }
+
+        for (elapsed, power, rate) in [(3_600u64, 100u64, 10u64), (5_400, 31, 7), (86_400, 1_234, 1_000)] {
+            let reward = (elapsed as f64 / 3600.0 * power as f64 * rate as f64) as u64;
+            assert_eq!(calculate_mining_reward(elapsed, power, rate, 1).unwrap(), reward);
+        }
+    }
+
+    /// Build raw SlotHashes sysvar data (newest first), hash byte = slot as u8
+    fn slot_hashes_data(slots: &[u64]) -> Vec<u8> {
+        let mut data = (slots.len() as u64).to_le_bytes().to_vec();
+        for &slot in slots {
+            data.extend_from_slice(&slot.to_le_bytes());
+            data.extend_from_slice(&[slot as u8; 32]);
+        }
+        data
+    }
+
+    #[test]
+    fn test_slot_hash_lookup() {
+        let data = slot_hashes_data(&[105, 104, 102, 101, 100]);
+
+        // Exact slot
+        assert_eq!(lookup_slot_hash(&data, 104).unwrap(), SlotHashLookup::Found([104; 32]));
+        assert_eq!(lookup_slot_hash(&data, 100).unwrap(), SlotHashLookup::Found([100; 32]));
+
+        // Skipped slot 103 resolves to the next recorded slot
+        assert_eq!(lookup_slot_hash(&data, 103).unwrap(), SlotHashLookup::Found([104; 32]));
+
+        // Not produced yet
+        assert_eq!(lookup_slot_hash(&data, 106).unwrap(), SlotHashLookup::NotYetAvailable);
+
+        // Aged out of the window, falls back to the newest hash
+        assert_eq!(lookup_slot_hash(&data, 99).unwrap(), SlotHashLookup::Expired([105; 32]));
+    }
+
+    #[test]
+    fn test_slot_hash_lookup_malformed() {
+        assert!(lookup_slot_hash(&[], 1).is_err());
+        assert_eq!(
+            lookup_slot_hash(&slot_hashes_data(&[]), 1).unwrap(),
+            SlotHashLookup::NotYetAvailable
+        );
+
+        // Count claims more entries than the data holds
+        let mut data = slot_hashes_data(&[10]);
+        data[..8].copy_from_slice(&3u64.to_le_bytes());
+        assert!(lookup_slot_hash(&data, 5).is_err());
+    }
+
+    /// Mint heroes offline with a fixed commitment, varying only the hero count
+    fn mint_offline(count: u64) -> Vec<Hero> {
+        let randomness = [42u8; 32];
+        let owner = Pubkey::new_from_array([7u8; 32]);
+        (0..count)
+            .map(|i| generate_hero(0, 0, &randomness, owner, i, None).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_rarity_table_statistics() {
+        const MINTS: u64 = 100_000;
+        let heroes = mint_offline(MINTS);
+
+        let mut counts = [0u64; 6];
+        for hero in &heroes {
+            counts[hero.rarity as usize] += 1;
+
+            // Every stat stays inside its rarity range
+            let (stat_min, stat_max) = hero.rarity.stat_range();
+            let (bomb_min, bomb_max) = hero.rarity.bomb_count_range();
+            let (range_min, range_max) = hero.rarity.bomb_range_range();
+            assert!((stat_min..=stat_max).contains(&hero.power));
+            assert!((stat_min..=stat_max).contains(&hero.speed));
+            assert!((stat_min..=stat_max).contains(&hero.stamina));
+            assert!((bomb_min..=bomb_max).contains(&hero.bomb_number));
+            assert!((range_min..=range_max).contains(&hero.bomb_range));
+            assert!((1..=9).contains(&hero.skin_id));
+        }
+
+        // Share of each tier in HeroRarity::from_roll, per mille
+        let expected_per_mille = [500, 300, 150, 40, 9, 1];
+
+        // Within 5 standard deviations of the binomial mean
+        for (tier, (&count, &per_mille)) in counts.iter().zip(expected_per_mille.iter()).enumerate() {
+            let p = per_mille as f64 / 1000.0;
+            let mean = MINTS as f64 * p;
+            let std_dev = (mean * (1.0 - p)).sqrt();
+            assert!(
+                (count as f64 - mean).abs() <= 5.0 * std_dev,
+                "tier {} minted {} times, expected ~{}",
+                tier,
+                count,
+                mean
+            );
+        }
+    }
+
+    #[test]
+    fn test_hero_stats_uncorrelated() {
+        let heroes: Vec<Hero> = mint_offline(20_000)
+            .into_iter()
+            .filter(|h| h.rarity == HeroRarity::Common)
+            .collect();
+
+        let correlation = |a: &dyn Fn(&Hero) -> u32, b: &dyn Fn(&Hero) -> u32| -> f64 {
+            let n = heroes.len() as f64;
+            let mean_a = heroes.iter().map(|h| a(h) as f64).sum::<f64>() / n;
+            let mean_b = heroes.iter().map(|h| b(h) as f64).sum::<f64>() / n;
+            let mut cov = 0.0;
+            let mut var_a = 0.0;
+            let mut var_b = 0.0;
+            for h in &heroes {
+                let da = a(h) as f64 - mean_a;
+                let db = b(h) as f64 - mean_b;
+                cov += da * db;
+                var_a += da * da;
+                var_b += db * db;
+            }
+            cov / (var_a * var_b).sqrt()
+        };
+
+        assert!(correlation(&|h| h.power, &|h| h.speed).abs() < 0.05);
+        assert!(correlation(&|h| h.power, &|h| h.stamina).abs() < 0.05);
+        assert!(correlation(&|h| h.speed, &|h| h.stamina).abs() < 0.05);
+        assert!(correlation(&|h| h.skin_id as u32, &|h| h.power).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_rng_range_is_uniform() {
+        let mut rng = HeroRng::new(&[b"uniformity"]);
+
+        // Span of 3 does not divide 2^32, so a plain modulo would be biased
+        const DRAWS: usize = 30_000;
+        let mut counts = [0usize; 3];
+        for _ in 0..DRAWS {
+            let value = rng.range_u32(10, 12);
+            assert!((10..=12).contains(&value));
+            counts[(value - 10) as usize] += 1;
+        }
+
+        // Chi-square with 2 degrees of freedom, 13.8 is the p = 0.001 cutoff
+        let expected = DRAWS as f64 / 3.0;
+        let chi_square: f64 = counts
+            .iter()
+            .map(|&c| (c as f64 - expected).powi(2) / expected)
+            .sum();
+        assert!(chi_square < 13.8, "chi-square {} too high", chi_square);
+
+        // Degenerate ranges
+        assert_eq!(rng.range_u32(5, 5), 5);
+        assert_eq!(rng.range_u8(1, 1), 1);
+    }
+
+    #[test]
+    fn test_generate_hero_deterministic() {
+        let owner = Pubkey::new_from_array([1u8; 32]);
+        let a = generate_hero(3, 100, &[9u8; 32], owner, 11, None).unwrap();
+        let b = generate_hero(8, 200, &[9u8; 32], owner, 11, None).unwrap();
+
+        // Same commitment rolls the same hero, regardless of reveal time or the ID it gets
+        assert_eq!(a.rarity, b.rarity);
+        assert_eq!((a.power, a.speed, a.stamina), (b.power, b.speed, b.stamina));
+        assert_eq!((b.id, b.last_action_time), (8, 200));
+
+        // Each nonce of a batch rolls its own hero
+        let c = generate_hero(3, 100, &[9u8; 32], owner, 12, None).unwrap();
+        assert_ne!((a.power, a.speed, a.stamina), (c.power, c.speed, c.stamina));
+
+        // Override forces the tier but still rolls stats inside it
+        let forced = generate_hero(3, 100, &[9u8; 32], owner, 11, Some(HeroRarity::Legendary)).unwrap();
+        assert_eq!(forced.rarity, HeroRarity::Legendary);
+        let (min, max) = HeroRarity::Legendary.stat_range();
+        assert!((min..=max).contains(&forced.power));
+    }
+
+    /// SplitMix64 - tiny deterministic generator for simulation tests
+    struct SplitMix(u64);
+
+    impl SplitMix {
+        fn next(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        }
+    }
+
+    /// Miner position as tracked by UserAccount (power, reward_debt)
+    struct Miner {
+        power: u64,
+        reward_debt: u128,
+    }
+
+    /// Replays the program's update_pool → harvest → change power → reset debt
+    /// sequence with random users, powers and time gaps.
+    /// Returns (total_emitted, total_paid)
+    fn simulate_pool(seed: u64, precision: u64, steps: usize) -> (u64, u64) {
+        const RATE: u64 = 1_000;
+        let mut rng = SplitMix(seed);
+        let mut miners: Vec<Miner> = (0..8).map(|_| Miner { power: 0, reward_debt: 0 }).collect();
+        let mut acc: u128 = 0;
+        let mut total_power: u64 = 0;
+        let mut emitted: u64 = 0;
+        let mut paid: u64 = 0;
+
+        let update_pool = |acc: &mut u128, total_power: u64, elapsed: u64, emitted: &mut u64| {
+            if total_power > 0 {
+                let window = elapsed * RATE;
+                *acc += reward_per_power_delta(window, total_power, precision).unwrap();
+                *emitted += window;
+            }
+        };
+
+        for _ in 0..steps {
+            let elapsed = rng.next() % 600;
+            update_pool(&mut acc, total_power, elapsed, &mut emitted);
+
+            // Harvest, then change this miner's power (join, leave, or rebalance)
+            let miner = &mut miners[(rng.next() % 8) as usize];
+            paid += calculate_pending_reward(miner.power, acc, miner.reward_debt, precision).unwrap();
+
+            let new_power = if rng.next() % 4 == 0 { 0 } else { rng.next() % 5_000 };
+            total_power = total_power - miner.power + new_power;
+            miner.power = new_power;
+            miner.reward_debt = new_power as u128 * acc;
+        }
+
+        // Final update and everyone harvests
+        update_pool(&mut acc, total_power, 3_600, &mut emitted);
+        for miner in &miners {
+            paid += calculate_pending_reward(miner.power, acc, miner.reward_debt, precision).unwrap();
+        }
+
+        (emitted, paid)
+    }
+
+    #[test]
+    fn test_masterchef_never_pays_more_than_emitted() {
+        for seed in 0..200 {
+            for &precision in &[1u64, 1_000_000, 1_000_000_000_000] {
+                let (emitted, paid) = simulate_pool(seed, precision, 300);
+                assert!(
+                    paid <= emitted,
+                    "seed {} precision {}: paid {} > emitted {}",
+                    seed,
+                    precision,
+                    paid,
+                    emitted
+                );
+
+                // With high precision only per-harvest rounding dust is lost
+                if precision == 1_000_000_000_000 {
+                    assert!(emitted - paid <= 2 * 300 + 8, "seed {}: lost {}", seed, emitted - paid);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_late_joiner_cannot_take_past_rewards() {
+        const PRECISION: u64 = 1_000_000_000_000;
+
+        // Alice mines alone for 1000s at 1000 coins/s
+        let alice_power = 100;
+        let mut acc = reward_per_power_delta(1_000 * 1_000, alice_power, PRECISION).unwrap();
+
+        // Bob joins with 9x her power; his debt starts at the current accumulator
+        let bob_power = 900;
+        let bob_debt = bob_power as u128 * acc;
+        assert_eq!(calculate_pending_reward(bob_power, acc, bob_debt, PRECISION).unwrap(), 0);
+
+        // 1000s more, now shared 10% / 90%
+        acc += reward_per_power_delta(1_000 * 1_000, alice_power + bob_power, PRECISION).unwrap();
+
+        let alice = calculate_pending_reward(alice_power, acc, 0, PRECISION).unwrap();
+        let bob = calculate_pending_reward(bob_power, acc, bob_debt, PRECISION).unwrap();
+        assert_eq!(alice, 1_000_000 + 100_000);
+        assert_eq!(bob, 900_000);
+    }
+
+    #[test]
+    fn test_pending_reward_rounding() {
+        // Subtracting before scaling down never rounds up
+        assert_eq!(calculate_pending_reward(1, 19, 9, 10).unwrap(), 1);
+        assert_eq!(calculate_pending_reward(1, 10, 9, 10).unwrap(), 0);
+
+        // Debt above earnings (power dropped) yields nothing
+        assert_eq!(calculate_pending_reward(1, 10, 50, 10).unwrap(), 0);
+
+        // No power shares nothing
+        assert_eq!(reward_per_power_delta(1_000, 0, 10).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_time_emission_splits_at_halving() {
+        // 100/s, halving every 1000s from t=0
+        let emit = |from, to| calculate_emission(EmissionSchedule::Time, 100, 1_000, 0, 0, from, to).unwrap();
+
+        assert_eq!(emit(0, 1_000), 100_000);
+        // Window straddling the first halving: 500s at 100 + 500s at 50
+        assert_eq!(emit(500, 1_500), 50_000 + 25_000);
+        // Splitting a window never changes the total
+        assert_eq!(emit(0, 3_500), emit(0, 1_234) + emit(1_234, 2_999) + emit(2_999, 3_500));
+        // Before start nothing is emitted
+        assert_eq!(calculate_emission(EmissionSchedule::Time, 100, 1_000, 500, 0, 0, 500).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_supply_emission_splits_at_milestone() {
+        // 100/s, halving every 10_000 coins emitted
+        let emit = |before, from, to| calculate_emission(EmissionSchedule::Supply, 100, 10_000, 0, before, from, to).unwrap();
+
+        assert_eq!(emit(0, 0, 100), 10_000);
+        // 50s reaches the milestone at 5_000 supply, the other 50s run at 50/s
+        assert_eq!(emit(5_000, 0, 100), 5_000 + 2_500);
+
+        // Sequential windows agree with a single long window
+        let first = emit(0, 0, 150);
+        let second = emit(first, 150, 400);
+        assert_eq!(first + second, emit(0, 0, 400));
+    }
+
+    #[test]
+    fn test_emission_stops_when_fully_halved() {
+        assert_eq!(halved_rate(100, 7), 0);
+        assert_eq!(halved_rate(u64::MAX, 64), 0);
+        assert_eq!(calculate_emission(EmissionSchedule::Time, 100, 10, 0, 0, 1_000, 2_000).unwrap(), 0);
+
+        // Each supply era emits exactly one interval until the rate reaches zero (100 >> 7)
+        let total = calculate_emission(EmissionSchedule::Supply, 100, 10_000, 0, 0, 0, 1_000_000).unwrap();
+        assert_eq!(total, 7 * 10_000);
+    }
+
+    #[test]
+    fn test_emission_clamped_at_max_supply() {
+        // Plenty of room: emission unchanged
+        assert_eq!(cap_emission(1_000, 0, 10_000), 1_000);
+        // Window crossing the cap only emits the remainder
+        assert_eq!(cap_emission(1_000, 9_400, 10_000), 600);
+        // Cap reached (or lowered below emitted): nothing more
+        assert_eq!(cap_emission(1_000, 10_000, 10_000), 0);
+        assert_eq!(cap_emission(1_000, 12_000, 10_000), 0);
+    }
+
+    #[test]
+    fn test_tier_bonuses() {
+        // 2.5% / 1% / 0.5%
+        let bonuses = calculate_tier_bonuses(1_000_000, &[250, 100, 50]);
+        assert_eq!(bonuses, [25_000, 10_000, 5_000]);
+
+        // Each tier rounds down independently, so the total never exceeds the combined fee
+        let bonuses = calculate_tier_bonuses(999, &[250, 100, 50]);
+        assert_eq!(bonuses, [24, 9, 4]);
+        assert!(bonuses.iter().sum::<u64>() <= 999 * 400 / 10_000);
+    }
+
+    #[test]
+    fn test_mining_xp_stops_when_exhausted() {
+        let mut hero = generate_hero(0, 0, &[1u8; 32], Pubkey::default(), 0, None).unwrap();
+        hero.speed = 10;
+        hero.hp = 100;
+
+        // 5 minutes at speed 10: 50 HP drained, 5 XP
+        hero.apply_mining(5 * 60);
+        assert_eq!((hero.hp, hero.xp), (50, 5));
+
+        // An hour would drain 600 HP, but only 5 more minutes are mined
+        hero.apply_mining(3_600);
+        assert_eq!((hero.hp, hero.xp), (0, 10));
+
+        // Sleeping heroes earn nothing
+        hero.apply_mining(3_600);
+        assert_eq!(hero.xp, 10);
+    }
+
+    #[test]
+    fn test_level_up_stats_capped_by_rarity() {
+        assert_eq!(Hero::xp_for_level(1), 0);
+        assert_eq!(Hero::xp_for_level(2), 60);
+        assert_eq!(Hero::xp_for_level(3), 180);
+
+        let mut hero = generate_hero(0, 0, &[2u8; 32], Pubkey::default(), 0, Some(HeroRarity::Common)).unwrap();
+        let (_, stat_max) = HeroRarity::Common.stat_range();
+        hero.power = stat_max - 1;
+        hero.speed = stat_max;
+        hero.stamina = 10;
+
+        assert!(hero.raise_stats());
+        assert_eq!((hero.power, hero.speed, hero.stamina), (stat_max, stat_max, 13));
+
+        hero.stamina = stat_max;
+        assert!(!hero.raise_stats());
+
+        // Cost scales with level and rarity
+        hero.level = 2;
+        assert_eq!(hero.level_up_cost(), 2 * LEVEL_UP_BASE_COST);
+        hero.rarity = HeroRarity::Legendary;
+        assert_eq!(hero.level_up_cost(), 12 * LEVEL_UP_BASE_COST);
+    }
+
+    #[test]
+    fn test_fusion_success_rate() {
+        let owner = Pubkey::new_from_array([9u8; 32]);
+        let randomness = [5u8; 32];
+
+        // Edge chances are exact
+        assert!(!roll_fusion_success(&randomness, owner, 0, 0));
+        assert!(roll_fusion_success(&randomness, owner, 0, 10_000));
+
+        // 30% chance lands within 5σ over 20k attempts
+        const TRIALS: u64 = 20_000;
+        let successes = (0..TRIALS)
+            .filter(|&i| roll_fusion_success(&randomness, owner, i, 3_000))
+            .count() as f64;
+        let expected = TRIALS as f64 * 0.3;
+        let sigma = (TRIALS as f64 * 0.3 * 0.7).sqrt();
+        assert!((successes - expected).abs() < 5.0 * sigma, "{} successes", successes);
+    }
+
+    fn test_user_account() -> UserAccount {
+        UserAccount {
+            owner: Pubkey::default(),
+            version: USER_ACCOUNT_VERSION,
+            initialized_starter_house: true,
+            house_level: 1,
+            last_house_upgrade_timestamp: 0,
+            grid_width: 4,
+            grid_height: 4,
+            house_occupied_coords: vec![
+                HouseTile { x: 0, y: 0, hero_id: 1, is_restroom: false },
+                HouseTile { x: 1, y: 0, hero_id: 4, is_restroom: true },
+            ],
+            inventory: vec![],
+            active_map: vec![0, 2, 3],
+            pending_mint: None,
+            next_hero_id: 5,
+            hero_count: 5,
+            coin_balance: 0,
+            player_power: 0,
+            player_pending_rewards: 0,
+            last_reward_block: 0,
+            reward_debt: 0,
+            referrer: None,
+            referral_bonus_paid: 0,
+            referral_earnings_by_tier: [0; MAX_REFERRAL_TIERS],
+            referrals: vec![],
+            bump: 0,
+        }
+    }
+
+    fn test_hero(id: u16) -> Hero {
+        generate_hero(id, 0, &[3u8; 32], Pubkey::default(), id as u64, None).unwrap()
+    }
+
+    #[test]
+    fn test_release_heroes_keeps_stable_ids() {
+        let mut user = test_user_account();
+
+        // Release one mining hero and one grid hero
+        user.release_heroes(&[2, 1]);
+
+        // Remaining references are untouched
+        assert_eq!(user.active_map, vec![0, 3]);
+        assert_eq!(user.house_occupied_coords.len(), 1);
+        assert_eq!(user.house_occupied_coords[0].hero_id, 4);
+        assert_eq!(user.hero_count, 3);
+
+        // IDs are never reused after a removal
+        assert_eq!(user.allocate_hero_id(), Some(5));
+        user.next_hero_id = u16::MAX;
+        assert_eq!(user.allocate_hero_id(), None);
+    }
+
+    #[test]
+    fn test_hero_page_slots_follow_ids() {
+        let mut page = HeroPage {
+            owner: Pubkey::default(),
+            index: 1,
+            heroes: Default::default(),
+            bump: 0,
+        };
+
+        // IDs 32..=63 live on page 1, in slot id % 32
+        assert_eq!(HeroPage::index_of(31), 0);
+        assert_eq!(HeroPage::index_of(32), 1);
+        assert_eq!(HeroPage::index_of(u16::MAX - 1), HeroPage::MAX_INDEX);
+        assert!(page.insert(test_hero(33)));
+        assert!(page.heroes[1].is_some());
+        assert!(!page.insert(test_hero(33)), "slot already taken");
+        assert!(!page.insert(test_hero(64)), "hero belongs to page 2");
+        assert!(page.hero(1).is_none(), "slot 1 of page 0 is a different hero");
+
+        page.hero_mut(33).unwrap().level = 2;
+        assert_eq!(page.hero(33).unwrap().level, 2);
+        assert_eq!(page.hero_count(), 1);
+        assert_eq!(page.remove(33).unwrap().id, 33);
+        assert_eq!(page.hero_count(), 0);
+
+        // A full page serializes into exactly the reserved space
+        let first_id = HEROES_PER_PAGE as u16;
+        for id in first_id..first_id + HEROES_PER_PAGE as u16 {
+            assert!(page.insert(test_hero(id)));
+        }
+        assert_eq!(8 + page.try_to_vec().unwrap().len(), HeroPage::LEN);
+    }
+
+    #[test]
+    fn test_hero_nft_metadata_fits_limits() {
+        let mut hero = generate_hero(7, 0, &[9u8; 32], Pubkey::default(), 0, Some(HeroRarity::SuperRare)).unwrap();
+        hero.level = MAX_HERO_LEVEL;
+        hero.power = u32::MAX;
+        hero.speed = u32::MAX;
+        hero.stamina = u32::MAX;
+        hero.max_hp = u32::MAX;
+
+        // Longest base URI the admin can set
+        let base_uri = format!("https://arweave.net/{}", "x".repeat(MAX_HERO_NFT_BASE_URI_LEN - 20));
+        assert!(valid_hero_nft_base_uri(&base_uri));
+        assert!(hero_nft_name(&hero).len() <= 32);
+        assert!(hero_nft_uri(&base_uri, &hero).len() <= 200);
+        assert!(hero_nft_uri(&base_uri, &hero).starts_with(&format!("{}?skin=", base_uri)));
+        assert!(hero_nft_uri(&base_uri, &hero).contains("rarity=SuperRare"));
+
+        // Stored zero-padded in the global state
+        let mut state = test_global_state_v1().into_current(0, 0);
+        assert_eq!(state.nft_base_uri(), "");
+        state.hero_nft_base_uri[..base_uri.len()].copy_from_slice(base_uri.as_bytes());
+        assert_eq!(state.nft_base_uri(), base_uri);
+
+        assert!(!valid_hero_nft_base_uri(""));
+        assert!(!valid_hero_nft_base_uri(&format!("{}x", base_uri)));
+        assert!(!valid_hero_nft_base_uri("https://arweave.net/abc?v=1"));
+        assert!(!valid_hero_nft_base_uri("https://arweave.net/a b"));
+
+        // Serialized hero matches the space reserved for it
+        assert_eq!(hero.try_to_vec().unwrap().len(), Hero::LEN);
+    }
+
+    #[test]
+    fn test_marketplace_fee_split() {
+        // 5% fee, then half of the fee burned
+        let (fee, proceeds) = calculate_marketplace_fee(1_000_000, 500);
+        assert_eq!((fee, proceeds), (50_000, 950_000));
+        assert_eq!(calculate_burn_split(fee, 5_000), (25_000, 25_000));
+
+        // Nothing is lost to rounding
+        let (fee, proceeds) = calculate_marketplace_fee(999, 333);
+        assert_eq!(fee + proceeds, 999);
+        assert_eq!(calculate_marketplace_fee(u64::MAX, 10_000), (u64::MAX, 0));
+    }
+
+    #[test]
+    fn test_house_level_validation() {
+        assert!(valid_house_levels(&DEFAULT_HOUSE_LEVELS));
+        let mut levels = DEFAULT_HOUSE_LEVELS;
+
+        // Larger grids and cheaper upgrades are fine
+        levels[1] = HouseLevelConfig::new(5, 6, 20, 0, 60);
+        assert!(valid_house_levels(&levels));
+
+        // A grid may not shrink on upgrade, in either direction
+        levels[1] = HouseLevelConfig::new(3, 6, 6, 1_000, 7_200);
+        assert!(!valid_house_levels(&levels));
+        levels[1] = HouseLevelConfig::new(5, 7, 6, 1_000, 7_200);
+        assert!(!valid_house_levels(&levels), "taller than level 3");
+
+        // Grids must fit the account, restrooms the grid
+        let mut levels = DEFAULT_HOUSE_LEVELS;
+        levels[5] = HouseLevelConfig::new(7, 8, 15, 0, 0);
+        assert!(!valid_house_levels(&levels));
+        levels[5] = HouseLevelConfig::new(7, 7, 50, 0, 0);
+        assert!(!valid_house_levels(&levels));
+        levels[0] = HouseLevelConfig::new(0, 4, 0, 500, 3_600);
+        assert!(!valid_house_levels(&levels));
+
+        let mut levels = DEFAULT_HOUSE_LEVELS;
+        levels[2].upgrade_cooldown = -1;
+        assert!(!valid_house_levels(&levels));
+    }
+
+    #[test]
+    fn test_required_len_covers_serialized_account() {
+        let mut user = test_user_account();
+        user.referrals = vec![Pubkey::default(); 3];
+        let serialized = 8 + user.try_to_vec().unwrap().len();
+        assert!(user.required_len() >= serialized);
+        // Only the reserved pending-mint and referrer slots are unused
+        assert_eq!(user.required_len() - serialized, PendingMint::LEN + 32);
+
+        // Heroes a pending mint will reveal go to hero pages, but count against the cap
+        let before = user.required_len();
+        user.pending_mint = Some(PendingMint {
+            quantity: 4,
+            target_slot: 0,
+            use_feed: false,
+            feed_sequence: 0,
+            fuse_into: None,
+            fusion_success_bps: 0,
+            nonce: 0,
+        });
+        assert_eq!(user.required_len(), before);
+        assert_eq!(user.remaining_hero_slots(), UserAccount::MAX_HEROES - 5 - 4);
+        assert!(!user.has_inventory_space(UserAccount::MAX_HEROES - 8));
+
+        // Unmigrated heroes still take space in the account and count against the cap
+        user.inventory = (0..2).map(test_hero).collect();
+        assert_eq!(user.required_len(), before + 2 * Hero::LEN);
+        assert_eq!(user.remaining_hero_slots(), UserAccount::MAX_HEROES - 5 - 4 - 2);
+    }
+
+    fn test_global_state_v1() -> GlobalStateV1 {
+        GlobalStateV1 {
+            authority: Pubkey::new_unique(),
+            dev_treasury: Pubkey::new_unique(),
+            reward_token_mint: Pubkey::new_unique(),
+            game_has_started: true,
+            paused: false,
+            minting_enabled: true,
+            house_upgrades_enabled: true,
+            start_block: 1_000,
+            house_count: 7,
+            unique_heroes_count: 42,
+            total_hash_power: 500,
+            cumulative_bombcoin_per_power: 123_456_789,
+            initial_house_price: 250_000_000,
+            initial_bombcoin_per_block: 1_000,
+            halving_interval: 3_600,
+            burn_pct: 5_000,
+            referral_fee: 250,
+            rewards_precision: 1_000_000,
+            total_mined: 9_000,
+            total_burned: 300,
+            reward_pool: 700,
+            bump: 254,
+        }
+    }
+
+    #[test]
+    fn test_dividend_accounting() {
+        let mut state = test_global_state_v1().into_current(2_000, 0);
+        state.reward_pool = 0;
+        let mut pool = StakePool {
+            total_staked: 0,
+            acc_dividend_per_share: 0,
+            total_distributed: 0,
+            total_claimed: 0,
+            bump: 0,
+        };
+        let staker = |amount: u64| StakeAccount {
+            owner: Pubkey::new_unique(),
+            amount,
+            reward_debt: 0,
+            pending_dividends: 0,
+            bump: 0,
+        };
+        let (mut alice, mut bob) = (staker(0), staker(0));
+
+        // Nothing staked: the pool waits
+        state.reward_pool = 1_000;
+        crate::distribute_dividends(&mut state, &mut pool).unwrap();
+        assert_eq!((state.reward_pool, pool.acc_dividend_per_share), (1_000, 0));
+
+        // Alice stakes 100 and is alone when the pool is shared
+        alice.amount = 100;
+        pool.total_staked = 100;
+        crate::distribute_dividends(&mut state, &mut pool).unwrap();
+        crate::harvest_dividends(&pool, &mut alice).unwrap();
+        assert_eq!((alice.pending_dividends, state.reward_pool), (1_000, 0));
+
+        // Bob joins with 300 after that distribution and earns nothing from it
+        crate::harvest_dividends(&pool, &mut bob).unwrap();
+        bob.amount = 300;
+        pool.total_staked += 300;
+        crate::sync_stake_debt(&pool, &mut bob).unwrap();
+
+        // The next 1000 split 1:3
+        state.reward_pool = 1_000;
+        crate::distribute_dividends(&mut state, &mut pool).unwrap();
+        crate::harvest_dividends(&pool, &mut alice).unwrap();
+        crate::harvest_dividends(&pool, &mut bob).unwrap();
+        assert_eq!((alice.pending_dividends, bob.pending_dividends), (1_250, 750));
+        assert_eq!(state.reward_pool, 0);
+
+        // Harvesting twice pays nothing extra
+        assert_eq!(crate::harvest_dividends(&pool, &mut alice).unwrap(), 0);
+
+        // Alice leaves; 7 over Bob's 300 rounds down and the remainder stays pooled
+        pool.total_staked -= alice.amount;
+        alice.amount = 0;
+        crate::sync_stake_debt(&pool, &mut alice).unwrap();
+        state.reward_pool = 7;
+        crate::distribute_dividends(&mut state, &mut pool).unwrap();
+        crate::harvest_dividends(&pool, &mut alice).unwrap();
+        crate::harvest_dividends(&pool, &mut bob).unwrap();
+        assert_eq!((alice.pending_dividends, bob.pending_dividends), (1_250, 756));
+        assert_eq!(state.reward_pool, 1);
+
+        // Every coin that left the pool is owed to exactly one staker
+        assert_eq!(pool.total_distributed, 2_006);
+        assert_eq!(alice.pending_dividends + bob.pending_dividends, pool.total_distributed);
+        assert_eq!(pool.total_distributed + state.reward_pool, 1_000 + 1_000 + 7);
+
+        assert_eq!(dividend_distribution(5, 0).unwrap(), (0, 0));
+        assert_eq!(dividend_distribution(0, 10).unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn test_token_supply_accounting() {
+        let mut state = test_global_state_v1().into_current(2_000, 1_000);
+        let coins_burned = state.total_burned;
+        assert_eq!(state.expected_token_supply(), 1_000);
+
+        // Claims and withdrawals mint, token purchases burn their share, deposits burn it all
+        state.record_token_mint(500).unwrap();
+        state.record_token_burn(120).unwrap();
+        state.total_tokens_deposited += 80;
+        assert_eq!(state.expected_token_supply(), 1_000 + 500 - 120 - 80);
+
+        // total_burned keeps covering both currencies
+        assert_eq!(state.total_burned, coins_burned + 120);
+        assert_eq!(state.total_burned - state.total_tokens_burned, coins_burned);
+
+        state.total_tokens_minted = u64::MAX;
+        assert!(state.record_token_mint(1).is_err());
+
+        // Admin credits share the supply budget with claims
+        state.max_supply = 10_000;
+        state.total_mined = 6_000;
+        state.total_coins_credited = 1_500;
+        assert_eq!(state.remaining_supply(), 2_500);
+        state.total_coins_credited = 5_000;
+        assert_eq!(state.remaining_supply(), 0);
+    }
+
+    #[test]
+    fn test_emission_capped_by_admin_credits() {
+        let mut state = test_global_state_v1().into_current(2_000, 0);
+        state.halving_interval = 1_000_000_000;
+        state.max_supply = state.total_mined + 50_000;
+        state.total_coins_credited = 20_000;
+
+        // Mining long enough to hit the cap only emits what the credits left over
+        crate::update_pool(&mut state, 1_000_000).unwrap();
+        assert_eq!(state.total_emitted - state.total_mined, 30_000);
+        assert_eq!(state.issued_supply().unwrap(), state.max_supply);
+
+        // Every emitted coin can still be claimed, and nothing more is emitted
+        assert!(state.total_emitted - state.total_mined <= state.remaining_supply());
+        crate::update_pool(&mut state, 2_000_000).unwrap();
+        assert_eq!(state.issued_supply().unwrap(), state.max_supply);
+    }
+
+    #[cfg(feature = "bench")]
+    #[test]
+    fn test_global_state_zc_shares_borsh_layout() {
+        let mut state = test_global_state_v1().into_current(2_000, 0);
+        state.emission_schedule = EmissionSchedule::Time;
+        state.upline_referral_fees = [100, 50];
+        state.house_levels[5].upgrade_cooldown = 90;
+        state.total_tokens_minted = 5_000;
+        state.total_tokens_deposited = 250;
+
+        // The snapshot takes the Borsh bytes of the live account as-is
+        assert_eq!(GlobalStateZc::LEN, GlobalState::LEN);
+        assert_ne!(GlobalStateZc::DISCRIMINATOR, GlobalState::DISCRIMINATOR);
+
+        let mut view: GlobalStateZc = bytemuck::Zeroable::zeroed();
+        view.copy_from(&state, state.bump).unwrap();
+        assert_eq!(view.authority, state.authority);
+        assert_eq!(view.reward_token_mint, state.reward_token_mint);
+        assert_eq!(view.version, GLOBAL_STATE_VERSION);
+        assert_eq!(view.game_has_started, 1);
+        assert!(!view.is_paused());
+        assert_eq!({ view.cumulative_bombcoin_per_power }, state.cumulative_bombcoin_per_power);
+        assert_eq!({ view.last_reward_time }, state.last_reward_time);
+        assert_eq!(view.emission_schedule(), EmissionSchedule::Time);
+        assert_eq!(view.referral_tier_fees(), state.referral_tier_fees());
+        assert_eq!({ view.max_supply }, state.max_supply);
+        assert_eq!({ view.hero_price }, state.hero_price);
+        assert_eq!({ view.house_levels[5].upgrade_cooldown }, 90);
+        assert_eq!({ view.reward_pool }, state.reward_pool);
+        assert_eq!({ view.total_tokens_minted }, 5_000);
+        assert_eq!({ view.total_tokens_deposited }, 250);
+        assert_eq!(view.bump, state.bump);
+
+        // Both layouts advance the accumulator identically
+        crate::update_pool(&mut state, 9_000).unwrap();
+        view.update_pool(9_000).unwrap();
+        assert_eq!(bytemuck::bytes_of(&view), state.try_to_vec().unwrap().as_slice());
+    }
+
+    #[cfg(feature = "bench")]
+    #[test]
+    fn test_zero_copy_hero_storage() {
+        let hero = test_hero(35);
+        let stored = HeroZc::from_hero(&hero);
+        assert_eq!(stored.to_hero().try_to_vec().unwrap(), hero.try_to_vec().unwrap());
+
+        let mut page: HeroPageZc = bytemuck::Zeroable::zeroed();
+        page.index = 1;
+        assert!(page.hero(35).is_none(), "zeroed slots are empty");
+        assert!(page.insert(&hero));
+        assert!(!page.insert(&hero), "slot already taken");
+        assert!(!page.insert(&test_hero(3)), "hero belongs to page 0");
+
+        page.update_hero(35, |hero| hero.level = 4).unwrap();
+        assert_eq!(page.hero(35).unwrap().level, 4);
+        assert!(page.update_hero(36, |hero| hero.level = 4).is_none());
+
+        // Packed tiles keep each hero at its coordinate, restrooms in the mask
+        let user = test_user_account();
+        let mut packed: UserAccountZc = bytemuck::Zeroable::zeroed();
+        packed.copy_from(&user, 1);
+        assert_eq!(
+            packed.occupied_tiles().collect::<Vec<_>>(),
+            vec![(0, 1, false), (UserAccountZc::tile_index(1, 0), 4, true)]
+        );
+        assert_eq!(packed.active_map().collect::<Vec<_>>(), user.active_map);
+
+        packed.remove_from_grid(4);
+        assert_eq!(packed.occupied_tiles().count(), 1);
+        while packed.push_to_map(9) {}
+        assert_eq!(packed.active_map().count(), UserAccount::MAX_MAP_HEROES);
+        assert!(packed.is_mining(9));
+    }
+
+    #[test]
+    fn test_migrate_global_state_v1_blob() {
+        let legacy = test_global_state_v1();
+        let (authority, treasury) = (legacy.authority, legacy.dev_treasury);
+        let mut blob = GlobalState::DISCRIMINATOR.to_vec();
+        legacy.serialize(&mut blob).unwrap();
+        assert_eq!(blob.len(), GlobalStateV1::LEN);
+        assert_eq!(global_state_version(&blob).unwrap(), 1);
+
+        let (from_version, data) = upgrade_global_state(&blob, 5_000, 4_000).unwrap();
+        assert_eq!(from_version, 1);
+        assert_eq!(data.len(), GlobalState::LEN);
+
+        let state = GlobalState::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!(state.version, GLOBAL_STATE_VERSION);
+        assert_eq!((state.authority, state.dev_treasury), (authority, treasury));
+        assert_eq!(state.cumulative_bombcoin_per_power, 123_456_789);
+        assert_eq!(state.last_reward_time, 5_000, "accumulator starts at migration");
+        assert_eq!(state.total_emitted, state.total_mined);
+        assert_eq!(state.max_supply, MAX_BOMBCOIN_SUPPLY);
+        assert_eq!(state.reward_pool, 700);
+        assert_eq!(state.expected_token_supply(), 4_000);
+        assert_eq!(state.bump, 254);
+
+        assert_eq!(
+            upgrade_global_state(&data, 6_000, 0).unwrap_err(),
+            error!(GameError::AlreadyMigrated)
+        );
+    }
+
+    #[test]
+    fn test_migrate_global_state_unversioned_blob() {
+        // Accumulator layout before the version byte (v2), written field by field
+        let authority = Pubkey::new_unique();
+        let mut blob = GlobalState::DISCRIMINATOR.to_vec();
+        (authority, Pubkey::new_unique(), Pubkey::new_unique()).serialize(&mut blob).unwrap();
+        // use_randomness_feed set, so the byte at the version offset reads 1
+        (true, true, false, true, true, 1_000i64, 7u64, 42u64, 500u64).serialize(&mut blob).unwrap();
+        (123_456_789u128, 4_000i64, 250_000_000u64, 1_000u64, 3_600u64).serialize(&mut blob).unwrap();
+        (EmissionSchedule::Time, 5_000u16, 250u16, [100u16, 50]).serialize(&mut blob).unwrap();
+        (1_000_000u64, MAX_BOMBCOIN_SUPPLY, 4u8, 2_500u16, 300u64, 400u16).serialize(&mut blob).unwrap();
+        (9_500u64, 9_000u64, 300u64, 700u64, 253u8).serialize(&mut blob).unwrap();
+        // No version byte, no hero price / house tables, no token or credit counters
+        // and no NFT base URI yet
+        let tables_len = 8 + 1 + HouseLevelConfig::LEN * MAX_HOUSE_LEVEL;
+        assert_eq!(blob.len(), GlobalState::LEN - 1 - tables_len - 8 * 4 - MAX_HERO_NFT_BASE_URI_LEN);
+        assert_eq!(global_state_version(&blob).unwrap(), 2);
+
+        let (from_version, data) = upgrade_global_state(&blob, 5_000, 4_000).unwrap();
+        assert_eq!(from_version, 2);
+        assert_eq!(data.len(), GlobalState::LEN);
+
+        let state = GlobalState::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!(state.version, GLOBAL_STATE_VERSION);
+        assert_eq!(state.authority, authority);
+        assert!(state.use_randomness_feed);
+        assert!(!state.paused);
+        assert_eq!(state.last_reward_time, 4_000, "accumulator state is kept");
+        assert_eq!(state.emission_schedule, EmissionSchedule::Time);
+        assert_eq!(state.referral_tier_fees(), [250, 100, 50]);
+        assert_eq!((state.fusion_hero_count, state.fusion_success_bps), (4, 2_500));
+        assert_eq!(state.marketplace_fee_bps, 400);
+        assert_eq!((state.hero_price, state.max_hero_quantity), (100, 10));
+        assert_eq!(state.house_levels, DEFAULT_HOUSE_LEVELS);
+        assert_eq!(state.nft_base_uri(), "");
+        assert_eq!((state.total_emitted, state.total_mined), (9_500, 9_000));
+        assert_eq!((state.total_burned, state.reward_pool), (300, 700));
+        assert_eq!(state.total_tokens_minted, 4_000, "circulating supply counts as minted");
+        assert_eq!((state.total_tokens_burned, state.total_tokens_deposited), (0, 0));
+        assert_eq!(state.expected_token_supply(), 4_000);
+        assert_eq!(state.total_coins_credited, 0);
+        assert_eq!(state.bump, 253);
+
+        // Layouts newer than this program are refused
+        let mut future = data.clone();
+        future[GlobalState::VERSION_OFFSET] = GLOBAL_STATE_VERSION + 1;
+        assert_eq!(
+            upgrade_global_state(&future, 0, 0).unwrap_err(),
+            error!(GameError::UnknownAccountVersion)
+        );
+    }
+
+    #[test]
+    fn test_migrate_user_account_v2_pending_mint() {
+        // A v2 account is the current layout without the pending mint's nonce
+        const NONCE: u64 = 0xA5A5_A5A5_A5A5_A5A5;
+        let mut user = test_user_account();
+        user.inventory = vec![test_hero(9)];
+        user.pending_mint = Some(PendingMint {
+            quantity: 2,
+            target_slot: 400,
+            use_feed: false,
+            feed_sequence: 0,
+            fuse_into: None,
+            fusion_success_bps: 0,
+            nonce: NONCE,
+        });
+        let mut blob = Vec::new();
+        user.try_serialize(&mut blob).unwrap();
+        let at = blob.windows(8).position(|w| w == NONCE.to_le_bytes()).unwrap();
+        blob.drain(at..at + 8);
+        blob[UserAccount::VERSION_OFFSET] = 2;
+        assert_eq!(user_account_version(&blob).unwrap(), 2);
+
+        let (from_version, data) = upgrade_user_account(&blob).unwrap();
+        assert_eq!(from_version, 2);
+        let migrated = UserAccount::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!(migrated.version, USER_ACCOUNT_VERSION);
+        let pending = migrated.pending_mint.unwrap();
+        assert_eq!((pending.quantity, pending.target_slot, pending.nonce), (2, 400, 0));
+        assert_eq!((migrated.next_hero_id, migrated.inventory.len()), (5, 1));
+        assert_eq!(migrated.active_map, user.active_map);
+
+        // Fusion commitments carry the target rarity before the nonce slot
+        user.pending_mint = Some(PendingMint { fuse_into: Some(HeroRarity::Epic), nonce: NONCE, ..pending });
+        let mut blob = Vec::new();
+        user.try_serialize(&mut blob).unwrap();
+        let at = blob.windows(8).position(|w| w == NONCE.to_le_bytes()).unwrap();
+        blob.drain(at..at + 8);
+        blob[UserAccount::VERSION_OFFSET] = 2;
+        let (_, data) = upgrade_user_account(&blob).unwrap();
+        let pending = UserAccount::try_deserialize(&mut &data[..]).unwrap().pending_mint.unwrap();
+        assert_eq!((pending.fuse_into, pending.nonce), (Some(HeroRarity::Epic), 0));
+
+        // Without a pending mint the account still grows by the reserved nonce
+        user.pending_mint = None;
+        let mut blob = Vec::new();
+        user.try_serialize(&mut blob).unwrap();
+        blob[UserAccount::VERSION_OFFSET] = 2;
+        let (_, data) = upgrade_user_account(&blob).unwrap();
+        assert_eq!(data.len(), blob.len() + 8);
+        assert_eq!(UserAccount::try_deserialize(&mut &data[..]).unwrap().pending_mint, None);
+    }
+
+    #[test]
+    fn test_migrate_user_account_v1_blob() {
+        // Layout of the original deployment, written field by field into the
+        // fixed space those accounts were created with
+        let owner = Pubkey::new_unique();
+        let referrer = Pubkey::new_unique();
+        let hero_v1 = |id: u16, rarity: HeroRarity| {
+            (id, 3u8, rarity, 20u32, 15u32, 12u32, 12u32, 2u8, 3u8, 80u32, 94u32, 1_500i64)
+        };
+        let tile = HouseTile { x: 2, y: 1, hero_id: 1, is_restroom: true };
+        let empty = HouseTile::empty(0, 0);
+        let mut blob = UserAccount::DISCRIMINATOR.to_vec();
+        (owner, true, 2u8, 1_700i64, 5u8, 4u8, vec![tile, empty]).serialize(&mut blob).unwrap();
+        vec![hero_v1(0, HeroRarity::Common), hero_v1(1, HeroRarity::Epic), hero_v1(2, HeroRarity::Rare)]
+            .serialize(&mut blob)
+            .unwrap();
+        // Map heroes are inventory positions; 7 does not exist
+        (vec![2u16, 7], 1_250u64, 90u64, 15u64, 1_600i64, 77_000u128).serialize(&mut blob).unwrap();
+        (Some(referrer), 40u64, vec![Pubkey::new_unique()], 251u8).serialize(&mut blob).unwrap();
+        blob.resize(UserAccountV1::LEN, 0);
+        assert_eq!(user_account_version(&blob).unwrap(), 1);
+
+        let (from_version, data) = upgrade_user_account(&blob).unwrap();
+        assert_eq!(from_version, 1);
+        assert_eq!(data.len(), UserAccountV1::LEN, "the account keeps its space");
+
+        let user = UserAccount::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!(user.version, USER_ACCOUNT_VERSION);
+        assert_eq!(user.owner, owner);
+        assert!(user.initialized_starter_house);
+        assert_eq!((user.house_level, user.grid_width, user.grid_height), (2, 5, 4));
+        assert_eq!(user.house_occupied_coords, vec![tile, empty]);
+        assert_eq!(user.inventory.len(), 3);
+        let epic = &user.inventory[1];
+        assert_eq!((epic.id, epic.rarity, epic.max_hp), (1, HeroRarity::Epic, 94));
+        assert_eq!((epic.level, epic.xp), (1, 0));
+        assert_eq!(user.active_map, vec![2]);
+        assert_eq!(user.pending_mint, None);
+        assert_eq!((user.next_hero_id, user.hero_count), (3, 0));
+        assert_eq!((user.coin_balance, user.player_power), (1_250, 90));
+        assert_eq!((user.player_pending_rewards, user.reward_debt), (15, 77_000));
+        assert_eq!(user.referrer, Some(referrer));
+        assert_eq!(user.referral_earnings_by_tier, [40, 0, 0]);
+        assert_eq!(user.referrals.len(), 1);
+        assert_eq!(user.bump, 251);
+        assert!(data.len() >= user.required_len());
+
+        assert_eq!(
+            upgrade_user_account(&data).unwrap_err(),
+            error!(GameError::AlreadyMigrated)
+        );
+
+        // A player without a house stored `false` at the version offset
+        blob[UserAccount::VERSION_OFFSET] = 0;
+        assert_eq!(user_account_version(&blob).unwrap(), 1);
+
+        // Other accounts are not mistaken for user accounts
+        assert_eq!(
+            upgrade_user_account(&data[..8]).unwrap_err(),
+            error!(GameError::InvalidOwner)
+        );
+        let mut global = GlobalState::DISCRIMINATOR.to_vec();
+        global.extend_from_slice(&data[8..]);
+        assert!(upgrade_user_account(&global).is_err());
     }
 }
@@ -0,0 +1,522 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::GameError;
+use crate::state::*;
+use crate::utils::*;
+
+/// Zero-copy snapshot of the `GlobalState` account
+///
+/// Seeds: [b"global_state_zc", owner]. Packed in the Borsh field order, so
+/// `copy_from` takes the live account's bytes as they are; booleans and the
+/// emission schedule are kept as their Borsh byte. The benchmarks only ever
+/// write to this snapshot, never to the live account.
+#[account(zero_copy(unsafe))]
+pub struct GlobalStateZc {
+    pub authority: Pubkey,
+    pub dev_treasury: Pubkey,
+    pub reward_token_mint: Pubkey,
+    pub version: u8,
+    pub use_randomness_feed: u8,
+    pub game_has_started: u8,
+    pub paused: u8,
+    pub minting_enabled: u8,
+    pub house_upgrades_enabled: u8,
+    pub start_block: i64,
+    pub house_count: u64,
+    pub unique_heroes_count: u64,
+    pub total_hash_power: u64,
+    pub cumulative_bombcoin_per_power: u128,
+    pub last_reward_time: i64,
+    pub initial_house_price: u64,
+    pub initial_bombcoin_per_block: u64,
+    pub halving_interval: u64,
+    pub emission_schedule: u8,
+    pub burn_pct: u16,
+    pub referral_fee: u16,
+    pub upline_referral_fees: [u16; MAX_REFERRAL_TIERS - 1],
+    pub rewards_precision: u64,
+    pub max_supply: u64,
+    pub fusion_hero_count: u8,
+    pub fusion_success_bps: u16,
+    pub fusion_cost: u64,
+    pub marketplace_fee_bps: u16,
+    pub hero_price: u64,
+    pub max_hero_quantity: u8,
+    pub house_levels: [HouseLevelZc; MAX_HOUSE_LEVEL],
+    pub hero_nft_base_uri: [u8; MAX_HERO_NFT_BASE_URI_LEN],
+    pub total_emitted: u64,
+    pub total_mined: u64,
+    pub total_burned: u64,
+    pub reward_pool: u64,
+    pub total_tokens_minted: u64,
+    pub total_tokens_burned: u64,
+    pub total_tokens_deposited: u64,
+    pub total_coins_credited: u64,
+    pub bump: u8,
+}
+
+impl GlobalStateZc {
+    pub const LEN: usize = 8 + std::mem::size_of::<GlobalStateZc>();
+
+    /// Copy the live global state (fails if the layouts have drifted apart)
+    pub fn copy_from(&mut self, global_state: &GlobalState, bump: u8) -> Result<()> {
+        let bytes = global_state.try_to_vec()?;
+        *self = *anchor_lang::__private::bytemuck::try_from_bytes(&bytes)
+            .map_err(|_| error!(ErrorCode::AccountDidNotSerialize))?;
+        self.bump = bump;
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused != 0
+    }
+
+    pub fn emission_schedule(&self) -> EmissionSchedule {
+        if self.emission_schedule == EmissionSchedule::Time as u8 {
+            EmissionSchedule::Time
+        } else {
+            EmissionSchedule::Supply
+        }
+    }
+
+    /// Referral fee for every tier, direct referrer first
+    pub fn referral_tier_fees(&self) -> [u16; MAX_REFERRAL_TIERS] {
+        let upline_fees = self.upline_referral_fees;
+        let mut fees = [0u16; MAX_REFERRAL_TIERS];
+        fees[0] = self.referral_fee;
+        fees[1..].copy_from_slice(&upline_fees);
+        fees
+    }
+
+    /// BOMBcoin that can still be minted before the cap is reached
+    pub fn remaining_supply(&self) -> u64 {
+        self.max_supply
+            .saturating_sub(self.total_mined)
+            .saturating_sub(self.total_coins_credited)
+    }
+
+    /// Same accumulator update as `update_pool` on the Borsh account
+    pub fn update_pool(&mut self, current_time: i64) -> Result<()> {
+        if self.start_block == 0 || current_time <= self.last_reward_time {
+            return Ok(());
+        }
+
+        let last_reward_time = self.last_reward_time.max(self.start_block);
+        if self.total_hash_power > 0 && current_time > last_reward_time {
+            let emitted = calculate_emission(
+                self.emission_schedule(),
+                self.initial_bombcoin_per_block,
+                self.halving_interval,
+                self.start_block,
+                self.total_emitted,
+                last_reward_time,
+                current_time,
+            )?;
+            let emitted = cap_emission(
+                emitted,
+                self.total_emitted.saturating_add(self.total_coins_credited),
+                self.max_supply,
+            );
+            let delta = reward_per_power_delta(emitted, self.total_hash_power, self.rewards_precision)?;
+
+            self.cumulative_bombcoin_per_power = self.cumulative_bombcoin_per_power
+                .checked_add(delta)
+                .ok_or(GameError::ArithmeticOverflow)?;
+            self.total_emitted = self.total_emitted
+                .checked_add(emitted)
+                .ok_or(GameError::ArithmeticOverflow)?;
+        }
+        self.last_reward_time = current_time;
+
+        Ok(())
+    }
+}
+
+/// `HouseLevelConfig` as stored in `GlobalStateZc`
+#[zero_copy(unsafe)]
+pub struct HouseLevelZc {
+    pub grid_width: u8,
+    pub grid_height: u8,
+    pub max_restroom_slots: u8,
+    pub upgrade_cost: u64,
+    pub upgrade_cooldown: i64,
+}
+
+/// Hero as stored in a zero-copy hero page (`Hero` with the rarity as its index)
+#[zero_copy(unsafe)]
+pub struct HeroZc {
+    pub id: u16,
+    pub skin_id: u8,
+    pub rarity: u8,
+    pub power: u32,
+    pub speed: u32,
+    pub stamina: u32,
+    pub max_stamina: u32,
+    pub bomb_number: u8,
+    pub bomb_range: u8,
+    pub hp: u32,
+    pub max_hp: u32,
+    pub last_action_time: i64,
+    pub level: u8,
+    pub xp: u64,
+}
+
+impl HeroZc {
+    pub fn from_hero(hero: &Hero) -> Self {
+        Self {
+            id: hero.id,
+            skin_id: hero.skin_id,
+            rarity: hero.rarity as u8,
+            power: hero.power,
+            speed: hero.speed,
+            stamina: hero.stamina,
+            max_stamina: hero.max_stamina,
+            bomb_number: hero.bomb_number,
+            bomb_range: hero.bomb_range,
+            hp: hero.hp,
+            max_hp: hero.max_hp,
+            last_action_time: hero.last_action_time,
+            level: hero.level,
+            xp: hero.xp,
+        }
+    }
+
+    pub fn to_hero(&self) -> Hero {
+        let rarity = match self.rarity {
+            1 => HeroRarity::Uncommon,
+            2 => HeroRarity::Rare,
+            3 => HeroRarity::SuperRare,
+            4 => HeroRarity::Epic,
+            5 => HeroRarity::Legendary,
+            _ => HeroRarity::Common,
+        };
+
+        Hero {
+            id: self.id,
+            skin_id: self.skin_id,
+            rarity,
+            power: self.power,
+            speed: self.speed,
+            stamina: self.stamina,
+            max_stamina: self.max_stamina,
+            bomb_number: self.bomb_number,
+            bomb_range: self.bomb_range,
+            hp: self.hp,
+            max_hp: self.max_hp,
+            last_action_time: self.last_action_time,
+            level: self.level,
+            xp: self.xp,
+        }
+    }
+}
+
+/// Zero-copy counterpart of `HeroPage`: a flat hero array with an occupancy mask
+///
+/// Seeds: [b"hero_page_zc", owner, page index]. Slots follow the same
+/// `id / HEROES_PER_PAGE`, `id % HEROES_PER_PAGE` addressing as `HeroPage`.
+#[account(zero_copy(unsafe))]
+pub struct HeroPageZc {
+    /// Owner wallet
+    pub owner: Pubkey,
+
+    /// Page index (first hero ID on the page / HEROES_PER_PAGE)
+    pub index: u16,
+
+    /// Bit `slot` is set when that slot holds a hero
+    pub occupied: u32,
+
+    /// Hero slots, indexed by hero ID within the page
+    pub heroes: [HeroZc; HEROES_PER_PAGE],
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl HeroPageZc {
+    pub const LEN: usize = 8 + std::mem::size_of::<HeroPageZc>();
+
+    /// Slot holding this hero ID, if it is on this page
+    fn slot(&self, hero_id: u16) -> Option<usize> {
+        if HeroPage::index_of(hero_id) != self.index {
+            return None;
+        }
+        let slot = hero_id as usize % HEROES_PER_PAGE;
+        (self.occupied & (1 << slot) != 0).then_some(slot)
+    }
+
+    /// Hero with this ID, if it is on this page
+    pub fn hero(&self, hero_id: u16) -> Option<Hero> {
+        self.slot(hero_id).map(|slot| self.heroes[slot].to_hero())
+    }
+
+    /// Apply `f` to the hero with this ID and store the result
+    pub fn update_hero<R>(&mut self, hero_id: u16, f: impl FnOnce(&mut Hero) -> R) -> Option<R> {
+        let slot = self.slot(hero_id)?;
+        let mut hero = self.heroes[slot].to_hero();
+        let result = f(&mut hero);
+        self.heroes[slot] = HeroZc::from_hero(&hero);
+        Some(result)
+    }
+
+    /// Put a hero into its slot; false if it belongs to another page or the slot is taken
+    pub fn insert(&mut self, hero: &Hero) -> bool {
+        if HeroPage::index_of(hero.id) != self.index || self.slot(hero.id).is_some() {
+            return false;
+        }
+        let slot = hero.id as usize % HEROES_PER_PAGE;
+        self.heroes[slot] = HeroZc::from_hero(hero);
+        self.occupied |= 1 << slot;
+        true
+    }
+}
+
+/// Widest house grid (level 6); packed tiles are indexed `y * MAX_GRID_WIDTH + x`
+pub const MAX_GRID_WIDTH: usize = 7;
+
+/// Zero-copy counterpart of the per-player state the mining hot paths touch
+///
+/// Seeds: [b"user_account_zc", owner]. Grid tiles are packed into a fixed
+/// array of hero IDs (u16::MAX = empty) with a restroom bitmask, and the map
+/// is a fixed array with a length. Mint commitments, the legacy inventory and
+/// the referral list stay in `UserAccount`.
+#[account(zero_copy(unsafe))]
+pub struct UserAccountZc {
+    /// Owner wallet
+    pub owner: Pubkey,
+
+    // ========== House System ==========
+    pub house_level: u8,
+    pub grid_width: u8,
+    pub grid_height: u8,
+
+    /// Hero ID on each tile (u16::MAX if empty)
+    pub tiles: [u16; UserAccount::MAX_GRID_TILES],
+
+    /// Bit `tile` is set when that tile is a restroom
+    pub restroom_mask: u64,
+
+    // ========== Heroes ==========
+    /// Mining hero IDs; the first `active_map_len` entries are in use
+    pub active_map: [u16; UserAccount::MAX_MAP_HEROES],
+    pub active_map_len: u8,
+    pub next_hero_id: u16,
+    pub hero_count: u16,
+
+    // ========== Economy ==========
+    pub coin_balance: u64,
+    pub player_power: u64,
+    pub player_pending_rewards: u64,
+    pub last_reward_block: i64,
+    pub reward_debt: u128,
+
+    // ========== Referral System ==========
+    /// Referrer wallet (default pubkey when unset)
+    pub referrer: Pubkey,
+    pub referral_bonus_paid: u64,
+    pub referral_earnings_by_tier: [u64; MAX_REFERRAL_TIERS],
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl UserAccountZc {
+    pub const LEN: usize = 8 + std::mem::size_of::<UserAccountZc>();
+
+    /// Packed tile index of a grid coordinate
+    pub fn tile_index(x: u8, y: u8) -> usize {
+        y as usize * MAX_GRID_WIDTH + x as usize
+    }
+
+    /// Copy the hot-path state of a Borsh user account
+    pub fn copy_from(&mut self, user_account: &UserAccount, bump: u8) {
+        let mut tiles = [u16::MAX; UserAccount::MAX_GRID_TILES];
+        let mut restroom_mask = 0u64;
+        for tile in user_account.house_occupied_coords.iter() {
+            let index = Self::tile_index(tile.x, tile.y);
+            tiles[index] = tile.hero_id;
+            if tile.is_restroom {
+                restroom_mask |= 1 << index;
+            }
+        }
+
+        let mut active_map = [0u16; UserAccount::MAX_MAP_HEROES];
+        active_map[..user_account.active_map.len()].copy_from_slice(&user_account.active_map);
+
+        self.owner = user_account.owner;
+        self.house_level = user_account.house_level;
+        self.grid_width = user_account.grid_width;
+        self.grid_height = user_account.grid_height;
+        self.tiles = tiles;
+        self.restroom_mask = restroom_mask;
+        self.active_map = active_map;
+        self.active_map_len = user_account.active_map.len() as u8;
+        self.next_hero_id = user_account.next_hero_id;
+        self.hero_count = user_account.hero_count;
+        self.coin_balance = user_account.coin_balance;
+        self.player_power = user_account.player_power;
+        self.player_pending_rewards = user_account.player_pending_rewards;
+        self.last_reward_block = user_account.last_reward_block;
+        self.reward_debt = user_account.reward_debt;
+        self.referrer = user_account.referrer.unwrap_or_default();
+        self.referral_bonus_paid = user_account.referral_bonus_paid;
+        self.referral_earnings_by_tier = user_account.referral_earnings_by_tier;
+        self.bump = bump;
+    }
+
+    /// Mining hero IDs
+    pub fn active_map(&self) -> impl Iterator<Item = u16> {
+        let map = self.active_map;
+        map.into_iter().take(self.active_map_len as usize)
+    }
+
+    pub fn is_mining(&self, hero_id: u16) -> bool {
+        self.active_map().any(|id| id == hero_id)
+    }
+
+    /// Append a hero to the map; false when the map is full
+    pub fn push_to_map(&mut self, hero_id: u16) -> bool {
+        let len = self.active_map_len as usize;
+        if len >= UserAccount::MAX_MAP_HEROES {
+            return false;
+        }
+        let mut map = self.active_map;
+        map[len] = hero_id;
+        self.active_map = map;
+        self.active_map_len += 1;
+        true
+    }
+
+    /// Clear the tile holding this hero, if any
+    pub fn remove_from_grid(&mut self, hero_id: u16) {
+        let mut tiles = self.tiles;
+        if let Some(tile) = tiles.iter_mut().find(|tile| **tile == hero_id) {
+            *tile = u16::MAX;
+            self.tiles = tiles;
+        }
+    }
+
+    /// Occupied tiles as (tile index, hero ID, is restroom)
+    pub fn occupied_tiles(&self) -> impl Iterator<Item = (usize, u16, bool)> {
+        let tiles = self.tiles;
+        let restroom_mask = self.restroom_mask;
+        tiles
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, hero_id)| hero_id != u16::MAX)
+            .map(move |(index, hero_id)| (index, hero_id, restroom_mask & (1 << index) != 0))
+    }
+
+    /// Same harvest as `harvest_pending_rewards` on the Borsh accounts
+    pub fn harvest(&mut self, global_state: &GlobalStateZc) -> Result<u64> {
+        if self.player_power == 0 {
+            return Ok(0);
+        }
+
+        let acc_per_power = global_state.cumulative_bombcoin_per_power;
+        let pending = calculate_pending_reward(
+            self.player_power,
+            acc_per_power,
+            self.reward_debt,
+            global_state.rewards_precision,
+        )?;
+
+        if pending > 0 {
+            self.player_pending_rewards = self.player_pending_rewards
+                .checked_add(pending)
+                .ok_or(GameError::ArithmeticOverflow)?;
+            self.reward_debt = (self.player_power as u128)
+                .checked_mul(acc_per_power)
+                .ok_or(GameError::ArithmeticOverflow)?;
+        }
+
+        Ok(pending)
+    }
+
+    /// Same power sync as `sync_player_power` on the Borsh accounts
+    /// Returns (old_power, new_power)
+    pub fn sync_power(&mut self, global_state: &mut GlobalStateZc, new_power: u64) -> Result<(u64, u64)> {
+        let old_power = self.player_power;
+
+        global_state.total_hash_power = global_state.total_hash_power
+            .saturating_sub(old_power)
+            .checked_add(new_power)
+            .ok_or(GameError::ArithmeticOverflow)?;
+        self.player_power = new_power;
+        self.reward_debt = (new_power as u128)
+            .checked_mul(global_state.cumulative_bombcoin_per_power)
+            .ok_or(GameError::ArithmeticOverflow)?;
+
+        Ok((old_power, new_power))
+    }
+}
+
+/// A player's zero-copy hero pages, loaded from an instruction's remaining accounts
+/// Heroes are read and written in place, so there is nothing to persist on exit.
+pub struct HeroPagesZc<'info> {
+    pages: Vec<AccountLoader<'info, HeroPageZc>>,
+    indexes: Vec<u16>,
+}
+
+impl<'info> HeroPagesZc<'info> {
+    /// Load `owner`'s zero-copy pages; pages of other players are skipped
+    pub fn load_for(owner: Pubkey, accounts: &'info [AccountInfo<'info>]) -> Result<Self> {
+        let mut pages = Vec::with_capacity(accounts.len());
+        let mut indexes = Vec::with_capacity(accounts.len());
+        for info in accounts.iter() {
+            let page = AccountLoader::<HeroPageZc>::try_from(info)?;
+            let (page_owner, index) = {
+                let data = page.load()?;
+                (data.owner, data.index)
+            };
+            if page_owner == owner {
+                require!(!indexes.contains(&index), GameError::InvalidHeroPage);
+                pages.push(page);
+                indexes.push(index);
+            }
+        }
+        Ok(Self { pages, indexes })
+    }
+
+    fn page(&self, hero_id: u16) -> Result<&AccountLoader<'info, HeroPageZc>> {
+        let index = HeroPage::index_of(hero_id);
+        self.indexes
+            .iter()
+            .position(|&i| i == index)
+            .map(|pos| &self.pages[pos])
+            .ok_or_else(|| error!(GameError::HeroPageMissing))
+    }
+
+    /// Whether the page that holds this hero ID was passed in
+    pub fn has_page_for(&self, hero_id: u16) -> bool {
+        self.page(hero_id).is_ok()
+    }
+
+    /// Hero with this ID
+    pub fn hero(&self, hero_id: u16) -> Result<Hero> {
+        self.page(hero_id)?
+            .load()?
+            .hero(hero_id)
+            .ok_or_else(|| error!(GameError::HeroNotInInventory))
+    }
+
+    /// Apply `f` to the hero with this ID in place
+    pub fn update_hero<R>(&self, hero_id: u16, f: impl FnOnce(&mut Hero) -> R) -> Result<R> {
+        self.page(hero_id)?
+            .load_mut()?
+            .update_hero(hero_id, f)
+            .ok_or_else(|| error!(GameError::HeroNotInInventory))
+    }
+
+    /// Mining power (whole HMP) of mining heroes that still have HP
+    /// Every mining hero's page is required
+    pub fn map_power(&self, active_map: impl Iterator<Item = u16>) -> Result<u64> {
+        let mut total_hmp = 0u64;
+        for hero_id in active_map {
+            let hero = self.hero(hero_id)?;
+            if hero.is_active() {
+                total_hmp += hero.calculate_hmp();
+            }
+        }
+        Ok(mining_power(total_hmp))
+    }
+}